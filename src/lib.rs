@@ -1,18 +1,20 @@
 use regex::Regex;
+use std::collections::HashMap;
 use std::error::Error;
+use std::sync::LazyLock;
 //use std::fs::File;
 
 ///////////////
 ///TOKENISER///
 ///////////////
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Bracket {
     Curly,
     Square,
     Paren,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Symbol {
     Equals,
     Plus,
@@ -27,6 +29,12 @@ pub enum Symbol {
     Exclamation,
     DoubleDot,
     Colon,
+    Pipe,
+    At,
+    Ampersand,
+    Question,
+    Hash,
+    Caret,
 }
 #[derive(Debug, Clone)]
 pub enum Token {
@@ -35,6 +43,8 @@ pub enum Token {
     Number(String),
     String(String),
     Char(String),
+    // A character literal like `'a'`
+    Character(String),
     Symbol(Symbol),
 }
 
@@ -72,10 +82,20 @@ pub fn tokenise(input: String) -> Result<Vec<Token>, String> {
             i += 1;
             curr_char = chars[i];
 
-            while is_number(&curr_char.to_string()) {
+            // Suffixes and separators belong to the literal, `1_000u64`
+            while is_number(&curr_char.to_string()) || is_letter(&curr_char.to_string()) {
                 num.push(curr_char);
                 i += 1;
                 curr_char = chars[i];
+                // The sign of an exponent, `1e-5`
+                if (num.ends_with(['e', 'E']) && !num.starts_with("0x"))
+                    && (curr_char == '-' || curr_char == '+')
+                    && chars[i + 1].is_ascii_digit()
+                {
+                    num.push(curr_char);
+                    i += 1;
+                    curr_char = chars[i];
+                }
             }
 
             tokens.push(Token::Number(num));
@@ -90,9 +110,12 @@ pub fn tokenise(input: String) -> Result<Vec<Token>, String> {
             while curr_char != '\"' {
                 //Deal with escaped chars
                 if curr_char == '\\' {
+                    i += 1;
+                    if let Some(c) = unescape(&chars, &mut i)? {
+                        str.push(c);
+                    }
                     i += 1;
                     curr_char = chars[i];
-                    str.push(curr_char);
                     continue;
                 }
 
@@ -106,6 +129,35 @@ pub fn tokenise(input: String) -> Result<Vec<Token>, String> {
             continue;
         }
 
+        // Character literals, a quote that is not closed after one character starts a lifetime
+        if curr_char == '\'' {
+            let mut end = i + 1;
+            let c = match chars.get(end) {
+                Some('\\') => {
+                    end += 1;
+                    unescape(&chars, &mut end)?
+                }
+                c => c.copied(),
+            };
+            if let (Some(c), Some('\'')) = (c, chars.get(end + 1)) {
+                tokens.push(Token::Character(c.to_string()));
+                i = end + 2;
+                continue;
+            }
+            // Lifetimes have no meaning in the output, `<'a, T>` loses the comma too
+            i += 1;
+            while chars.get(i).is_some_and(|c| is_letter(&c.to_string())) {
+                i += 1;
+            }
+            while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+                i += 1;
+            }
+            if chars.get(i) == Some(&',') {
+                i += 1;
+            }
+            continue;
+        }
+
         //Deal with single characters
         match curr_char {
             '=' => {
@@ -173,6 +225,36 @@ pub fn tokenise(input: String) -> Result<Vec<Token>, String> {
                 i += 1;
                 continue;
             }
+            '|' => {
+                tokens.push(Token::Symbol(Symbol::Pipe));
+                i += 1;
+                continue;
+            }
+            '@' => {
+                tokens.push(Token::Symbol(Symbol::At));
+                i += 1;
+                continue;
+            }
+            '&' => {
+                tokens.push(Token::Symbol(Symbol::Ampersand));
+                i += 1;
+                continue;
+            }
+            '?' => {
+                tokens.push(Token::Symbol(Symbol::Question));
+                i += 1;
+                continue;
+            }
+            '#' => {
+                tokens.push(Token::Symbol(Symbol::Hash));
+                i += 1;
+                continue;
+            }
+            '^' => {
+                tokens.push(Token::Symbol(Symbol::Caret));
+                i += 1;
+                continue;
+            }
             '{' => {
                 tokens.push(Token::BracketOpen(Bracket::Curly));
                 i += 1;
@@ -211,64 +293,357 @@ pub fn tokenise(input: String) -> Result<Vec<Token>, String> {
     Ok(tokens)
 }
 
-fn is_number(str: &String) -> bool {
-    let numbers: Regex = Regex::new(r"[0-9]").unwrap();
+// Compiled once, the tokeniser tests every character against them
+static NUMBERS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[0-9]").unwrap());
+static LETTERS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[a-zA-Z_]").unwrap());
+static WHITESPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s").unwrap());
 
-    numbers.is_match(str)
+fn is_number(str: &str) -> bool {
+    NUMBERS.is_match(str)
 }
 
-fn is_letter(str: &String) -> bool {
-    let letters: Regex = Regex::new(r"[a-zA-Z]").unwrap();
-
-    letters.is_match(str)
+fn is_letter(str: &str) -> bool {
+    LETTERS.is_match(str)
 }
 
-fn is_whitespace(str: &String) -> bool {
-    let whitespace: Regex = Regex::new(r"\s").unwrap();
+// Reads the escape after a `\\`, `i` ends on its last character.
+// A line ending in `\\` continues after the next line's indentation
+fn unescape(chars: &[char], i: &mut usize) -> Result<Option<char>, String> {
+    Ok(Some(match chars.get(*i) {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('u') => {
+            // `\u{1F600}`
+            let end = (*i..chars.len()).find(|&n| chars[n] == '}').unwrap_or(*i);
+            let code: String = chars[*i + 1..end]
+                .iter()
+                .filter(|c| c.is_ascii_hexdigit())
+                .collect();
+            let c = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32);
+            *i = end;
+            c.ok_or_else(|| format!("Tokeniser String: Invalid escape at {i}"))?
+        }
+        Some('\n') => {
+            while chars.get(*i + 1).is_some_and(|c| c.is_whitespace()) {
+                *i += 1;
+            }
+            return Ok(None);
+        }
+        Some(&c) => c,
+        None => return Err(format!("Tokeniser String: Unterminated string at {i}")),
+    }))
+}
 
-    whitespace.is_match(str)
+fn is_whitespace(str: &str) -> bool {
+    WHITESPACE.is_match(str)
 }
 
 ////////////
 ///PARSER///
 ////////////
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    Lesser,
+    LesserEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl Operator {
+    /// How tightly the operator binds in Rust, higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Multiply | Operator::Divide | Operator::Modulo => 10,
+            Operator::Add | Operator::Subtract => 9,
+            Operator::ShiftLeft | Operator::ShiftRight => 8,
+            Operator::BitAnd => 7,
+            Operator::BitXor => 6,
+            Operator::BitOr => 5,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Lesser
+            | Operator::LesserEqual
+            | Operator::Greater
+            | Operator::GreaterEqual => 4,
+            Operator::And => 3,
+            Operator::Or => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Named { name: String, generics: Vec<Type> },
+    Tuple(Vec<Type>),
+    // The element type and the length when it is known
+    Array(Box<Type>, Option<usize>),
+    Unknown,
+}
+
+impl Type {
+    fn named(name: &str) -> Type {
+        Type::Named {
+            name: name.to_string(),
+            generics: Vec::new(),
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self {
+            Type::Named { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Fields {
+    Unit,
+    Tuple(Vec<Type>),
+    Named(Vec<(String, Type)>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Fields,
+    pub discriminant: Option<i64>,
+    // Marked `#[default]` for `#[derive(Default)]`
+    pub default: bool,
+}
+
+// A generic parameter with the traits it is bound by, from the `<...>` or a `where` clause
+#[derive(Debug, Clone)]
+pub struct Generic {
+    pub name: String,
+    pub bounds: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub ty: Type,
+    // `&mut` parameters, `&mut self` included
+    pub mutable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    // `name` or `name @ pattern`
+    Binding {
+        name: String,
+        pattern: Option<Box<Pattern>>,
+    },
+    Literal(Node),
+    Range {
+        start: Option<Node>,
+        end: Option<Node>,
+        inclusive: bool,
+    },
+    Or(Vec<Pattern>),
+    Tuple(Vec<Pattern>),
+    // `Point { x, y: 0, .. }` and `Shape::Rect { w, h }`
+    Struct {
+        name: String,
+        fields: Vec<(String, Pattern)>,
+    },
+    // `Shape::Circle(r)`
+    TupleStruct {
+        name: String,
+        fields: Vec<Pattern>,
+    },
+    // `Shape::Empty`
+    Path(String),
+    // `ref mut name`, which binds the matched place instead of a copy of it
+    RefMut(String),
+    Slice(Vec<Pattern>),
+    // The `..` of tuple and slice patterns
+    Rest,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<Node>,
+    pub body: Node,
+}
+
+#[derive(Debug, Clone)]
 pub enum Node {
     StringLiteral(String),
+    CharLiteral(String),
     NumberLiteral(String),
-    Symbol(Symbol),
-    BracketOpen(Bracket),
-    BracketClose(Bracket),
     NewLine,
     VeriableCall(String),
     Veriable {
         name: String,
-        value: Vec<Node>,
+        ty: Option<Type>,
+        value: Option<Box<Node>>,
     },
+    // `if let` and `while let` carry the pattern the condition is matched against
     IfStatement {
-        condition: Vec<Node>,
-        body: Vec<Node>,
-    },
-    ElseStatement {
+        condition: Box<Node>,
+        pattern: Option<Box<Pattern>>,
         body: Vec<Node>,
+        else_body: Option<Vec<Node>>,
     },
     ForLoop {
-        name: String,
-        start: String,
-        end: String,
+        pattern: Box<Pattern>,
+        iterable: Box<Node>,
         body: Vec<Node>,
     },
     WhileLoop {
-        condition: Vec<Node>,
+        condition: Box<Node>,
+        pattern: Option<Box<Pattern>>,
         body: Vec<Node>,
     },
+    // `let` with a pattern, `else_body` runs when it does not match
+    LetPattern {
+        pattern: Box<Pattern>,
+        value: Box<Node>,
+        else_body: Option<Vec<Node>>,
+    },
     Function {
         name: String,
-        input: Vec<Node>,
+        generics: Vec<Generic>,
+        input: Vec<Parameter>,
+        output: Option<Type>,
         body: Vec<Node>,
     },
     FunctionCall {
+        name: String,
+        // The turbofish, `max::<f64>(..)`
+        generics: Vec<Type>,
+        input: Vec<Node>,
+    },
+    // Calling what an expression evaluates to, `adder(1)(2)`
+    Call {
+        callee: Box<Node>,
+        input: Vec<Node>,
+    },
+    Enum {
+        name: String,
+        generics: Vec<Generic>,
+        variants: Vec<Variant>,
+        derives: Vec<String>,
+    },
+    Struct {
+        name: String,
+        generics: Vec<Generic>,
+        fields: Fields,
+        derives: Vec<String>,
+    },
+    // The methods of a trait, required ones have no body
+    Trait {
+        name: String,
+        supertraits: Vec<String>,
+        body: Vec<Node>,
+    },
+    // `impl Type` or `impl Trait for Type`
+    Impl {
+        name: String,
+        trait_name: Option<String>,
+        body: Vec<Node>,
+    },
+    // `type Name = Type;`, associated types included
+    TypeAlias {
+        name: String,
+        ty: Type,
+    },
+    Block(Vec<Node>),
+    Match {
+        expression: Box<Node>,
+        arms: Vec<Arm>,
+    },
+    Return(Option<Box<Node>>),
+    Break,
+    Continue,
+    Assignment {
+        target: Box<Node>,
+        value: Box<Node>,
+    },
+    // `a += b` and the other operators followed by `=`
+    CompoundAssignment {
+        target: Box<Node>,
+        operator: Operator,
+        value: Box<Node>,
+    },
+    Binary {
+        left: Box<Node>,
+        operator: Operator,
+        right: Box<Node>,
+    },
+    Unary {
+        operator: Symbol,
+        expression: Box<Node>,
+    },
+    Cast {
+        expression: Box<Node>,
+        ty: Type,
+    },
+    // `&mut value`, shared references are erased
+    MutableReference(Box<Node>),
+    // `*value`, only a `&mut` to a scalar is something to read through
+    Deref(Box<Node>),
+    FieldAccess {
+        expression: Box<Node>,
+        field: String,
+    },
+    MethodCall {
+        expression: Box<Node>,
+        method: String,
+        // The turbofish, `parse::<i32>()`
+        generics: Vec<Type>,
+        input: Vec<Node>,
+    },
+    Index {
+        expression: Box<Node>,
+        index: Box<Node>,
+    },
+    ArrayLiteral(Vec<Node>),
+    // `[value; count]`
+    ArrayRepeat {
+        value: Box<Node>,
+        count: Box<Node>,
+    },
+    TupleLiteral(Vec<Node>),
+    // The `?` operator
+    Try(Box<Node>),
+    Closure {
+        input: Vec<(Pattern, Option<Type>)>,
+        body: Box<Node>,
+        // `move` closures keep the values they capture
+        capture: bool,
+    },
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, Node)>,
+    },
+    Range {
+        start: Option<Box<Node>>,
+        end: Option<Box<Node>>,
+        inclusive: bool,
+    },
+    // `name!(...)`, named arguments are kept as assignments
+    Macro {
         name: String,
         input: Vec<Node>,
     },
@@ -279,19 +654,65 @@ pub struct Output {
     end_num: usize,
 }
 
-pub fn parser(start: usize, tokens: Vec<Token>) -> Result<Output, Box<dyn Error>> {
+pub fn parser(start: usize, tokens: &[Token]) -> Result<Output, Box<dyn Error>> {
     let mut ast: Vec<Node> = Vec::new();
     let mut i = start;
+    // The `#[derive(...)]` traits of the next struct or enum
+    let mut derives = Vec::new();
 
     while i < tokens.len() {
         let mut curr_token = &tokens[i];
 
+        // Other attributes like `#[allow(dead_code)]` do not change the output
+        if is_symbol(tokens, i, Symbol::Hash) {
+            let (attribute, end) = attribute(i, tokens)?;
+            if let [Token::Char(name), Token::BracketOpen(Bracket::Paren), list @ ..] =
+                &attribute[..]
+            {
+                if name == "derive" {
+                    // Only the last segment of `std::fmt::Debug` names the trait
+                    for (n, token) in list.iter().enumerate() {
+                        if let Token::Char(c) = token {
+                            if !matches!(list.get(n + 1), Some(Token::Symbol(Symbol::DoubleDot))) {
+                                derives.push(c.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            i = end;
+            continue;
+        }
+
         if let Token::Char(a) = curr_token {
+            // Visibility has no meaning in the output
+            if a == "pub" {
+                i += 1;
+                continue;
+            }
+
+            // Paths are resolved by their last segment, imports are not needed
+            if a == "use" {
+                while i < tokens.len() && !is_symbol(tokens, i, Symbol::SemiColon) {
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+
             if a == "let" {
-                let name;
-                let mut value = Vec::new();
                 i += 1;
+                if !is_simple_binding(tokens, i) {
+                    let (node, end) = let_pattern(i, tokens)?;
+                    ast.push(node);
+                    i = end;
+                    continue;
+                }
+                if is_keyword(tokens, i, "mut") {
+                    i += 1;
+                }
                 curr_token = &tokens[i];
+                let name;
                 if let Token::Char(b) = curr_token {
                     name = b.to_string();
                 } else {
@@ -301,54 +722,50 @@ pub fn parser(start: usize, tokens: Vec<Token>) -> Result<Output, Box<dyn Error>
                     )
                     .into());
                 }
-
                 i += 1;
-                curr_token = &tokens[i];
 
-                while !matches!(*curr_token, Token::Symbol(Symbol::SemiColon)) {
-                    match curr_token {
-                        Token::Char(n) => value.push(Node::VeriableCall(n.to_string())),
-                        Token::Number(n) => value.push(Node::NumberLiteral(n.to_string())),
-                        Token::String(s) => value.push(Node::NumberLiteral(s.to_string())),
-                        Token::Symbol(s) => value.push(Node::Symbol(*s)),
-                        Token::BracketOpen(b) => value.push(Node::BracketOpen(b.clone())),
-                        Token::BracketClose(b) => value.push(Node::BracketClose(b.clone())),
-                    }
-                    i += 1;
-                    curr_token = &tokens[i];
+                let mut ty = None;
+                if is_symbol(tokens, i, Symbol::DoubleDot) {
+                    let (annotation, end) = parse_type(i + 1, tokens)?;
+                    ty = Some(annotation);
+                    i = end;
+                }
+
+                let mut value = None;
+                if is_symbol(tokens, i, Symbol::Equals) {
+                    let (expression, end) = expression(i + 1, tokens, false)?;
+                    value = Some(Box::new(expression));
+                    i = end;
+                }
+
+                if !is_symbol(tokens, i, Symbol::SemiColon) {
+                    return Err(format!(
+                        "Parser Variable: Expected SemiColon got: {:?} at {i}",
+                        tokens.get(i)
+                    )
+                    .into());
                 }
-                ast.push(Node::Veriable { name, value });
+                ast.push(Node::Veriable { name, ty, value });
                 continue;
             }
 
-            if a == "if" {
+            if a == "while" {
                 // Create the condition
-                let mut condition: Vec<Node> = vec![];
-                i += 1;
-                curr_token = &tokens[i];
-
-                while !matches!(*curr_token, Token::BracketOpen(Bracket::Curly)) {
-                    match curr_token {
-                        Token::Number(n) => condition.push(Node::NumberLiteral(n.to_string())),
-                        Token::String(s) => condition.push(Node::StringLiteral(s.to_string())),
-                        Token::Char(c) => condition.push(Node::VeriableCall(c.to_string())),
-                        Token::Symbol(s) => condition.push(Node::Symbol(*s)),
-                        _ => {
-                            return Err(format!(
-                                "Parser If statement: Expected Char/String/Number got: {:?} at {i}",
-                                curr_token
-                            )
-                            .into())
-                        }
-                    }
-                    i += 1;
-                    curr_token = &tokens[i];
+                let (pattern, condition, end) = condition(i + 1, tokens)?;
+                i = end;
+                if !is_bracket_open(tokens, i, Bracket::Curly) {
+                    return Err(format!(
+                        "Parser While loop: Expected CurlyOpen got: {:?} at {i}",
+                        tokens.get(i)
+                    )
+                    .into());
                 }
                 i += 1;
                 // Creat the body of the statement
-                let result = parser(i, tokens.to_vec())?;
-                ast.push(Node::IfStatement {
-                    condition,
+                let result = parser(i, tokens)?;
+                ast.push(Node::WhileLoop {
+                    condition: Box::new(condition),
+                    pattern: pattern.map(Box::new),
                     body: result.node,
                 });
                 i = result.end_num + 1;
@@ -356,261 +773,312 @@ pub fn parser(start: usize, tokens: Vec<Token>) -> Result<Output, Box<dyn Error>
                 continue;
             }
 
-            if a == "else" {
-                i += 2;
+            if a == "for" {
+                let (pattern, end) = pattern(i + 1, tokens)?;
+                i = end;
+                if !matches!(tokens.get(i), Some(Token::Char(c)) if c == "in") {
+                    return Err(format!(
+                        "Parser For loop: Expected in got: {:?} at {i}",
+                        tokens.get(i)
+                    )
+                    .into());
+                }
+                let (iterable, end) = expression(i + 1, tokens, true)?;
+                i = end;
+                if !is_bracket_open(tokens, i, Bracket::Curly) {
+                    return Err(format!(
+                        "Parser For loop: Expected CurlyOpen got: {:?} at {i}",
+                        tokens.get(i)
+                    )
+                    .into());
+                }
+                i += 1;
                 // Creat the body of the statement
-                let result = parser(i, tokens.to_vec())?;
-                ast.push(Node::ElseStatement { body: result.node });
+                let result = parser(i, tokens)?;
+                ast.push(Node::ForLoop {
+                    pattern: Box::new(pattern),
+                    iterable: Box::new(iterable),
+                    body: result.node,
+                });
                 i = result.end_num + 1;
 
                 continue;
             }
 
-            if a == "while" {
-                // Create the condition
-                let mut condition: Vec<Node> = vec![];
+            if a == "fn" {
+                let (function, end) = function(i + 1, tokens)?;
+                ast.push(function);
+                i = end;
+                continue;
+            }
+
+            if a == "enum" {
                 i += 1;
                 curr_token = &tokens[i];
+                let name;
+                if let Token::Char(c) = curr_token {
+                    name = c.to_string();
+                } else {
+                    return Err(
+                        format!("Parser Enum: Expected Char got: {:?} at {i}", curr_token).into(),
+                    );
+                }
 
-                while !matches!(*curr_token, Token::BracketOpen(Bracket::Curly)) {
-                    match curr_token {
-                        Token::Number(n) => condition.push(Node::NumberLiteral(n.to_string())),
-                        Token::String(s) => condition.push(Node::StringLiteral(s.to_string())),
-                        Token::Char(c) => condition.push(Node::VeriableCall(c.to_string())),
-                        Token::Symbol(s) => condition.push(Node::Symbol(*s)),
+                i += 1;
+                let mut generics = Vec::new();
+                if is_symbol(tokens, i, Symbol::Lesser) {
+                    let (parameters, end) = generic_parameters(i, tokens)?;
+                    generics = parameters;
+                    i = end;
+                }
+                if is_keyword(tokens, i, "where") {
+                    i = where_clause(i + 1, tokens, &mut generics)?;
+                }
+                i += 1;
+                let mut variants = Vec::new();
+                let mut default = false;
+                while !is_bracket_close(tokens, i, Bracket::Curly) {
+                    if is_symbol(tokens, i, Symbol::Hash) {
+                        let (attribute, end) = attribute(i, tokens)?;
+                        default |= matches!(&attribute[..], [Token::Char(c)] if c == "default");
+                        i = end;
+                        continue;
+                    }
+                    curr_token = &tokens[i];
+                    let name = match curr_token {
+                        Token::Char(c) => c.to_string(),
                         _ => {
                             return Err(format!(
-                                "Parser While loop: Expected Char/String/Number got: {:?} at {i}",
+                                "Parser Enum: Expected Char/CurlyClose got: {:?} at {i}",
                                 curr_token
                             )
                             .into())
                         }
-                    }
+                    };
                     i += 1;
-                    curr_token = &tokens[i];
+
+                    let mut fields = Fields::Unit;
+                    if is_bracket_open(tokens, i, Bracket::Paren) {
+                        let (types, end) = tuple_fields(i + 1, tokens)?;
+                        fields = Fields::Tuple(types);
+                        i = end;
+                    } else if is_bracket_open(tokens, i, Bracket::Curly) {
+                        let (named, end) = named_fields(i + 1, tokens)?;
+                        fields = named;
+                        i = end;
+                    }
+
+                    let mut discriminant = None;
+                    if is_symbol(tokens, i, Symbol::Equals) {
+                        i += 1;
+                        let negative = is_symbol(tokens, i, Symbol::Minus);
+                        if negative {
+                            i += 1;
+                        }
+                        curr_token = &tokens[i];
+                        let value = match curr_token {
+                            Token::Number(n) => number_literal(n).0.parse::<i64>()?,
+                            _ => {
+                                return Err(format!(
+                                    "Parser Enum: Expected Number got: {:?} at {i}",
+                                    curr_token
+                                )
+                                .into())
+                            }
+                        };
+                        discriminant = Some(if negative { -value } else { value });
+                        i += 1;
+                    }
+
+                    variants.push(Variant {
+                        name,
+                        fields,
+                        discriminant,
+                        default: std::mem::take(&mut default),
+                    });
+                    if is_symbol(tokens, i, Symbol::Colon) {
+                        i += 1;
+                    }
                 }
                 i += 1;
-                // Creat the body of the statement
-                let result = parser(i, tokens.to_vec())?;
-                ast.push(Node::WhileLoop {
-                    condition,
-                    body: result.node,
-                });
-                i = result.end_num + 1;
 
+                ast.push(Node::Enum {
+                    name,
+                    generics,
+                    variants,
+                    derives: std::mem::take(&mut derives),
+                });
                 continue;
             }
 
-            if a == "for" {
-                // Create the condition
+            if a == "struct" {
                 i += 1;
                 curr_token = &tokens[i];
                 let name;
-                let start;
                 if let Token::Char(c) = curr_token {
                     name = c.to_string();
                 } else {
                     return Err(format!(
-                        "Parser For loop: Expected Char got: {:?} at {i}",
+                        "Parser Struct: Expected Char got: {:?} at {i}",
                         curr_token
                     )
                     .into());
                 }
+                i += 1;
+                let mut generics = Vec::new();
+                if is_symbol(tokens, i, Symbol::Lesser) {
+                    let (parameters, end) = generic_parameters(i, tokens)?;
+                    generics = parameters;
+                    i = end;
+                }
+                if is_keyword(tokens, i, "where") {
+                    i = where_clause(i + 1, tokens, &mut generics)?;
+                }
 
-                i += 2;
-                curr_token = &tokens[i];
-
-                match curr_token {
-                    Token::Number(n) => start = n.to_string(),
-                    Token::Char(c) => start = c.to_string(),
-                    _ => {
-                        return Err(format!(
-                            "Parser For loop: Expected Num/Char got: {:?} at {i}",
-                            curr_token
-                        )
-                        .into())
+                let mut fields = Fields::Unit;
+                if is_bracket_open(tokens, i, Bracket::Curly) {
+                    let (named, end) = named_fields(i + 1, tokens)?;
+                    fields = named;
+                    i = end;
+                } else {
+                    if is_bracket_open(tokens, i, Bracket::Paren) {
+                        let (types, end) = tuple_fields(i + 1, tokens)?;
+                        fields = Fields::Tuple(types);
+                        i = end;
+                    }
+                    // `struct Wrapper<T>(T) where T: Copy;`
+                    if is_keyword(tokens, i, "where") {
+                        i = where_clause(i + 1, tokens, &mut generics)?;
                     }
+                    // Unit and tuple structs end with a semicolon
+                    i += 1;
                 }
 
-                i += 3;
-                curr_token = &tokens[i];
-                let end;
-
-                if let Token::Symbol(_) = curr_token {
-                    i += 1;
-                    curr_token = &tokens[i];
+                ast.push(Node::Struct {
+                    name,
+                    generics,
+                    fields,
+                    derives: std::mem::take(&mut derives),
+                });
+                continue;
+            }
 
-                    match curr_token {
-                        Token::Number(n) => end = (n.parse::<usize>()? - 1).to_string(),
-                        Token::Char(c) => end = (c.parse::<usize>()? - 1).to_string(),
-                        _ => {
-                            return Err(format!(
-                                "Parser For loop: Expected Num/Char got: {:?} at {i}",
-                                curr_token
-                            )
-                            .into())
-                        }
-                    }
-                } else if let Token::Number(_) = curr_token {
-                    match curr_token {
-                        Token::Number(n) => end = n.to_string(),
-                        Token::Char(c) => end = c.to_string(),
-                        _ => {
-                            return Err(format!(
-                                "Parser For loop: Expected Num/Char got: {:?} at {i}",
-                                curr_token
-                            )
-                            .into())
-                        }
-                    }
-                } else {
+            if a == "type" {
+                let Some(Token::Char(name)) = tokens.get(i + 1) else {
                     return Err(format!(
-                        "Parser For loop: Expected Char got: {:?} at {i}",
-                        curr_token
+                        "Parser Type alias: Expected Char got: {:?} at {}",
+                        tokens.get(i + 1),
+                        i + 1
+                    )
+                    .into());
+                };
+                if !is_symbol(tokens, i + 2, Symbol::Equals) {
+                    return Err(format!(
+                        "Parser Type alias: Expected Equals got: {:?} at {}",
+                        tokens.get(i + 2),
+                        i + 2
                     )
                     .into());
                 }
-
-                i += 2;
-                // Creat the body of the statement
-                let result = parser(i, tokens.to_vec())?;
-                ast.push(Node::ForLoop {
-                    name,
-                    start,
-                    end,
-                    body: result.node,
+                let (ty, end) = parse_type(i + 3, tokens)?;
+                i = end;
+                if !is_symbol(tokens, i, Symbol::SemiColon) {
+                    return Err(format!(
+                        "Parser Type alias: Expected SemiColon got: {:?} at {i}",
+                        tokens.get(i)
+                    )
+                    .into());
+                }
+                ast.push(Node::TypeAlias {
+                    name: name.to_string(),
+                    ty,
                 });
-                i = result.end_num + 1;
-
+                i += 1;
                 continue;
             }
 
-            if a == "fn" {
-                // Create the condition
-                i += 1;
-                curr_token = &tokens[i];
-                let name;
-                if let Token::Char(c) = curr_token {
-                    name = c.to_string();
-                } else {
+            if a == "trait" {
+                let Some(Token::Char(name)) = tokens.get(i + 1) else {
                     return Err(format!(
-                        "Parser Function: Expected Char got: {:?} at {i}",
-                        curr_token
+                        "Parser Trait: Expected Char got: {:?} at {}",
+                        tokens.get(i + 1),
+                        i + 1
                     )
                     .into());
-                }
-
+                };
                 i += 2;
-                curr_token = &tokens[i];
-
-                let mut input = Vec::new();
-                while !matches!(*curr_token, Token::BracketOpen(Bracket::Curly)) {
-                    curr_token = &tokens[i];
-                    match curr_token {
-                        Token::Char(c) => input.push(Node::VeriableCall(c.to_string())),
-                        Token::Symbol(Symbol::Colon) => {
-                            i += 1;
-                            continue;
-                        }
-                        Token::BracketClose(Bracket::Paren) => {
-                            i += 1;
-                            break;
-                        }
-                        _ => {
-                            return Err(format!(
-                                "Parser Function: Expected Char/Colon/ParenOpen got: {:?} at {i}",
-                                curr_token
-                            )
-                            .into())
-                        }
-                    }
-                    i += 3;
+                if is_symbol(tokens, i, Symbol::Lesser) {
+                    let (_, end) = generic_parameters(i, tokens)?;
+                    i = end;
                 }
-
-                i += 1;
-                // Creat the body of the statement
-                let result = parser(i, tokens.to_vec())?;
-                ast.push(Node::Function {
-                    name,
-                    input,
+                let mut supertraits = Vec::new();
+                if is_symbol(tokens, i, Symbol::DoubleDot) {
+                    i = bounds(i + 1, tokens, &mut supertraits)?;
+                }
+                if is_keyword(tokens, i, "where") {
+                    i = where_clause(i + 1, tokens, &mut Vec::new())?;
+                }
+                if !is_bracket_open(tokens, i, Bracket::Curly) {
+                    return Err(format!(
+                        "Parser Trait: Expected CurlyOpen got: {:?} at {i}",
+                        tokens.get(i)
+                    )
+                    .into());
+                }
+                let result = parser(i + 1, tokens)?;
+                ast.push(Node::Trait {
+                    name: name.to_string(),
+                    supertraits,
                     body: result.node,
                 });
                 i = result.end_num + 1;
-
                 continue;
             }
 
-            // Handle Function calls
-            if let Token::BracketOpen(Bracket::Paren) = tokens[i + 1] {
-                let name;
-                match curr_token {
-                    Token::Char(c) => name = c.to_string(),
+            if a == "impl" {
+                i += 1;
+                if is_symbol(tokens, i, Symbol::Lesser) {
+                    let (_, end) = generic_parameters(i, tokens)?;
+                    i = end;
+                }
+                let (ty, end) = parse_type(i, tokens)?;
+                i = end;
+                let (trait_name, ty) = if matches!(tokens.get(i), Some(Token::Char(c)) if c == "for")
+                {
+                    let (implemented, end) = parse_type(i + 1, tokens)?;
+                    i = end;
+                    (ty.name().map(str::to_string), implemented)
+                } else {
+                    (None, ty)
+                };
+                if is_keyword(tokens, i, "where") {
+                    i = where_clause(i + 1, tokens, &mut Vec::new())?;
+                }
+                let name = match ty {
+                    Type::Named { name, .. } => name,
                     _ => {
                         return Err(format!(
-                            "Parser Function call: Expected Char got: {:?} at {i}",
-                            curr_token
+                            "Parser Impl: Expected Char got: {:?} at {}",
+                            tokens.get(i + 1),
+                            i + 1
                         )
                         .into())
                     }
+                };
+                if !is_bracket_open(tokens, i, Bracket::Curly) {
+                    return Err(format!(
+                        "Parser Impl: Expected CurlyOpen got: {:?} at {i}",
+                        tokens.get(i)
+                    )
+                    .into());
                 }
-
-                i += 2;
-                curr_token = &tokens[i];
-
-                let mut input = Vec::new();
-                while !matches!(*curr_token, Token::BracketClose(Bracket::Paren)) {
-                    match curr_token {
-                        Token::Char(c) => {
-                            input.push(Node::VeriableCall(c.to_string()));
-                            i += 1;
-                        }
-                        Token::Number(c) => {
-                            input.push(Node::NumberLiteral(c.to_string()));
-                            i += 1;
-                        }
-                        Token::String(c) => {
-                            input.push(Node::StringLiteral(c.to_string()));
-                            i += 1;
-                        }
-                        Token::Symbol(Symbol::Colon) => i += 1,
-                        Token::BracketClose(Bracket::Paren) => i += 1,
-                        _ => {
-                            return Err(format!(
-                                "Parser Function call: Expected Char/Number/String/Colon/ParenOpen got: {:?} at {i}",
-                                curr_token
-                            )
-                            .into())
-                        }
-                    }
-                    curr_token = &tokens[i]
-                }
-                i += 1;
-                ast.push(Node::FunctionCall { name, input });
-                continue;
-            }
-            // Handle var calls
-            else {
-                ast.push(Node::VeriableCall(a.to_string()));
-                i += 1;
-                curr_token = &tokens[i];
-
-                if let Token::Symbol(Symbol::Equals) = curr_token {
-                    ast.push(Node::Symbol(Symbol::Equals));
-                    i += 1;
-                    curr_token = &tokens[i];
-                    while !matches!(*curr_token, Token::Symbol(Symbol::SemiColon)) {
-                        match curr_token {
-                            Token::Char(n) => ast.push(Node::VeriableCall(n.to_string())),
-                            Token::Number(n) => ast.push(Node::NumberLiteral(n.to_string())),
-                            Token::String(s) => ast.push(Node::NumberLiteral(s.to_string())),
-                            Token::Symbol(s) => ast.push(Node::Symbol(*s)),
-                            Token::BracketOpen(b) => ast.push(Node::BracketOpen(b.clone())),
-                            Token::BracketClose(b) => ast.push(Node::BracketClose(b.clone())),
-                        }
-                        i += 1;
-                        curr_token = &tokens[i];
-                    }
-                }
+                let result = parser(i + 1, tokens)?;
+                ast.push(Node::Impl {
+                    name,
+                    trait_name,
+                    body: result.node,
+                });
+                i = result.end_num + 1;
                 continue;
             }
         }
@@ -626,134 +1094,13313 @@ pub fn parser(start: usize, tokens: Vec<Token>) -> Result<Output, Box<dyn Error>
                     end_num: i,
                 })
             }
-            _ => return Err(format!("Parser: Could not parse: {:?} at {i}", curr_token).into()),
+            // Handle function calls, assignments and any other expression
+            _ => {
+                let (expression, end) = expression(i, tokens, false)?;
+                ast.push(expression);
+                i = end;
+            }
         }
     }
-    return Ok(Output {
+    Ok(Output {
         node: ast,
         end_num: i,
-    });
+    })
 }
-////////////////////
-///CODE GENERATOR///
-////////////////////
 
-pub fn code_generator(ast: Vec<Node>) -> Result<String, Box<dyn Error>> {
-    let mut program = String::new();
-    for i in ast {
-        match i {
-            Node::Veriable { name, value } => {
-                program.push_str(format!("let {}{}", name, code_generator(value)?).as_str())
-            }
-            Node::VeriableCall(c) => program.push_str(format!("{c}").as_str()),
-            Node::IfStatement { condition, body } => program.push_str(
-                format!(
-                    "if ({}){{\n{}}}\n",
-                    code_generator(condition)?,
-                    code_generator(body)?
+// `#[...]` or `#![...]` at `start`, returns the tokens inside the brackets and the index after it
+fn attribute(start: usize, tokens: &[Token]) -> Result<(Vec<Token>, usize), Box<dyn Error>> {
+    let mut i = start + 1;
+    if is_symbol(tokens, i, Symbol::Exclamation) {
+        i += 1;
+    }
+    if !is_bracket_open(tokens, i, Bracket::Square) {
+        return Err(format!(
+            "Parser Attribute: Expected SquareOpen got: {:?} at {i}",
+            tokens.get(i)
+        )
+        .into());
+    }
+    let open = i + 1;
+    let mut depth = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            Token::BracketOpen(_) => depth += 1,
+            Token::BracketClose(_) => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+        if depth == 0 {
+            return Ok((tokens[open..i - 1].to_vec(), i));
+        }
+    }
+    Err(format!("Parser Attribute: Expected SquareClose got: None at {i}").into())
+}
+
+// Parses a function from its name on, `&self`/`self` receivers become a parameter named `self`
+fn function(start: usize, tokens: &[Token]) -> Result<(Node, usize), Box<dyn Error>> {
+    let mut i = start;
+    let mut curr_token = &tokens[i];
+    let name;
+    if let Token::Char(c) = curr_token {
+        name = c.to_string();
+    } else {
+        return Err(format!(
+            "Parser Function: Expected Char got: {:?} at {i}",
+            curr_token
+        )
+        .into());
+    }
+
+    i += 1;
+    let mut generics = Vec::new();
+    if is_symbol(tokens, i, Symbol::Lesser) {
+        let (parameters, end) = generic_parameters(i, tokens)?;
+        generics = parameters;
+        i = end;
+    }
+    if !is_bracket_open(tokens, i, Bracket::Paren) {
+        return Err(format!(
+            "Parser Function: Expected ParenOpen got: {:?} at {i}",
+            tokens.get(i)
+        )
+        .into());
+    }
+    i += 1;
+    let mut input = Vec::new();
+    while !is_bracket_close(tokens, i, Bracket::Paren) {
+        let mut mutable = false;
+        while is_symbol(tokens, i, Symbol::Ampersand) || is_keyword(tokens, i, "mut") {
+            // `mut self` owns its value, `&mut self` borrows it
+            mutable |= is_symbol(tokens, i, Symbol::Ampersand) && is_keyword(tokens, i + 1, "mut");
+            i += 1;
+        }
+        curr_token = &tokens[i];
+        let name = match curr_token {
+            Token::Char(c) => c.to_string(),
+            _ => {
+                return Err(format!(
+                    "Parser Function: Expected Char/ParenClose got: {:?} at {i}",
+                    curr_token
                 )
-                .as_str(),
-            ),
-            Node::ElseStatement { body } => {
-                program.push_str(format!("else{{\n{}}}\n", code_generator(body)?).as_str())
+                .into())
             }
-            Node::WhileLoop { condition, body } => program.push_str(
-                format!(
-                    "while ({}){{\n{}}}\n",
-                    code_generator(condition)?,
-                    code_generator(body)?
+        };
+        if name == "self" && !is_symbol(tokens, i + 1, Symbol::DoubleDot) {
+            input.push(Parameter {
+                name,
+                ty: Type::named("Self"),
+                mutable,
+            });
+            i += 1;
+        } else {
+            if !is_symbol(tokens, i + 1, Symbol::DoubleDot) {
+                return Err(format!(
+                    "Parser Function: Expected DoubleDot got: {:?} at {}",
+                    tokens.get(i + 1),
+                    i + 1
                 )
-                .as_str(),
-            ),
-            Node::ForLoop {
+                .into());
+            }
+            let mutable =
+                is_symbol(tokens, i + 2, Symbol::Ampersand) && is_keyword(tokens, i + 3, "mut");
+            let (ty, end) = parse_type(i + 2, tokens)?;
+            input.push(Parameter { name, ty, mutable });
+            i = end;
+        }
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        }
+    }
+    i += 1;
+
+    let mut output = None;
+    if is_symbol(tokens, i, Symbol::Minus) && is_symbol(tokens, i + 1, Symbol::Grater) {
+        let (ty, end) = parse_type(i + 2, tokens)?;
+        output = Some(ty);
+        i = end;
+    }
+    if is_keyword(tokens, i, "where") {
+        i = where_clause(i + 1, tokens, &mut generics)?;
+    }
+    // Required trait methods end with the signature
+    if is_symbol(tokens, i, Symbol::SemiColon) {
+        return Ok((
+            Node::Function {
                 name,
-                start,
-                end,
-                body,
-            } => program.push_str(
-                format!(
-                    "for (let {name}={start}; {name}==={end}; {name}++){{\n{}}}\n",
-                    code_generator(body)?
+                generics,
+                input,
+                output,
+                body: Vec::new(),
+            },
+            i + 1,
+        ));
+    }
+
+    i += 1;
+    // Creat the body of the function
+    let result = parser(i, tokens)?;
+    Ok((
+        Node::Function {
+            name,
+            generics,
+            input,
+            output,
+            body: result.node,
+        },
+        result.end_num + 1,
+    ))
+}
+
+// `<T, U: Display + Clone>` starting at the `<`, returns the index after the `>`
+fn generic_parameters(
+    start: usize,
+    tokens: &[Token],
+) -> Result<(Vec<Generic>, usize), Box<dyn Error>> {
+    let mut i = start + 1;
+    let mut generics = Vec::new();
+    while !is_symbol(tokens, i, Symbol::Grater) {
+        // Const generics are values, they are not needed in the output
+        if is_keyword(tokens, i, "const") {
+            let (_, end) = parse_type(i + 3, tokens)?;
+            i = end;
+        } else {
+            let Some(Token::Char(name)) = tokens.get(i) else {
+                return Err(format!(
+                    "Parser Generics: Expected Char got: {:?} at {i}",
+                    tokens.get(i)
                 )
-                .as_str(),
-            ),
-            Node::FunctionCall { name, input } => {
-                program.push_str(format!("{name}(").as_str());
-                let mut i = 0;
-                let len = input.len();
-                for n in input {
-                    if let Node::VeriableCall(call) = n {
-                        if i > 0 {
-                            program.push_str(" ")
-                        }
-                        program.push_str(format!("{call}").as_str());
-                        if i < len - 1 {
-                            program.push_str(",")
-                        }
-                    } else {
-                        return Err(format!(
-                            "Code generator Function call: Expected Variable call got: {:?}",
-                            n
-                        )
-                        .into());
-                    };
-                    i += 1;
-                }
-                program.push_str(")");
+                .into());
+            };
+            let mut generic = Generic {
+                name: name.to_string(),
+                bounds: Vec::new(),
+            };
+            i += 1;
+            if is_symbol(tokens, i, Symbol::DoubleDot) {
+                i = bounds(i + 1, tokens, &mut generic.bounds)?;
             }
-            Node::Function { name, input, body } => {
-                program.push_str(format!("function {name}(").as_str());
-                let mut i = 0;
-                let len = input.len();
-                for n in input {
-                    if let Node::VeriableCall(call) = n {
-                        if i > 0 {
-                            program.push_str(" ")
-                        }
-                        program.push_str(format!("{call}").as_str());
-                        if i < len - 1 {
-                            program.push_str(",")
-                        }
-                    } else {
-                        return Err(format!(
-                            "Code generator Function: Expected Variable call got: {:?}",
-                            n
-                        )
-                        .into());
-                    };
-                    i += 1;
-                }
-                program.push_str(format!("){{\n{}}}\n", code_generator(body)?).as_str());
-            }
-            Node::NewLine => program.push_str(";\n"),
-            Node::StringLiteral(s) => program.push_str(format!("\"{s}\"").as_str()),
-            Node::NumberLiteral(n) => program.push_str(format!("{n}").as_str()),
-            Node::Symbol(s) => match s {
-                Symbol::Equals => program.push_str("="),
-                Symbol::Plus => program.push_str("+"),
-                Symbol::Minus => program.push_str("-"),
-                Symbol::Multiply => program.push_str("*"),
-                Symbol::Devide => program.push_str("/"),
-                Symbol::Modulo => program.push_str("%"),
-                Symbol::Grater => program.push_str(">"),
-                Symbol::Lesser => program.push_str("<"),
-                Symbol::Dot => program.push_str("."),
-                Symbol::SemiColon => program.push_str(";"),
-                Symbol::Exclamation => program.push_str("!"),
-                Symbol::DoubleDot => program.push_str(":"),
-                Symbol::Colon => program.push_str(","),
-            },
-            Node::BracketOpen(b) => match b {
-                Bracket::Curly => program.push_str("{"),
-                Bracket::Square => program.push_str("["),
-                Bracket::Paren => program.push_str("("),
-            },
-            Node::BracketClose(b) => match b {
-                Bracket::Curly => program.push_str("}"),
-                Bracket::Square => program.push_str("]"),
-                Bracket::Paren => program.push_str(")"),
-            },
-        }
-    }
-    Ok(program)
+            // A default type
+            if is_symbol(tokens, i, Symbol::Equals) {
+                let (_, end) = parse_type(i + 1, tokens)?;
+                i = end;
+            }
+            generics.push(generic);
+        }
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        }
+    }
+    Ok((generics, i + 1))
+}
+
+// `A + B<T> + ?Sized`, returns the index after the last bound
+fn bounds(
+    start: usize,
+    tokens: &[Token],
+    names: &mut Vec<String>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut i = start;
+    loop {
+        if is_symbol(tokens, i, Symbol::Question) {
+            i += 1;
+        }
+        if !matches!(tokens.get(i), Some(Token::Char(_))) {
+            return Ok(i);
+        }
+        let (bound, end) = parse_type(i, tokens)?;
+        names.extend(bound.name().map(str::to_string));
+        i = end;
+        if !is_symbol(tokens, i, Symbol::Plus) {
+            return Ok(i);
+        }
+        i += 1;
+    }
+}
+
+// `where T: A + B, U: C` up to the body, the bounds are added to the generics
+fn where_clause(
+    start: usize,
+    tokens: &[Token],
+    generics: &mut Vec<Generic>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut i = start;
+    while !is_bracket_open(tokens, i, Bracket::Curly) && !is_symbol(tokens, i, Symbol::SemiColon) {
+        let (ty, end) = parse_type(i, tokens)?;
+        if !is_symbol(tokens, end, Symbol::DoubleDot) {
+            return Err(format!(
+                "Parser Where: Expected DoubleDot got: {:?} at {end}",
+                tokens.get(end)
+            )
+            .into());
+        }
+        let mut names = Vec::new();
+        i = bounds(end + 1, tokens, &mut names)?;
+        let name = ty.name().unwrap_or_default();
+        match generics.iter_mut().find(|generic| generic.name == name) {
+            Some(generic) => generic.bounds.extend(names),
+            // Bounds on types like `Vec<T>: Debug` do not introduce a generic
+            None if matches!(&ty, Type::Named { generics, .. } if generics.is_empty()) => generics
+                .push(Generic {
+                    name: name.to_string(),
+                    bounds: names,
+                }),
+            None => {}
+        }
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        }
+    }
+    Ok(i)
+}
+
+// Parses `{ ... }` as a list of statements, `start` is the index of the `{`
+fn block(start: usize, tokens: &[Token]) -> Result<(Vec<Node>, usize), Box<dyn Error>> {
+    if !is_bracket_open(tokens, start, Bracket::Curly) {
+        return Err(format!(
+            "Parser Block: Expected CurlyOpen got: {:?} at {start}",
+            tokens.get(start)
+        )
+        .into());
+    }
+    let result = parser(start + 1, tokens)?;
+    Ok((result.node, result.end_num + 1))
+}
+
+// `start` is just after the `if`, `else if` chains end up nested in the else body
+fn if_expression(start: usize, tokens: &[Token]) -> Result<(Node, usize), Box<dyn Error>> {
+    // Create the condition
+    let (pattern, condition, end) = condition(start, tokens)?;
+    // Creat the body of the statement
+    let (body, mut i) = block(end, tokens)?;
+
+    let mut else_body = None;
+    if is_keyword(tokens, i, "else") {
+        if is_keyword(tokens, i + 1, "if") {
+            let (chained, end) = if_expression(i + 2, tokens)?;
+            else_body = Some(vec![chained]);
+            i = end;
+        } else {
+            let (body, end) = block(i + 1, tokens)?;
+            else_body = Some(body);
+            i = end;
+        }
+    }
+    Ok((
+        Node::IfStatement {
+            condition: Box::new(condition),
+            pattern: pattern.map(Box::new),
+            body,
+            else_body,
+        },
+        i,
+    ))
+}
+
+// The condition of an `if` or `while`, with the pattern of `if let` and `while let`
+fn condition(
+    start: usize,
+    tokens: &[Token],
+) -> Result<(Option<Pattern>, Node, usize), Box<dyn Error>> {
+    if !is_keyword(tokens, start, "let") {
+        let (condition, end) = expression(start, tokens, true)?;
+        return Ok((None, condition, end));
+    }
+    let (pattern, i) = pattern(start + 1, tokens)?;
+    if !is_symbol(tokens, i, Symbol::Equals) {
+        return Err(format!(
+            "Parser Condition: Expected Equals got: {:?} at {i}",
+            tokens.get(i)
+        )
+        .into());
+    }
+    let (condition, end) = expression(i + 1, tokens, true)?;
+    Ok((Some(pattern), condition, end))
+}
+
+// `let x`, `let mut x` and `let x: T` bind a plain name, anything else is a pattern
+fn is_simple_binding(tokens: &[Token], start: usize) -> bool {
+    let i = if is_keyword(tokens, start, "mut") {
+        start + 1
+    } else {
+        start
+    };
+    // `let Shape::Circle(r)` is a path, not a type annotation
+    let path =
+        is_symbol(tokens, i + 1, Symbol::DoubleDot) && is_symbol(tokens, i + 2, Symbol::DoubleDot);
+    matches!(tokens.get(i), Some(Token::Char(c)) if c != "_")
+        && !path
+        && matches!(
+            tokens.get(i + 1),
+            Some(Token::Symbol(
+                Symbol::DoubleDot | Symbol::Equals | Symbol::SemiColon
+            ))
+        )
+}
+
+// `start` is just after the `let`, ends at the SemiColon
+fn let_pattern(start: usize, tokens: &[Token]) -> Result<(Node, usize), Box<dyn Error>> {
+    let (pattern, mut i) = pattern(start, tokens)?;
+    // The type does not change how the pattern is matched
+    if is_symbol(tokens, i, Symbol::DoubleDot) {
+        let (_, end) = parse_type(i + 1, tokens)?;
+        i = end;
+    }
+    if !is_symbol(tokens, i, Symbol::Equals) {
+        return Err(format!(
+            "Parser Variable: Expected Equals got: {:?} at {i}",
+            tokens.get(i)
+        )
+        .into());
+    }
+    let (value, end) = expression(i + 1, tokens, false)?;
+    i = end;
+    let mut else_body = None;
+    if is_keyword(tokens, i, "else") {
+        let (body, end) = block(i + 1, tokens)?;
+        else_body = Some(body);
+        i = end;
+    }
+    if !is_symbol(tokens, i, Symbol::SemiColon) {
+        return Err(format!(
+            "Parser Variable: Expected SemiColon got: {:?} at {i}",
+            tokens.get(i)
+        )
+        .into());
+    }
+    Ok((
+        Node::LetPattern {
+            pattern: Box::new(pattern),
+            value: Box::new(value),
+            else_body,
+        },
+        i,
+    ))
+}
+
+// `start` is just after the `match`
+fn match_expression(start: usize, tokens: &[Token]) -> Result<(Node, usize), Box<dyn Error>> {
+    let (scrutinee, mut i) = expression(start, tokens, true)?;
+    if !is_bracket_open(tokens, i, Bracket::Curly) {
+        return Err(format!(
+            "Parser Match: Expected CurlyOpen got: {:?} at {i}",
+            tokens.get(i)
+        )
+        .into());
+    }
+    i += 1;
+
+    let mut arms = Vec::new();
+    while !is_bracket_close(tokens, i, Bracket::Curly) {
+        let (pattern, end) = pattern(i, tokens)?;
+        i = end;
+
+        let mut guard = None;
+        if is_keyword(tokens, i, "if") {
+            let (condition, end) = expression(i + 1, tokens, false)?;
+            guard = Some(condition);
+            i = end;
+        }
+
+        if !(is_symbol(tokens, i, Symbol::Equals) && is_symbol(tokens, i + 1, Symbol::Grater)) {
+            return Err(format!(
+                "Parser Match: Expected FatArrow got: {:?} at {i}",
+                tokens.get(i)
+            )
+            .into());
+        }
+        i += 2;
+
+        let body;
+        if is_bracket_open(tokens, i, Bracket::Curly) {
+            let (statements, end) = block(i, tokens)?;
+            body = Node::Block(statements);
+            i = end;
+        } else {
+            let (expression, end) = expression(i, tokens, false)?;
+            body = expression;
+            i = end;
+        }
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        }
+
+        arms.push(Arm {
+            pattern,
+            guard,
+            body,
+        });
+    }
+    Ok((
+        Node::Match {
+            expression: Box::new(scrutinee),
+            arms,
+        },
+        i + 1,
+    ))
+}
+
+// A pattern including `A | B` alternatives
+fn pattern(start: usize, tokens: &[Token]) -> Result<(Pattern, usize), Box<dyn Error>> {
+    let mut i = start;
+    if is_symbol(tokens, i, Symbol::Pipe) {
+        i += 1;
+    }
+    let (first, end) = single_pattern(i, tokens)?;
+    i = end;
+    if !is_symbol(tokens, i, Symbol::Pipe) {
+        return Ok((first, i));
+    }
+
+    let mut alternatives = vec![first];
+    while is_symbol(tokens, i, Symbol::Pipe) {
+        let (alternative, end) = single_pattern(i + 1, tokens)?;
+        alternatives.push(alternative);
+        i = end;
+    }
+    Ok((Pattern::Or(alternatives), i))
+}
+
+// Comma separated patterns up to the closing bracket, returns the index after it
+fn pattern_list(
+    start: usize,
+    tokens: &[Token],
+    close: Bracket,
+) -> Result<(Vec<Pattern>, usize), Box<dyn Error>> {
+    let mut patterns = Vec::new();
+    let mut i = start;
+    while !is_bracket_close(tokens, i, close) {
+        let (pattern, end) = pattern(i, tokens)?;
+        patterns.push(pattern);
+        i = end;
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        } else if !is_bracket_close(tokens, i, close) {
+            return Err(format!(
+                "Parser Pattern: Expected Colon/{:?}Close got: {:?} at {i}",
+                close,
+                tokens.get(i)
+            )
+            .into());
+        }
+    }
+    Ok((patterns, i + 1))
+}
+
+fn single_pattern(start: usize, tokens: &[Token]) -> Result<(Pattern, usize), Box<dyn Error>> {
+    let i = start;
+    match tokens.get(i) {
+        // References do not exist in the output
+        Some(Token::Symbol(Symbol::Ampersand)) => {
+            let next = if is_keyword(tokens, i + 1, "mut") {
+                i + 2
+            } else {
+                i + 1
+            };
+            single_pattern(next, tokens)
+        }
+        Some(Token::BracketOpen(Bracket::Paren)) => {
+            let (mut patterns, end) = pattern_list(i + 1, tokens, Bracket::Paren)?;
+            // `(pattern)` only groups, `(pattern,)` is a tuple
+            if patterns.len() == 1 && !is_symbol(tokens, end - 2, Symbol::Colon) {
+                return Ok((patterns.remove(0), end));
+            }
+            Ok((Pattern::Tuple(patterns), end))
+        }
+        Some(Token::BracketOpen(Bracket::Square)) => {
+            let (patterns, end) = pattern_list(i + 1, tokens, Bracket::Square)?;
+            Ok((Pattern::Slice(patterns), end))
+        }
+        Some(Token::Symbol(Symbol::Dot)) if is_symbol(tokens, i + 1, Symbol::Dot) => {
+            // `..=5`
+            if is_symbol(tokens, i + 2, Symbol::Equals) {
+                let (end, next) = pattern_literal(i + 3, tokens)?;
+                return Ok((
+                    Pattern::Range {
+                        start: None,
+                        end: Some(end),
+                        inclusive: true,
+                    },
+                    next,
+                ));
+            }
+            Ok((Pattern::Rest, i + 2))
+        }
+        Some(Token::Char(c)) if c == "_" => Ok((Pattern::Wildcard, i + 1)),
+        Some(Token::Char(c)) if c == "ref" && is_keyword(tokens, i + 1, "mut") => {
+            match tokens.get(i + 2) {
+                Some(Token::Char(name)) => Ok((Pattern::RefMut(name.to_string()), i + 3)),
+                curr_token => Err(format!(
+                    "Parser Pattern: Expected Char got: {:?} at {}",
+                    curr_token,
+                    i + 2
+                )
+                .into()),
+            }
+        }
+        Some(Token::Char(c)) if c == "ref" || c == "mut" => single_pattern(i + 1, tokens),
+        Some(Token::Char(c)) if c != "true" && c != "false" => {
+            let (name, mut i) = path(i, tokens)?;
+            if is_bracket_open(tokens, i, Bracket::Paren) {
+                let (fields, end) = pattern_list(i + 1, tokens, Bracket::Paren)?;
+                return Ok((Pattern::TupleStruct { name, fields }, end));
+            }
+            if is_bracket_open(tokens, i, Bracket::Curly) {
+                i += 1;
+                let mut fields = Vec::new();
+                while !is_bracket_close(tokens, i, Bracket::Curly) {
+                    // The remaining fields are ignored
+                    if is_symbol(tokens, i, Symbol::Dot) && is_symbol(tokens, i + 1, Symbol::Dot) {
+                        i += 2;
+                        continue;
+                    }
+                    let reference =
+                        is_keyword(tokens, i, "ref") && is_keyword(tokens, i + 1, "mut");
+                    while is_keyword(tokens, i, "ref") || is_keyword(tokens, i, "mut") {
+                        i += 1;
+                    }
+                    let field = match tokens.get(i) {
+                        Some(Token::Char(c)) => c.to_string(),
+                        curr_token => {
+                            return Err(format!(
+                                "Parser Pattern: Expected Char got: {:?} at {i}",
+                                curr_token
+                            )
+                            .into())
+                        }
+                    };
+                    if is_symbol(tokens, i + 1, Symbol::DoubleDot) {
+                        let (pattern, end) = pattern(i + 2, tokens)?;
+                        fields.push((field, pattern));
+                        i = end;
+                    } else {
+                        // Shorthand `Point { x, y }`
+                        let binding = match reference {
+                            true => Pattern::RefMut(field.clone()),
+                            false => Pattern::Binding {
+                                name: field.clone(),
+                                pattern: None,
+                            },
+                        };
+                        fields.push((field, binding));
+                        i += 1;
+                    }
+                    if is_symbol(tokens, i, Symbol::Colon) {
+                        i += 1;
+                    }
+                }
+                return Ok((Pattern::Struct { name, fields }, i + 1));
+            }
+            if let Some(value) = integer_constant(&name) {
+                return range_pattern(Node::NumberLiteral(value), i, tokens);
+            }
+            if name.contains("::") {
+                return Ok((Pattern::Path(name), i));
+            }
+            if is_symbol(tokens, i, Symbol::At) {
+                let (pattern, end) = single_pattern(i + 1, tokens)?;
+                return Ok((
+                    Pattern::Binding {
+                        name,
+                        pattern: Some(Box::new(pattern)),
+                    },
+                    end,
+                ));
+            }
+            Ok((
+                Pattern::Binding {
+                    name,
+                    pattern: None,
+                },
+                i,
+            ))
+        }
+        _ => {
+            let (start, i) = pattern_literal(i, tokens)?;
+            range_pattern(start, i, tokens)
+        }
+    }
+}
+
+// A literal pattern, or a range when `..` follows the literal
+fn range_pattern(
+    start: Node,
+    mut i: usize,
+    tokens: &[Token],
+) -> Result<(Pattern, usize), Box<dyn Error>> {
+    if !(is_symbol(tokens, i, Symbol::Dot) && is_symbol(tokens, i + 1, Symbol::Dot)) {
+        return Ok((Pattern::Literal(start), i));
+    }
+    i += 2;
+    let inclusive = is_symbol(tokens, i, Symbol::Equals);
+    if inclusive {
+        i += 1;
+    }
+    // `5..` has no end
+    let mut end = None;
+    let constant = matches!(tokens.get(i), Some(Token::Char(_)))
+        && path(i, tokens).is_ok_and(|(name, _)| integer_constant(&name).is_some());
+    if constant
+        || matches!(
+            tokens.get(i),
+            Some(
+                Token::Number(_)
+                    | Token::String(_)
+                    | Token::Character(_)
+                    | Token::Symbol(Symbol::Minus)
+            )
+        )
+    {
+        let (literal, next) = pattern_literal(i, tokens)?;
+        end = Some(literal);
+        i = next;
+    }
+    Ok((
+        Pattern::Range {
+            start: Some(start),
+            end,
+            inclusive,
+        },
+        i,
+    ))
+}
+
+const NUMBER_TYPES: [&str; 14] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64",
+];
+
+// The digits and the suffix type of a number literal, `1_000u64` is `1000` of type `u64`
+fn number_literal(text: &str) -> (String, Option<Type>) {
+    let text = text.replace('_', "");
+    // `f32` and `f64` are hex digits
+    let hex = text.starts_with("0x");
+    for suffix in NUMBER_TYPES {
+        if hex && suffix.starts_with('f') {
+            continue;
+        }
+        if let Some(digits) = text.strip_suffix(suffix).filter(|d| !d.is_empty()) {
+            return (digits.to_string(), Some(Type::named(suffix)));
+        }
+    }
+    (text, None)
+}
+
+// Literals with a fraction or an exponent are floats
+fn is_float_literal(literal: &str) -> bool {
+    literal.contains('.') || !literal.starts_with("0x") && literal.contains(['e', 'E'])
+}
+
+fn pattern_literal(start: usize, tokens: &[Token]) -> Result<(Node, usize), Box<dyn Error>> {
+    let i = start;
+    match tokens.get(i) {
+        Some(Token::Symbol(Symbol::Minus)) => match pattern_literal(i + 1, tokens)? {
+            (Node::NumberLiteral(n), end) => Ok((Node::NumberLiteral(format!("-{n}")), end)),
+            _ => Err(format!(
+                "Parser Pattern: Expected Number got: {:?} at {}",
+                tokens.get(i + 1),
+                i + 1
+            )
+            .into()),
+        },
+        Some(Token::Number(n)) => {
+            if is_symbol(tokens, i + 1, Symbol::Dot) {
+                if let Some(Token::Number(fraction)) = tokens.get(i + 2) {
+                    let (literal, _) = number_literal(&format!("{n}.{fraction}"));
+                    return Ok((Node::NumberLiteral(literal), i + 3));
+                }
+            }
+            Ok((Node::NumberLiteral(number_literal(n).0), i + 1))
+        }
+        Some(Token::String(s)) => Ok((Node::StringLiteral(s.to_string()), i + 1)),
+        Some(Token::Character(c)) => Ok((Node::CharLiteral(c.to_string()), i + 1)),
+        Some(Token::Char(c)) if c == "true" || c == "false" => {
+            Ok((Node::VeriableCall(c.to_string()), i + 1))
+        }
+        // `i32::MIN..=0`
+        Some(Token::Char(_)) => {
+            let (name, end) = path(i, tokens)?;
+            match integer_constant(&name) {
+                Some(value) => Ok((Node::NumberLiteral(value), end)),
+                None => Err(format!(
+                    "Parser Pattern: Expected a numeric constant got: {name} at {i}"
+                )
+                .into()),
+            }
+        }
+        curr_token => Err(format!(
+            "Parser Pattern: Expected Char/Number/String got: {:?} at {i}",
+            curr_token
+        )
+        .into()),
+    }
+}
+
+fn is_symbol(tokens: &[Token], i: usize, symbol: Symbol) -> bool {
+    matches!(tokens.get(i), Some(Token::Symbol(s)) if *s == symbol)
+}
+
+fn is_keyword(tokens: &[Token], i: usize, keyword: &str) -> bool {
+    matches!(tokens.get(i), Some(Token::Char(c)) if c == keyword)
+}
+
+fn is_bracket_open(tokens: &[Token], i: usize, bracket: Bracket) -> bool {
+    matches!(tokens.get(i), Some(Token::BracketOpen(b)) if *b == bracket)
+}
+
+fn is_bracket_close(tokens: &[Token], i: usize, bracket: Bracket) -> bool {
+    matches!(tokens.get(i), Some(Token::BracketClose(b)) if *b == bracket)
+}
+
+// Parses the types of a tuple struct/variant, `start` is just after the `(`
+fn tuple_fields(start: usize, tokens: &[Token]) -> Result<(Vec<Type>, usize), Box<dyn Error>> {
+    let mut types = Vec::new();
+    let mut i = start;
+    while !is_bracket_close(tokens, i, Bracket::Paren) {
+        let (ty, end) = parse_type(i, tokens)?;
+        types.push(ty);
+        i = end;
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        }
+    }
+    Ok((types, i + 1))
+}
+
+// Parses `name: Type` pairs of a struct/variant, `start` is just after the `{`
+fn named_fields(start: usize, tokens: &[Token]) -> Result<(Fields, usize), Box<dyn Error>> {
+    let mut fields = Vec::new();
+    let mut i = start;
+    while !is_bracket_close(tokens, i, Bracket::Curly) {
+        if is_symbol(tokens, i, Symbol::Hash) {
+            let (_, end) = attribute(i, tokens)?;
+            i = end;
+            continue;
+        }
+        if is_keyword(tokens, i, "pub") {
+            i += 1;
+        }
+        let name = match &tokens[i] {
+            Token::Char(c) => c.to_string(),
+            curr_token => {
+                return Err(format!(
+                    "Parser Fields: Expected Char/CurlyClose got: {:?} at {i}",
+                    curr_token
+                )
+                .into())
+            }
+        };
+        if !is_symbol(tokens, i + 1, Symbol::DoubleDot) {
+            return Err(format!(
+                "Parser Fields: Expected DoubleDot got: {:?} at {}",
+                tokens.get(i + 1),
+                i + 1
+            )
+            .into());
+        }
+        let (ty, end) = parse_type(i + 2, tokens)?;
+        fields.push((name, ty));
+        i = end;
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        }
+    }
+    Ok((Fields::Named(fields), i + 1))
+}
+
+fn parse_type(start: usize, tokens: &[Token]) -> Result<(Type, usize), Box<dyn Error>> {
+    let mut i = start;
+    match tokens.get(i) {
+        Some(Token::BracketOpen(Bracket::Paren)) => {
+            let (types, end) = tuple_fields(i + 1, tokens)?;
+            Ok((Type::Tuple(types), end))
+        }
+        Some(Token::BracketOpen(Bracket::Square)) => {
+            let (element, end) = parse_type(i + 1, tokens)?;
+            i = end;
+            // Only literal lengths are known, a constant's value is not looked up
+            let mut length = None;
+            if is_symbol(tokens, i, Symbol::SemiColon) {
+                let (count, end) = expression(i + 1, tokens, false)?;
+                length = literal_length(&count);
+                i = end;
+            }
+            if !is_bracket_close(tokens, i, Bracket::Square) {
+                return Err(format!(
+                    "Parser Type: Expected SquareClose got: {:?} at {i}",
+                    tokens.get(i)
+                )
+                .into());
+            }
+            Ok((Type::Array(Box::new(element), length), i + 1))
+        }
+        Some(Token::Char(c)) if c == "dyn" || c == "impl" => parse_type(i + 1, tokens),
+        // References are erased, JS objects are always shared
+        Some(Token::Symbol(Symbol::Ampersand)) => {
+            i += 1;
+            if matches!(tokens.get(i), Some(Token::Char(c)) if c == "mut") {
+                i += 1;
+            }
+            parse_type(i, tokens)
+        }
+        Some(Token::Char(_)) => {
+            let (path, end) = path(i, tokens)?;
+            i = end;
+            // Only the last segment matters, `std::collections::HashMap` is a `HashMap`
+            let name = path.rsplit("::").next().unwrap_or_default().to_string();
+            let mut generics = Vec::new();
+            if is_symbol(tokens, i, Symbol::Lesser) {
+                i += 1;
+                while !is_symbol(tokens, i, Symbol::Grater) {
+                    // `Iterator<Item = T>` keeps only the bound type
+                    if matches!(tokens.get(i), Some(Token::Char(_)))
+                        && is_symbol(tokens, i + 1, Symbol::Equals)
+                    {
+                        i += 2;
+                    }
+                    let (ty, end) = parse_type(i, tokens)?;
+                    generics.push(ty);
+                    i = end;
+                    if is_symbol(tokens, i, Symbol::Colon) {
+                        i += 1;
+                    }
+                }
+                i += 1;
+            }
+            // `Fn(i32) -> i32` keeps the inputs as a tuple and the output after them
+            if is_bracket_open(tokens, i, Bracket::Paren) {
+                let (input, end) = tuple_fields(i + 1, tokens)?;
+                generics.push(Type::Tuple(input));
+                i = end;
+                if is_symbol(tokens, i, Symbol::Minus) && is_symbol(tokens, i + 1, Symbol::Grater) {
+                    let (output, end) = parse_type(i + 2, tokens)?;
+                    generics.push(output);
+                    i = end;
+                }
+            }
+            Ok((Type::Named { name, generics }, i))
+        }
+        curr_token => Err(format!(
+            "Parser Type: Expected Char/ParenOpen/SquareOpen got: {:?} at {i}",
+            curr_token
+        )
+        .into()),
+    }
+}
+
+// Reads a `::` separated path like `Shape::Circle`, the segments are joined back with `::`
+fn path(start: usize, tokens: &[Token]) -> Result<(String, usize), Box<dyn Error>> {
+    let (name, _, end) = generic_path(start, tokens)?;
+    Ok((name, end))
+}
+
+// A path that can have turbofishes, `Vec::<i32>::new` or `max::<f64>`, returns their types too
+fn generic_path(
+    start: usize,
+    tokens: &[Token],
+) -> Result<(String, Vec<Type>, usize), Box<dyn Error>> {
+    let mut generics = Vec::new();
+    let mut name = match tokens.get(start) {
+        Some(Token::Char(c)) => c.to_string(),
+        curr_token => {
+            return Err(format!(
+                "Parser Path: Expected Char got: {:?} at {start}",
+                curr_token
+            )
+            .into())
+        }
+    };
+    let mut i = start + 1;
+    loop {
+        let (types, end) = turbofish(i, tokens)?;
+        if end != i {
+            generics = types;
+            i = end;
+        }
+        if !(is_symbol(tokens, i, Symbol::DoubleDot) && is_symbol(tokens, i + 1, Symbol::DoubleDot))
+        {
+            break;
+        }
+        match tokens.get(i + 2) {
+            Some(Token::Char(c)) => {
+                name.push_str("::");
+                name.push_str(c);
+                i += 3;
+            }
+            curr_token => {
+                return Err(format!(
+                    "Parser Path: Expected Char got: {:?} at {}",
+                    curr_token,
+                    i + 2
+                )
+                .into())
+            }
+        }
+    }
+    Ok((name, generics, i))
+}
+
+// Returns the binary operator at `i` and how many tokens it is made of
+fn binary_operator(tokens: &[Token], i: usize) -> Option<(Operator, usize)> {
+    let next_is_equals = is_symbol(tokens, i + 1, Symbol::Equals);
+    // Generics close with single `>` tokens, so shifts are two of them
+    let doubled = |symbol| is_symbol(tokens, i + 1, symbol);
+    let operator = match tokens.get(i)? {
+        Token::Symbol(Symbol::Lesser) if doubled(Symbol::Lesser) => {
+            return (!is_symbol(tokens, i + 2, Symbol::Equals)).then_some((Operator::ShiftLeft, 2))
+        }
+        Token::Symbol(Symbol::Grater) if doubled(Symbol::Grater) => {
+            return (!is_symbol(tokens, i + 2, Symbol::Equals)).then_some((Operator::ShiftRight, 2))
+        }
+        Token::Symbol(Symbol::Equals) if next_is_equals => return Some((Operator::Equal, 2)),
+        Token::Symbol(Symbol::Exclamation) if next_is_equals => {
+            return Some((Operator::NotEqual, 2))
+        }
+        Token::Symbol(Symbol::Lesser) if next_is_equals => return Some((Operator::LesserEqual, 2)),
+        Token::Symbol(Symbol::Grater) if next_is_equals => {
+            return Some((Operator::GreaterEqual, 2))
+        }
+        Token::Symbol(Symbol::Lesser) => Operator::Lesser,
+        Token::Symbol(Symbol::Grater) => Operator::Greater,
+        Token::Symbol(Symbol::Ampersand) if is_symbol(tokens, i + 1, Symbol::Ampersand) => {
+            return Some((Operator::And, 2))
+        }
+        Token::Symbol(Symbol::Pipe) if is_symbol(tokens, i + 1, Symbol::Pipe) => {
+            return Some((Operator::Or, 2))
+        }
+        // Compound assignments like `+=` are not binary operators
+        _ if next_is_equals => return None,
+        Token::Symbol(Symbol::Plus) => Operator::Add,
+        Token::Symbol(Symbol::Minus) if !is_symbol(tokens, i + 1, Symbol::Grater) => {
+            Operator::Subtract
+        }
+        Token::Symbol(Symbol::Multiply) => Operator::Multiply,
+        Token::Symbol(Symbol::Devide) => Operator::Divide,
+        Token::Symbol(Symbol::Modulo) => Operator::Modulo,
+        Token::Symbol(Symbol::Ampersand) => Operator::BitAnd,
+        Token::Symbol(Symbol::Pipe) => Operator::BitOr,
+        Token::Symbol(Symbol::Caret) => Operator::BitXor,
+        _ => return None,
+    };
+    Some((operator, 1))
+}
+
+// Returns the operator of a compound assignment like `+=` at `i` and how many tokens it is
+// made of, the `=` included
+fn compound_operator(tokens: &[Token], i: usize) -> Option<(Operator, usize)> {
+    let (operator, width) = match tokens.get(i)? {
+        Token::Symbol(Symbol::Plus) => (Operator::Add, 1),
+        Token::Symbol(Symbol::Minus) => (Operator::Subtract, 1),
+        Token::Symbol(Symbol::Multiply) => (Operator::Multiply, 1),
+        Token::Symbol(Symbol::Devide) => (Operator::Divide, 1),
+        Token::Symbol(Symbol::Modulo) => (Operator::Modulo, 1),
+        Token::Symbol(Symbol::Ampersand) => (Operator::BitAnd, 1),
+        Token::Symbol(Symbol::Pipe) => (Operator::BitOr, 1),
+        Token::Symbol(Symbol::Caret) => (Operator::BitXor, 1),
+        Token::Symbol(Symbol::Lesser) if is_symbol(tokens, i + 1, Symbol::Lesser) => {
+            (Operator::ShiftLeft, 2)
+        }
+        Token::Symbol(Symbol::Grater) if is_symbol(tokens, i + 1, Symbol::Grater) => {
+            (Operator::ShiftRight, 2)
+        }
+        _ => return None,
+    };
+    is_symbol(tokens, i + width, Symbol::Equals).then_some((operator, width + 1))
+}
+
+// Parses a full expression, `no_struct` is set for the conditions of `if`/`while`
+// where a `{` after a name opens the body instead of a struct literal
+fn expression(
+    start: usize,
+    tokens: &[Token],
+    no_struct: bool,
+) -> Result<(Node, usize), Box<dyn Error>> {
+    let (target, i) = range(start, tokens, no_struct)?;
+    if let Some((operator, width)) = compound_operator(tokens, i) {
+        let (value, end) = expression(i + width, tokens, no_struct)?;
+        return Ok((
+            Node::CompoundAssignment {
+                target: Box::new(target),
+                operator,
+                value: Box::new(value),
+            },
+            end,
+        ));
+    }
+    if is_symbol(tokens, i, Symbol::Equals) && !is_symbol(tokens, i + 1, Symbol::Grater) {
+        let (value, end) = expression(i + 1, tokens, no_struct)?;
+        return Ok((
+            Node::Assignment {
+                target: Box::new(target),
+                value: Box::new(value),
+            },
+            end,
+        ));
+    }
+    Ok((target, i))
+}
+
+// `a..b`, `a..=b` and the forms missing either end
+fn range(start: usize, tokens: &[Token], no_struct: bool) -> Result<(Node, usize), Box<dyn Error>> {
+    let is_range =
+        |i: usize| is_symbol(tokens, i, Symbol::Dot) && is_symbol(tokens, i + 1, Symbol::Dot);
+    let (from, mut i) = if is_range(start) {
+        (None, start)
+    } else {
+        let (from, end) = binary(start, tokens, 0, no_struct)?;
+        if !is_range(end) {
+            return Ok((from, end));
+        }
+        (Some(Box::new(from)), end)
+    };
+    i += 2;
+    let inclusive = is_symbol(tokens, i, Symbol::Equals);
+    if inclusive {
+        i += 1;
+    }
+    let open = match tokens.get(i) {
+        None | Some(Token::BracketClose(_)) => true,
+        Some(Token::Symbol(Symbol::Colon | Symbol::SemiColon | Symbol::Equals)) => true,
+        Some(Token::BracketOpen(Bracket::Curly)) => no_struct,
+        _ => false,
+    };
+    let mut to = None;
+    if !open {
+        let (end, next) = binary(i, tokens, 0, no_struct)?;
+        to = Some(Box::new(end));
+        i = next;
+    }
+    Ok((
+        Node::Range {
+            start: from,
+            end: to,
+            inclusive,
+        },
+        i,
+    ))
+}
+
+fn binary(
+    start: usize,
+    tokens: &[Token],
+    min_precedence: u8,
+    no_struct: bool,
+) -> Result<(Node, usize), Box<dyn Error>> {
+    let (mut left, mut i) = unary(start, tokens, no_struct)?;
+    loop {
+        // Casts bind tighter than every binary operator
+        if is_keyword(tokens, i, "as") {
+            let (ty, end) = parse_type(i + 1, tokens)?;
+            left = Node::Cast {
+                expression: Box::new(left),
+                ty,
+            };
+            i = end;
+            continue;
+        }
+
+        let Some((operator, width)) = binary_operator(tokens, i) else {
+            break;
+        };
+        if operator.precedence() < min_precedence {
+            break;
+        }
+        let (right, end) = binary(i + width, tokens, operator.precedence() + 1, no_struct)?;
+        left = Node::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        };
+        i = end;
+    }
+    Ok((left, i))
+}
+
+fn unary(start: usize, tokens: &[Token], no_struct: bool) -> Result<(Node, usize), Box<dyn Error>> {
+    match tokens.get(start) {
+        Some(Token::Symbol(operator @ (Symbol::Minus | Symbol::Exclamation))) => {
+            let (expression, end) = unary(start + 1, tokens, no_struct)?;
+            Ok((
+                Node::Unary {
+                    operator: *operator,
+                    expression: Box::new(expression),
+                },
+                end,
+            ))
+        }
+        Some(Token::Symbol(Symbol::Multiply)) => {
+            let (expression, end) = unary(start + 1, tokens, no_struct)?;
+            Ok((Node::Deref(Box::new(expression)), end))
+        }
+        // Only `&mut` is kept, the value behind it must not be copied
+        Some(Token::Symbol(Symbol::Ampersand)) => {
+            let mut i = start + 1;
+            let mut mutable = false;
+            while is_symbol(tokens, i, Symbol::Ampersand) || is_keyword(tokens, i, "mut") {
+                mutable |= is_keyword(tokens, i, "mut");
+                i += 1;
+            }
+            let (expression, end) = unary(i, tokens, no_struct)?;
+            if !mutable {
+                return Ok((expression, end));
+            }
+            Ok((Node::MutableReference(Box::new(expression)), end))
+        }
+        _ => postfix(start, tokens, no_struct),
+    }
+}
+
+// Field accesses, method calls and indexing following an expression
+fn postfix(
+    start: usize,
+    tokens: &[Token],
+    no_struct: bool,
+) -> Result<(Node, usize), Box<dyn Error>> {
+    let (mut node, mut i) = primary(start, tokens, no_struct)?;
+    // A block like `if` or `match` is not called by a `(` after it
+    let block_like = is_bracket_open(tokens, start, Bracket::Curly)
+        || ["if", "match", "loop", "while", "for", "unsafe"]
+            .iter()
+            .any(|keyword| is_keyword(tokens, start, keyword));
+    loop {
+        if is_symbol(tokens, i, Symbol::Dot) && !is_symbol(tokens, i + 1, Symbol::Dot) {
+            let name = match tokens.get(i + 1) {
+                // Tuple fields, `pair.0`
+                Some(Token::Char(c) | Token::Number(c)) => c.to_string(),
+                curr_token => {
+                    return Err(format!(
+                        "Parser Field: Expected Char/Number got: {:?} at {}",
+                        curr_token,
+                        i + 1
+                    )
+                    .into())
+                }
+            };
+            let (generics, next) = turbofish(i + 2, tokens)?;
+            if is_bracket_open(tokens, next, Bracket::Paren) {
+                let (input, end) = arguments(next + 1, tokens, Bracket::Paren)?;
+                node = Node::MethodCall {
+                    expression: Box::new(node),
+                    method: name,
+                    generics,
+                    input,
+                };
+                i = end;
+            } else {
+                node = Node::FieldAccess {
+                    expression: Box::new(node),
+                    field: name,
+                };
+                i += 2;
+            }
+            continue;
+        }
+
+        if is_symbol(tokens, i, Symbol::Question) {
+            node = Node::Try(Box::new(node));
+            i += 1;
+            continue;
+        }
+
+        if !block_like && is_bracket_open(tokens, i, Bracket::Paren) {
+            let (input, end) = arguments(i + 1, tokens, Bracket::Paren)?;
+            node = Node::Call {
+                callee: Box::new(node),
+                input,
+            };
+            i = end;
+            continue;
+        }
+
+        if is_bracket_open(tokens, i, Bracket::Square) {
+            let (index, end) = expression(i + 1, tokens, false)?;
+            if !is_bracket_close(tokens, end, Bracket::Square) {
+                return Err(format!(
+                    "Parser Index: Expected SquareClose got: {:?} at {end}",
+                    tokens.get(end)
+                )
+                .into());
+            }
+            node = Node::Index {
+                expression: Box::new(node),
+                index: Box::new(index),
+            };
+            i = end + 1;
+            continue;
+        }
+
+        break;
+    }
+    Ok((node, i))
+}
+
+// `::<A, B>` at `start`, returns the types and the index after it
+fn turbofish(start: usize, tokens: &[Token]) -> Result<(Vec<Type>, usize), Box<dyn Error>> {
+    let mut generics = Vec::new();
+    if !(is_symbol(tokens, start, Symbol::DoubleDot)
+        && is_symbol(tokens, start + 1, Symbol::DoubleDot)
+        && is_symbol(tokens, start + 2, Symbol::Lesser))
+    {
+        return Ok((generics, start));
+    }
+    let mut i = start + 3;
+    while !is_symbol(tokens, i, Symbol::Grater) {
+        let (ty, end) = parse_type(i, tokens)?;
+        generics.push(ty);
+        i = end;
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        } else if !is_symbol(tokens, i, Symbol::Grater) {
+            return Err(format!(
+                "Parser Turbofish: Expected Colon/Grater got: {:?} at {i}",
+                tokens.get(i)
+            )
+            .into());
+        }
+    }
+    Ok((generics, i + 1))
+}
+
+fn primary(
+    start: usize,
+    tokens: &[Token],
+    no_struct: bool,
+) -> Result<(Node, usize), Box<dyn Error>> {
+    let i = start;
+    match tokens.get(i) {
+        Some(Token::Number(n)) => {
+            // `1.5` is tokenised as a number, a dot and another number
+            let (text, end) = match tokens.get(i + 2) {
+                Some(Token::Number(fraction)) if is_symbol(tokens, i + 1, Symbol::Dot) => {
+                    (format!("{n}.{fraction}"), i + 3)
+                }
+                _ => (n.to_string(), i + 1),
+            };
+            // A suffixed literal is cast to its type
+            let node = match number_literal(&text) {
+                (literal, Some(ty)) => Node::Cast {
+                    expression: Box::new(Node::NumberLiteral(literal)),
+                    ty,
+                },
+                (literal, None) => Node::NumberLiteral(literal),
+            };
+            Ok((node, end))
+        }
+        Some(Token::String(s)) => Ok((Node::StringLiteral(s.to_string()), i + 1)),
+        Some(Token::Character(c)) => Ok((Node::CharLiteral(c.to_string()), i + 1)),
+        Some(Token::BracketOpen(Bracket::Paren)) => {
+            let (mut elements, end) = arguments(i + 1, tokens, Bracket::Paren)?;
+            // `(value)` only groups, `(value,)` is a tuple
+            if elements.len() == 1 && !is_symbol(tokens, end - 2, Symbol::Colon) {
+                return Ok((elements.remove(0), end));
+            }
+            Ok((Node::TupleLiteral(elements), end))
+        }
+        Some(Token::BracketOpen(Bracket::Square)) => {
+            if !is_bracket_close(tokens, i + 1, Bracket::Square) {
+                let (value, end) = expression(i + 1, tokens, false)?;
+                if is_symbol(tokens, end, Symbol::SemiColon) {
+                    let (count, end) = expression(end + 1, tokens, false)?;
+                    if !is_bracket_close(tokens, end, Bracket::Square) {
+                        return Err(format!(
+                            "Parser Array: Expected SquareClose got: {:?} at {end}",
+                            tokens.get(end)
+                        )
+                        .into());
+                    }
+                    let value = Box::new(value);
+                    let count = Box::new(count);
+                    return Ok((Node::ArrayRepeat { value, count }, end + 1));
+                }
+            }
+            let (elements, end) = arguments(i + 1, tokens, Bracket::Square)?;
+            Ok((Node::ArrayLiteral(elements), end))
+        }
+        Some(Token::BracketOpen(Bracket::Curly)) => {
+            let (body, end) = block(i, tokens)?;
+            Ok((Node::Block(body), end))
+        }
+        Some(Token::Symbol(Symbol::Pipe)) => closure(i, tokens, false),
+        Some(Token::Char(c)) if c == "move" && is_symbol(tokens, i + 1, Symbol::Pipe) => {
+            closure(i + 1, tokens, true)
+        }
+        Some(Token::Char(c)) if c == "if" => if_expression(i + 1, tokens),
+        Some(Token::Char(c)) if c == "match" => match_expression(i + 1, tokens),
+        Some(Token::Char(c)) if c == "break" => Ok((Node::Break, i + 1)),
+        Some(Token::Char(c)) if c == "continue" => Ok((Node::Continue, i + 1)),
+        Some(Token::Char(c)) if c == "return" => {
+            if matches!(
+                tokens.get(i + 1),
+                None | Some(Token::Symbol(Symbol::SemiColon | Symbol::Colon))
+                    | Some(Token::BracketClose(_))
+            ) {
+                return Ok((Node::Return(None), i + 1));
+            }
+            let (value, end) = expression(i + 1, tokens, no_struct)?;
+            Ok((Node::Return(Some(Box::new(value))), end))
+        }
+        Some(Token::Char(_)) => {
+            let (name, generics, i) = generic_path(i, tokens)?;
+            if is_symbol(tokens, i, Symbol::Exclamation) {
+                // `vec![value; count]` is read like an array
+                if is_bracket_open(tokens, i + 1, Bracket::Square) {
+                    let (array, end) = primary(i + 1, tokens, false)?;
+                    let input = match array {
+                        Node::ArrayLiteral(elements) => elements,
+                        repeat => vec![repeat],
+                    };
+                    return Ok((Node::Macro { name, input }, end));
+                }
+                if let Some(Token::BracketOpen(bracket)) = tokens.get(i + 1) {
+                    let (input, end) = arguments(i + 2, tokens, *bracket)?;
+                    return Ok((Node::Macro { name, input }, end));
+                }
+            }
+            if is_bracket_open(tokens, i, Bracket::Paren) {
+                let (input, end) = arguments(i + 1, tokens, Bracket::Paren)?;
+                return Ok((
+                    Node::FunctionCall {
+                        name,
+                        generics,
+                        input,
+                    },
+                    end,
+                ));
+            }
+            if !no_struct && is_bracket_open(tokens, i, Bracket::Curly) {
+                return struct_literal(name, i + 1, tokens);
+            }
+            Ok((Node::VeriableCall(name), i))
+        }
+        curr_token => Err(format!(
+            "Parser Expression: Expected Char/Number/String got: {:?} at {i}",
+            curr_token
+        )
+        .into()),
+    }
+}
+
+// `start` is at the opening Pipe
+fn closure(start: usize, tokens: &[Token], capture: bool) -> Result<(Node, usize), Box<dyn Error>> {
+    let mut input = Vec::new();
+    let mut i = start + 1;
+    while !is_symbol(tokens, i, Symbol::Pipe) {
+        let (pattern, end) = single_pattern(i, tokens)?;
+        i = end;
+        let mut ty = None;
+        if is_symbol(tokens, i, Symbol::DoubleDot) {
+            let (annotation, end) = parse_type(i + 1, tokens)?;
+            ty = Some(annotation);
+            i = end;
+        }
+        input.push((pattern, ty));
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        } else if !is_symbol(tokens, i, Symbol::Pipe) {
+            return Err(format!(
+                "Parser Closure: Expected Colon/Pipe got: {:?} at {i}",
+                tokens.get(i)
+            )
+            .into());
+        }
+    }
+    i += 1;
+    // A return type requires a block body
+    if is_symbol(tokens, i, Symbol::Minus) && is_symbol(tokens, i + 1, Symbol::Grater) {
+        let (_, end) = parse_type(i + 2, tokens)?;
+        i = end;
+    }
+    let (body, end) = expression(i, tokens, false)?;
+    Ok((
+        Node::Closure {
+            input,
+            body: Box::new(body),
+            capture,
+        },
+        end,
+    ))
+}
+
+// Parses comma separated expressions up to the closing bracket, returns the index after it
+fn arguments(
+    start: usize,
+    tokens: &[Token],
+    close: Bracket,
+) -> Result<(Vec<Node>, usize), Box<dyn Error>> {
+    let mut input = Vec::new();
+    let mut i = start;
+    while !is_bracket_close(tokens, i, close) {
+        let (argument, end) = expression(i, tokens, false)?;
+        input.push(argument);
+        i = end;
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        } else if !is_bracket_close(tokens, i, close) {
+            return Err(format!(
+                "Parser Arguments: Expected Colon/{:?}Close got: {:?} at {i}",
+                close,
+                tokens.get(i)
+            )
+            .into());
+        }
+    }
+    Ok((input, i + 1))
+}
+
+// `start` is just after the `{` of `Name { field: value, .. }`
+fn struct_literal(
+    name: String,
+    start: usize,
+    tokens: &[Token],
+) -> Result<(Node, usize), Box<dyn Error>> {
+    let mut fields = Vec::new();
+    let mut i = start;
+    while !is_bracket_close(tokens, i, Bracket::Curly) {
+        let field = match tokens.get(i) {
+            Some(Token::Char(c)) => c.to_string(),
+            curr_token => {
+                return Err(format!(
+                    "Parser Struct: Expected Char/CurlyClose got: {:?} at {i}",
+                    curr_token
+                )
+                .into())
+            }
+        };
+        if is_symbol(tokens, i + 1, Symbol::DoubleDot) {
+            let (value, end) = expression(i + 2, tokens, false)?;
+            fields.push((field, value));
+            i = end;
+        } else {
+            // Shorthand `Rect { w, h }`
+            fields.push((field.clone(), Node::VeriableCall(field)));
+            i += 1;
+        }
+        if is_symbol(tokens, i, Symbol::Colon) {
+            i += 1;
+        }
+    }
+    Ok((Node::StructLiteral { name, fields }, i + 1))
+}
+////////////////////
+///CODE GENERATOR///
+////////////////////
+// Precedence of the generated JS, used to decide where parentheses are needed
+const ATOM: u8 = 20;
+const UNARY: u8 = 15;
+const ASSIGNMENT: u8 = 2;
+
+fn operator_precedence(operator: Operator) -> u8 {
+    match operator {
+        Operator::Multiply | Operator::Divide | Operator::Modulo => 13,
+        Operator::Add | Operator::Subtract => 12,
+        Operator::ShiftLeft | Operator::ShiftRight => 11,
+        Operator::Lesser | Operator::LesserEqual | Operator::Greater | Operator::GreaterEqual => 10,
+        Operator::Equal | Operator::NotEqual => 9,
+        Operator::BitAnd => 8,
+        Operator::BitXor => 7,
+        Operator::BitOr => 6,
+        Operator::And => 5,
+        Operator::Or => 4,
+    }
+}
+
+fn operator_js(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Add => "+",
+        Operator::Subtract => "-",
+        Operator::Multiply => "*",
+        Operator::Divide => "/",
+        Operator::Modulo => "%",
+        Operator::Equal => "===",
+        Operator::NotEqual => "!==",
+        Operator::Lesser => "<",
+        Operator::LesserEqual => "<=",
+        Operator::Greater => ">",
+        Operator::GreaterEqual => ">=",
+        Operator::And => "&&",
+        Operator::Or => "||",
+        Operator::BitAnd => "&",
+        Operator::BitOr => "|",
+        Operator::BitXor => "^",
+        Operator::ShiftLeft => "<<",
+        Operator::ShiftRight => ">>",
+    }
+}
+
+// Parenthesises generated code that binds looser than `min`
+fn wrap((code, precedence): (String, u8), min: u8) -> String {
+    if precedence < min {
+        format!("({code})")
+    } else {
+        code
+    }
+}
+
+// Joins pattern tests, no tests means the pattern always matches
+fn conjunction(tests: &[String]) -> String {
+    match tests {
+        [] => "true".to_string(),
+        [test] => test.to_string(),
+        _ => tests
+            .iter()
+            .map(|test| {
+                if test.contains("||") {
+                    format!("({test})")
+                } else {
+                    test.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("&&"),
+    }
+}
+
+fn is_identifier(code: &str) -> bool {
+    !code.starts_with(|c: char| c.is_ascii_digit())
+        && code
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+// Statements that can give a block its value
+fn is_expression(node: &Node) -> bool {
+    !matches!(
+        node,
+        Node::NewLine
+            | Node::Veriable { .. }
+            | Node::Function { .. }
+            | Node::Enum { .. }
+            | Node::Struct { .. }
+            | Node::Impl { .. }
+            | Node::Trait { .. }
+            | Node::TypeAlias { .. }
+            | Node::WhileLoop { .. }
+            | Node::ForLoop { .. }
+    )
+}
+
+// Expressions that have to be lowered to statements
+fn is_branching(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Match { .. }
+            | Node::Block(_)
+            | Node::IfStatement {
+                else_body: Some(_),
+                ..
+            }
+    )
+}
+
+// Whether a branching expression evaluates to a `&mut`
+fn returns_reference(node: &Node) -> bool {
+    match node {
+        Node::MutableReference(_) => true,
+        Node::Block(body) => body.last().is_some_and(returns_reference),
+        Node::IfStatement {
+            body,
+            else_body: Some(else_body),
+            ..
+        } => [body, else_body]
+            .iter()
+            .any(|body| body.last().is_some_and(returns_reference)),
+        Node::Match { arms, .. } => arms.iter().any(|arm| returns_reference(&arm.body)),
+        _ => false,
+    }
+}
+
+// A closure taking `&mut`s, the names its parameters destructure bind `ref mut`
+fn reference_parameters(node: &Node) -> Node {
+    fn nested(pattern: &Pattern) -> Pattern {
+        let all = |patterns: &[Pattern]| patterns.iter().map(nested).collect();
+        match pattern {
+            Pattern::Binding {
+                name,
+                pattern: None,
+            } => Pattern::RefMut(name.to_string()),
+            Pattern::Binding {
+                name,
+                pattern: Some(pattern),
+            } => Pattern::Binding {
+                name: name.to_string(),
+                pattern: Some(Box::new(nested(pattern))),
+            },
+            Pattern::Tuple(patterns) => Pattern::Tuple(all(patterns)),
+            Pattern::Slice(patterns) => Pattern::Slice(all(patterns)),
+            Pattern::TupleStruct { name, fields } => Pattern::TupleStruct {
+                name: name.to_string(),
+                fields: all(fields),
+            },
+            Pattern::Struct { name, fields } => Pattern::Struct {
+                name: name.to_string(),
+                fields: fields
+                    .iter()
+                    .map(|(field, pattern)| (field.to_string(), nested(pattern)))
+                    .collect(),
+            },
+            pattern => pattern.clone(),
+        }
+    }
+    match node {
+        Node::Closure {
+            input,
+            body,
+            capture,
+        } => Node::Closure {
+            // A plain parameter is the `&mut` itself
+            input: input
+                .iter()
+                .map(|(pattern, ty)| match pattern {
+                    Pattern::Binding { pattern: None, .. } => (pattern.clone(), ty.clone()),
+                    pattern => (nested(pattern), ty.clone()),
+                })
+                .collect(),
+            body: body.clone(),
+            capture: *capture,
+        },
+        node => node.clone(),
+    }
+}
+
+// Whether a pattern binds with `ref mut`, which needs the place it matches
+fn has_ref_mut(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::RefMut(_) => true,
+        Pattern::Binding {
+            pattern: Some(pattern),
+            ..
+        } => has_ref_mut(pattern),
+        Pattern::Or(patterns)
+        | Pattern::Tuple(patterns)
+        | Pattern::Slice(patterns)
+        | Pattern::TupleStruct {
+            fields: patterns, ..
+        } => patterns.iter().any(has_ref_mut),
+        Pattern::Struct { fields, .. } => fields.iter().any(|(_, pattern)| has_ref_mut(pattern)),
+        _ => false,
+    }
+}
+
+// Whether generated code names a variable, field or element that can be assigned to
+fn is_place_code(code: &str) -> bool {
+    !code.ends_with(')')
+        && code
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_$.[]".contains(c))
+}
+
+fn is_rest(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Rest => true,
+        Pattern::Binding {
+            pattern: Some(pattern),
+            ..
+        } => is_rest(pattern),
+        _ => false,
+    }
+}
+
+fn returns_value(output: &Option<Type>) -> bool {
+    match output {
+        Some(Type::Tuple(types)) => !types.is_empty(),
+        Some(_) => true,
+        None => false,
+    }
+}
+
+fn is_string(ty: &Type) -> bool {
+    matches!(ty.name(), Some("str" | "String"))
+}
+
+fn is_integer(ty: &Type) -> bool {
+    ty.name().is_some_and(|name| integer_range(name).is_some())
+}
+
+fn is_float(ty: &Type) -> bool {
+    matches!(ty.name(), Some("f32" | "f64"))
+}
+
+// The JS regex testing a `char` classification method
+fn char_class(method: &str) -> Option<&'static str> {
+    Some(match method {
+        "is_alphabetic" => r"/\p{Alphabetic}/u",
+        "is_numeric" => r"/\p{N}/u",
+        "is_alphanumeric" => r"/[\p{Alphabetic}\p{N}]/u",
+        "is_whitespace" => r"/\p{White_Space}/u",
+        "is_uppercase" => r"/\p{Uppercase}/u",
+        "is_lowercase" => r"/\p{Lowercase}/u",
+        "is_control" => r"/\p{Cc}/u",
+        "is_ascii" => r"/[\0-\x7f]/",
+        "is_ascii_alphabetic" => "/[A-Za-z]/",
+        "is_ascii_uppercase" => "/[A-Z]/",
+        "is_ascii_lowercase" => "/[a-z]/",
+        "is_ascii_digit" => "/[0-9]/",
+        "is_ascii_hexdigit" => "/[0-9A-Fa-f]/",
+        "is_ascii_alphanumeric" => "/[0-9A-Za-z]/",
+        "is_ascii_whitespace" => r"/[\t\n\f\r ]/",
+        "is_ascii_punctuation" => r"/[!-\/:-@[-`{-~]/",
+        "is_ascii_graphic" => "/[!-~]/",
+        "is_ascii_control" => r"/[\0-\x1f\x7f]/",
+        _ => return None,
+    })
+}
+
+// The result type of a `char` method, `None` for the ones without a JS counterpart
+fn char_type(method: &str) -> Option<Type> {
+    Some(match method {
+        "is_digit" => Type::named("bool"),
+        "to_ascii_uppercase" | "to_ascii_lowercase" => Type::named("char"),
+        "to_uppercase" | "to_lowercase" => Type::Array(Box::new(Type::named("char")), None),
+        "len_utf8" | "len_utf16" => Type::named("usize"),
+        "to_digit" => Type::Named {
+            name: "Option".to_string(),
+            generics: vec![Type::named("u32")],
+        },
+        _ => char_class(method).map(|_| Type::named("bool"))?,
+    })
+}
+
+// Whether an integer type is signed and how many bits it has, the pointer sized ones have 64
+fn integer_width(name: &str) -> Option<(bool, u32)> {
+    let (signed, bits) = match name.split_at_checked(1)? {
+        ("i", bits) => (true, bits),
+        ("u", bits) => (false, bits),
+        _ => return None,
+    };
+    let bits = if bits == "size" {
+        64
+    } else {
+        bits.parse().ok()?
+    };
+    Some((signed, bits))
+}
+
+// Integer literals and arithmetic on them, whose type is the one they are used as
+fn is_integer_literal(node: &Node) -> bool {
+    match node {
+        Node::NumberLiteral(n) => !is_float_literal(n),
+        Node::Unary {
+            operator: Symbol::Minus | Symbol::Exclamation,
+            expression,
+        } => is_integer_literal(expression),
+        Node::Binary {
+            left,
+            operator:
+                Operator::Add
+                | Operator::Subtract
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo
+                | Operator::BitAnd
+                | Operator::BitOr
+                | Operator::BitXor
+                | Operator::ShiftLeft
+                | Operator::ShiftRight,
+            right,
+        } => is_integer_literal(left) && is_integer_literal(right),
+        _ => false,
+    }
+}
+
+// A suffixed literal like `5i64`
+fn is_literal_cast(node: &Node) -> bool {
+    matches!(node, Node::Cast { expression, .. } if matches!(expression.as_ref(), Node::NumberLiteral(_)))
+}
+
+// The length written in `[T; 3]` or `[0; 3usize]`
+fn literal_length(count: &Node) -> Option<usize> {
+    match count {
+        Node::NumberLiteral(n) => n.parse().ok(),
+        Node::Cast { expression, .. } if is_literal_cast(count) => literal_length(expression),
+        _ => None,
+    }
+}
+
+// Scalars, which JS passes by value too
+fn is_primitive(ty: &Type) -> bool {
+    ty.name().is_some_and(|name| {
+        integer_range(name).is_some() || matches!(name, "f32" | "f64" | "bool" | "char" | "str")
+    })
+}
+
+// Whether `node` evaluates to the local `name` or to arithmetic on it, which
+// takes the width of the integers it meets
+fn carries(node: &Node, name: &str) -> bool {
+    match node {
+        Node::VeriableCall(local) => local == name,
+        Node::Deref(expression)
+        | Node::Unary {
+            operator: Symbol::Minus | Symbol::Exclamation,
+            expression,
+        } => carries(expression, name),
+        Node::Binary {
+            left,
+            operator: Operator::ShiftLeft | Operator::ShiftRight,
+            ..
+        } => carries(left, name),
+        Node::Binary {
+            left,
+            operator:
+                Operator::Add
+                | Operator::Subtract
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo
+                | Operator::BitAnd
+                | Operator::BitOr
+                | Operator::BitXor,
+            right,
+        } => carries(left, name) || carries(right, name),
+        Node::Block(body) => body.last().is_some_and(|node| carries(node, name)),
+        _ => false,
+    }
+}
+
+// The values JS copies on assignment, a `&mut` to one has to be a cell
+fn is_scalar(ty: &Type) -> bool {
+    is_primitive(ty) || is_string(ty)
+}
+
+// `Vec<T>`, arrays and slices are all JS arrays
+fn is_vec(ty: &Type) -> bool {
+    matches!(ty, Type::Array(..)) || ty.name() == Some("Vec")
+}
+
+fn element_type(ty: &Type) -> Type {
+    match ty {
+        Type::Array(element, _) => element.as_ref().clone(),
+        Type::Named { name, .. } if name == "Vec" || name == "VecDeque" || is_set(ty) => {
+            generic_argument(ty, 0)
+        }
+        _ => Type::Unknown,
+    }
+}
+
+fn is_iterator(ty: &Type) -> bool {
+    ty.name() == Some("Iterator")
+}
+
+// The type of the values a `for` loop or iterator over `ty` produces
+fn item_type(ty: &Type) -> Type {
+    match ty {
+        _ if is_map(ty) => Type::Tuple(vec![generic_argument(ty, 0), generic_argument(ty, 1)]),
+        _ if is_iterator(ty) || ty.name() == Some("Option") => generic_argument(ty, 0),
+        _ => element_type(ty),
+    }
+}
+
+fn generic_of_tuple(ty: &Type, n: usize) -> Type {
+    match ty {
+        Type::Tuple(types) => types.get(n).cloned().unwrap_or(Type::Unknown),
+        _ => Type::Unknown,
+    }
+}
+
+fn iterator(item: Type) -> Type {
+    Type::Named {
+        name: "Iterator".to_string(),
+        generics: vec![item],
+    }
+}
+
+fn generic_argument(ty: &Type, n: usize) -> Type {
+    match ty {
+        Type::Named { generics, .. } => generics.get(n).cloned().unwrap_or(Type::Unknown),
+        _ => Type::Unknown,
+    }
+}
+
+// `HashMap` is a JS `Map`, `BTreeMap` a runtime class with the same interface
+fn is_map(ty: &Type) -> bool {
+    matches!(ty.name(), Some("HashMap" | "BTreeMap"))
+}
+
+fn is_set(ty: &Type) -> bool {
+    matches!(ty.name(), Some("HashSet" | "BTreeSet"))
+}
+
+fn is_collection(resolved: &str) -> bool {
+    matches!(
+        resolved.split_once("::"),
+        Some((
+            "HashMap" | "HashSet" | "BTreeMap" | "BTreeSet" | "VecDeque",
+            _
+        ))
+    )
+}
+
+// Values of these types are immutable in JS, so sharing them is the same as copying
+fn is_copy(ty: &Type) -> bool {
+    match ty {
+        Type::Named { name, .. } => {
+            integer_range(name).is_some()
+                || matches!(name.as_str(), "f32" | "f64" | "bool" | "char" | "str")
+        }
+        _ => false,
+    }
+}
+
+// Helpers the generated code can call, only the used ones are emitted.
+// Each one lists the helpers it calls
+const RUNTIME: [(&str, &[&str], &str); 31] = [
+    (
+        "$panic",
+        &[],
+        "function $panic(message){
+throw new globalThis.Error(message);
+}
+",
+    ),
+    (
+        "$cell",
+        &[],
+        "function $cell(get, set){
+return {get value(){return get();}, set value(value){set(value);}};
+}
+",
+    ),
+    (
+        "$index",
+        &["$panic"],
+        "const $index={
+check(values, index){
+if (index>=values.length){
+$panic(`index out of bounds: the len is ${values.length} but the index is ${index}`);
+}
+return index;
+},
+at(values, index){
+return values[$index.check(values, index)];
+},
+set(values, index, value){
+values[$index.check(values, index)]=value;
+},
+};
+",
+    ),
+    (
+        "$char",
+        &["$panic"],
+        "const $char={
+to_ascii_uppercase(char){
+return /[a-z]/.test(char)?char.toUpperCase():char;
+},
+to_ascii_lowercase(char){
+return /[A-Z]/.test(char)?char.toLowerCase():char;
+},
+to_digit(char, radix){
+if (radix<2||radix>36){
+$panic(\"to_digit: invalid radix -- radix must be in the range 2 to 36 inclusive\");
+}
+const digit=/[0-9A-Za-z]/.test(char)?parseInt(char, 36):radix;
+return digit<radix?Option.Some(digit):Option.None;
+},
+};
+",
+    ),
+    (
+        "$int",
+        &["$panic"],
+        "const $int={
+ranges:{i8:[-128, 127], i16:[-32768, 32767], i32:[-2147483648, 2147483647], i64:[-(2n**63n), 2n**63n-1n], i128:[-(2n**127n), 2n**127n-1n], u8:[0, 255], u16:[0, 65535], u32:[0, 4294967295], u64:[0n, 2n**64n-1n], u128:[0n, 2n**128n-1n], usize:[0, Number.MAX_SAFE_INTEGER], isize:[Number.MIN_SAFE_INTEGER, Number.MAX_SAFE_INTEGER]},
+bits(type){
+return type.endsWith(\"size\")?64:Number(type.slice(1));
+},
+check(value, type, operation){
+const [min, max]=$int.ranges[type];
+if (value<min||value>max){
+$panic(`attempt to ${operation} with overflow`);
+}
+return value;
+},
+div(a, b, type){
+if (b==0){
+$panic(\"attempt to divide by zero\");
+}
+if (b==-1&&a===$int.ranges[type]?.[0]&&a<0){
+$panic(\"attempt to divide with overflow\");
+}
+return typeof a===\"bigint\"?a/b:Math.trunc(a/b);
+},
+rem(a, b, type){
+if (b==0){
+$panic(\"attempt to calculate the remainder with a divisor of zero\");
+}
+if (b==-1&&a===$int.ranges[type]?.[0]&&a<0){
+$panic(\"attempt to calculate the remainder with overflow\");
+}
+return a%b;
+},
+rem_euclid(a, b, type){
+const r=type==null?a%b:$int.rem(a, b, type);
+return r<0?r+(b<0?-b:b):r;
+},
+shift(amount, bits, direction){
+if (amount<0||amount>=bits){
+$panic(`attempt to shift ${direction} with overflow`);
+}
+return Number(amount);
+},
+checked(value, type){
+const [min, max]=$int.ranges[type];
+return value<min||value>max?Option.None:Option.Some(value);
+},
+checked_div(a, b, type){
+return b==0||b==-1&&a===$int.ranges[type][0]&&a<0?Option.None:Option.Some($int.div(a, b, type));
+},
+checked_rem(a, b, type){
+return b==0||b==-1&&a===$int.ranges[type][0]&&a<0?Option.None:Option.Some(a%b);
+},
+saturate(value, type){
+const [min, max]=$int.ranges[type];
+if (value!==value){
+return typeof min===\"bigint\"?0n:0;
+}
+if (value<=min||value>=max){
+return value<=min?min:max;
+}
+if (typeof value===\"number\"){
+value=Math.trunc(value);
+}
+return typeof min===\"bigint\"?BigInt(value):value;
+},
+pow(a, n, type, mode){
+const [min, max]=$int.ranges[type];
+const bits=$int.bits(type);
+let value=BigInt(a)**BigInt(n);
+if (mode===\"wrapping\"){
+value=type[0]===\"i\"?BigInt.asIntN(bits, value):BigInt.asUintN(bits, value);
+} else if (value<min||value>max){
+if (mode===\"checked\"){
+return Option.None;
+}
+if (mode===\"saturating\"){
+return value<min?min:max;
+}
+$panic(\"attempt to multiply with overflow\");
+}
+const result=typeof a===\"bigint\"?value:Number(value);
+return mode===\"checked\"?Option.Some(result):result;
+},
+leading_zeros(value, type){
+const bits=$int.bits(type);
+const digits=BigInt.asUintN(bits, BigInt(value)).toString(2);
+return digits===\"0\"?bits:bits-digits.length;
+},
+trailing_zeros(value, type){
+const digits=BigInt.asUintN($int.bits(type), BigInt(value)).toString(2);
+return digits===\"0\"?$int.bits(type):digits.length-1-digits.lastIndexOf(\"1\");
+},
+count_ones(value, type){
+return BigInt.asUintN($int.bits(type), BigInt(value)).toString(2).replaceAll(\"0\", \"\").length;
+},
+};
+",
+    ),
+    (
+        "$math",
+        &["$panic", "$display"],
+        "const $math={
+abs(value){
+return value<0?-value:value;
+},
+min(a, b){
+return a!==a?b:b!==b?a:b<a?b:a;
+},
+max(a, b){
+return a!==a?b:b!==b?a:b<a?a:b;
+},
+clamp(value, min, max, type){
+if (!(min<=max)){
+$panic(type==null?\"assertion failed: min <= max\":`min > max, or either was NaN. min = ${$float(min, type, true)}, max = ${$float(max, type, true)}`);
+}
+return value<min?min:value>max?max:value;
+},
+round(value){
+return value<0?-Math.round(-value):Math.round(value);
+},
+signum(value){
+return value!==value?NaN:value<0||Object.is(value, -0)?-1:1;
+},
+};
+",
+    ),
+    (
+        "$clone",
+        &[],
+        "function $clone(value){
+if (Array.isArray(value)){
+return value.map($clone);
+}
+if (value instanceof Map){
+return new value.constructor([...value].map(([key, entry])=>[$clone(key), $clone(entry)]));
+}
+if (value instanceof Set){
+return new value.constructor([...value].map($clone));
+}
+if (value===null||typeof value!==\"object\"){
+return value;
+}
+if (typeof value.clone===\"function\"){
+return value.clone();
+}
+return $clone_fields(value);
+}
+function $clone_fields(value){
+if (Object.isFrozen(value)){
+return value;
+}
+const copy=Object.create(Object.getPrototypeOf(value));
+for (const key of Object.keys(value)){
+copy[key]=$clone(value[key]);
+}
+return copy;
+}
+",
+    ),
+    (
+        "$eq",
+        &[],
+        "function $eq(a, b){
+if (a===b){
+return true;
+}
+if (a===null||typeof a!==\"object\"||b===null||typeof b!==\"object\"){
+return false;
+}
+if (typeof a.eq===\"function\"){
+return a.eq(b);
+}
+if (Array.isArray(a)){
+return a.length===b.length&&a.every((value, i)=>$eq(value, b[i]));
+}
+if (a instanceof Map){
+return a.size===b.size&&[...a].every(([key, value])=>b.has(key)&&$eq(value, b.get(key)));
+}
+if (a instanceof Set){
+return a.size===b.size&&[...a].every((value)=>b.has(value));
+}
+return $eq_fields(a, b);
+}
+function $eq_fields(a, b){
+if (a.constructor!==b.constructor){
+return false;
+}
+if (a.constructor.$collection!==undefined){
+return $eq([...a], [...b]);
+}
+const keys=Object.keys(a);
+return keys.length===Object.keys(b).length&&keys.every((key)=>$eq(a[key], b[key]));
+}
+",
+    ),
+    (
+        "Option",
+        &["$panic"],
+        "class Option{
+constructor($tag, $fields){
+this.$tag=$tag;
+Object.assign(this, $fields);
+}
+static Some(_0){
+return new Option(\"Some\", {0:_0});
+}
+static None=Object.freeze(new Option(\"None\", {}));
+is_some(){
+return this.$tag===\"Some\";
+}
+is_none(){
+return this.$tag===\"None\";
+}
+unwrap(){
+return this.expect(\"called `Option::unwrap()` on a `None` value\");
+}
+expect(message){
+if (this.$tag===\"None\"){
+$panic(message);
+}
+return this[0];
+}
+unwrap_or(value){
+return this.$tag===\"Some\"?this[0]:value;
+}
+unwrap_or_else(f){
+return this.$tag===\"Some\"?this[0]:f();
+}
+unwrap_or_default(value){
+return this.$tag===\"Some\"?this[0]:value;
+}
+as_ref(){
+return this;
+}
+as_mut(){
+return this;
+}
+static take(place){
+const value=place.value;
+place.value=Option.None;
+return value;
+}
+map(f){
+return this.$tag===\"Some\"?Option.Some(f(this[0])):this;
+}
+map_or(value, f){
+return this.$tag===\"Some\"?f(this[0]):value;
+}
+and_then(f){
+return this.$tag===\"Some\"?f(this[0]):this;
+}
+filter(f){
+return this.$tag===\"Some\"&&f(this[0])?this:Option.None;
+}
+or(other){
+return this.$tag===\"Some\"?this:other;
+}
+or_else(f){
+return this.$tag===\"Some\"?this:f();
+}
+ok_or(error){
+return this.$tag===\"Some\"?Result.Ok(this[0]):Result.Err(error);
+}
+ok_or_else(f){
+return this.$tag===\"Some\"?Result.Ok(this[0]):Result.Err(f());
+}
+}
+",
+    ),
+    // Nullable options are the value itself or `null`, their methods are plain functions
+    (
+        "$Option",
+        &["$panic"],
+        "const Option={Some:(value)=>value, None:null};
+const $Option={
+is_some(value){
+return value!==null;
+},
+is_none(value){
+return value===null;
+},
+unwrap(value){
+return $Option.expect(value, \"called `Option::unwrap()` on a `None` value\");
+},
+expect(value, message){
+if (value===null){
+$panic(message);
+}
+return value;
+},
+unwrap_or(value, other){
+return value!==null?value:other;
+},
+unwrap_or_else(value, f){
+return value!==null?value:f();
+},
+unwrap_or_default(value, other){
+return value!==null?value:other;
+},
+as_ref(value){
+return value;
+},
+as_mut(value){
+return value;
+},
+take(place){
+const value=place.value;
+place.value=null;
+return value;
+},
+map(value, f){
+return value!==null?f(value):null;
+},
+map_or(value, other, f){
+return value!==null?f(value):other;
+},
+and_then(value, f){
+return value!==null?f(value):null;
+},
+filter(value, f){
+return value!==null&&f(value)?value:null;
+},
+or(value, other){
+return value!==null?value:other;
+},
+or_else(value, f){
+return value!==null?value:f();
+},
+ok_or(value, error){
+return value!==null?Result.Ok(value):Result.Err(error);
+},
+ok_or_else(value, f){
+return value!==null?Result.Ok(value):Result.Err(f());
+},
+};
+",
+    ),
+    // Calls an option method on a value whose type is not known
+    (
+        "$call",
+        &["$Option"],
+        "function $call(value, method, ...input){
+if (value!==null&&typeof value[method]===\"function\"){
+return value[method](...input);
+}
+return $Option[method](value, ...input);
+}
+",
+    ),
+    (
+        "Result",
+        &["$panic"],
+        "class Result{
+constructor($tag, $fields){
+this.$tag=$tag;
+Object.assign(this, $fields);
+}
+static Ok(_0){
+return new Result(\"Ok\", {0:_0});
+}
+static Err(_0){
+return new Result(\"Err\", {0:_0});
+}
+is_ok(){
+return this.$tag===\"Ok\";
+}
+is_err(){
+return this.$tag===\"Err\";
+}
+unwrap(){
+return this.expect(\"called `Result::unwrap()` on an `Err` value\");
+}
+expect(message){
+if (this.$tag===\"Err\"){
+$panic(message+\": \"+JSON.stringify(this[0]));
+}
+return this[0];
+}
+unwrap_err(){
+if (this.$tag===\"Ok\"){
+$panic(\"called `Result::unwrap_err()` on an `Ok` value: \"+JSON.stringify(this[0]));
+}
+return this[0];
+}
+unwrap_or(value){
+return this.$tag===\"Ok\"?this[0]:value;
+}
+unwrap_or_else(f){
+return this.$tag===\"Ok\"?this[0]:f(this[0]);
+}
+map(f){
+return this.$tag===\"Ok\"?Result.Ok(f(this[0])):this;
+}
+map_err(f){
+return this.$tag===\"Err\"?Result.Err(f(this[0])):this;
+}
+and_then(f){
+return this.$tag===\"Ok\"?f(this[0]):this;
+}
+or_else(f){
+return this.$tag===\"Err\"?f(this[0]):this;
+}
+ok(){
+return this.$tag===\"Ok\"?Option.Some(this[0]):Option.None;
+}
+err(){
+return this.$tag===\"Err\"?Option.Some(this[0]):Option.None;
+}
+}
+",
+    ),
+    (
+        "Ordering",
+        &[],
+        "class Ordering{
+constructor($tag, $fields){
+this.$tag=$tag;
+Object.assign(this, $fields);
+}
+static Less=Object.freeze(new Ordering(\"Less\", {$disc:-1}));
+static Equal=Object.freeze(new Ordering(\"Equal\", {$disc:0}));
+static Greater=Object.freeze(new Ordering(\"Greater\", {$disc:1}));
+reverse(){
+return this===Ordering.Less?Ordering.Greater:this===Ordering.Greater?Ordering.Less:this;
+}
+then(other){
+return this===Ordering.Equal?other:this;
+}
+then_with(f){
+return this===Ordering.Equal?f():this;
+}
+is_lt(){
+return this===Ordering.Less;
+}
+is_eq(){
+return this===Ordering.Equal;
+}
+is_gt(){
+return this===Ordering.Greater;
+}
+}
+",
+    ),
+    (
+        "$cmp",
+        &["Ordering"],
+        "function $cmp(a, b){
+if (typeof a?.cmp===\"function\"){
+return a.cmp(b);
+}
+if (typeof a?.partial_cmp===\"function\"){
+const order=a.partial_cmp(b);
+return order instanceof Ordering?order:order?.[0]??Ordering.Equal;
+}
+if (Array.isArray(a)){
+for (let i=0; i<a.length&&i<b.length; i++){
+const order=$cmp(a[i], b[i]);
+if (order!==Ordering.Equal){
+return order;
+}
+}
+return $cmp(a.length, b.length);
+}
+return a<b?Ordering.Less:a>b?Ordering.Greater:Ordering.Equal;
+}
+function $partial_cmp(a, b){
+return a!==a||b!==b?Option.None:Option.Some($cmp(a, b));
+}
+",
+    ),
+    (
+        "$cmp_fields",
+        &["$cmp"],
+        "function $cmp_fields(a, b, variants, partial){
+if (variants!==undefined&&a.$tag!==b.$tag){
+return $cmp(variants.indexOf(a.$tag), variants.indexOf(b.$tag));
+}
+for (const key of Object.keys(a)){
+if (key.startsWith(\"$\")){
+continue;
+}
+if (partial&&(a[key]!==a[key]||b[key]!==b[key])){
+return undefined;
+}
+const order=$cmp(a[key], b[key]);
+if (order!==Ordering.Equal){
+return order;
+}
+}
+return Ordering.Equal;
+}
+function $partial_cmp_fields(a, b, variants){
+const order=$cmp_fields(a, b, variants, true);
+return order===undefined?Option.None:Option.Some(order);
+}
+",
+    ),
+    (
+        "$compare",
+        &["Ordering"],
+        "function $compare(a, b){
+const order=typeof a.partial_cmp===\"function\"?a.partial_cmp(b):Option.Some(a.cmp(b));
+if (order===Option.None){
+return NaN;
+}
+const value=order instanceof Ordering?order:order[0];
+return value===Ordering.Less?-1:value===Ordering.Greater?1:0;
+}
+",
+    ),
+    (
+        "$vec",
+        &["$panic", "$cmp", "$clone"],
+        "const $vec={
+pop(vector){
+return vector.length===0?Option.None:Option.Some(vector.pop());
+},
+get(vector, index){
+return index<vector.length?Option.Some(vector[index]):Option.None;
+},
+first(vector){
+return vector.length===0?Option.None:Option.Some(vector[0]);
+},
+last(vector){
+return vector.length===0?Option.None:Option.Some(vector[vector.length-1]);
+},
+insert(vector, index, value){
+if (index>vector.length){
+$panic(`insertion index (is ${index}) should be <= len (is ${vector.length})`);
+}
+vector.splice(index, 0, value);
+},
+remove(vector, index){
+if (index>=vector.length){
+$panic(`removal index (is ${index}) should be < len (is ${vector.length})`);
+}
+return vector.splice(index, 1)[0];
+},
+swap(vector, a, b){
+for (const index of [a, b]){
+if (index>=vector.length){
+$panic(`index out of bounds: the len is ${vector.length} but the index is ${index}`);
+}
+}
+[vector[a], vector[b]]=[vector[b], vector[a]];
+},
+extend(vector, values){
+for (const value of values){
+vector.push(value);
+}
+},
+dedup(vector){
+let length=0;
+for (const value of vector){
+if (length===0||vector[length-1]!==value){
+vector[length++]=value;
+}
+}
+vector.length=length;
+},
+retain(vector, keep){
+let length=0;
+for (const value of vector){
+if (keep(value)){
+vector[length++]=value;
+}
+}
+vector.length=length;
+},
+sort(vector){
+vector.sort((a, b)=>$cmp(a, b).$disc);
+},
+sort_by(vector, compare){
+vector.sort((a, b)=>compare(a, b).$disc);
+},
+sort_by_key(vector, key){
+vector.sort((a, b)=>$cmp(key(a), key(b)).$disc);
+},
+windows(vector, size){
+if (size===0){
+$panic(\"window size must be non-zero\");
+}
+return Array.from({length:Math.max(vector.length-size+1, 0)}, (_, i)=>vector.slice(i, i+size));
+},
+chunks(vector, size){
+if (size===0){
+$panic(\"chunk size must be non-zero\");
+}
+return Array.from({length:Math.ceil(vector.length/size)}, (_, i)=>vector.slice(i*size, i*size+size));
+},
+split_off(vector, at){
+if (at>vector.length){
+$panic(`\\`at\\` split index (is ${at}) should be <= len (is ${vector.length})`);
+}
+return vector.splice(at);
+},
+split_at(vector, mid){
+if (mid>vector.length){
+$panic(\"mid > len\");
+}
+return [vector.slice(0, mid), vector.slice(mid)];
+},
+drain(vector, start=0, end=vector.length){
+if (start>end){
+$panic(`slice index starts at ${start} but ends at ${end}`);
+}
+if (end>vector.length){
+$panic(`range end index ${end} out of range for slice of length ${vector.length}`);
+}
+return vector.splice(start, end-start);
+},
+resize(vector, length, value){
+while (vector.length<length){
+vector.push($clone(value));
+}
+vector.length=length;
+},
+binary_search(vector, value, zero=0){
+let size=vector.length;
+let base=0;
+if (size===0){
+return Result.Err(zero);
+}
+while (size>1){
+const half=Math.floor(size/2);
+if ($cmp(vector[base+half], value).$disc<=0){
+base+=half;
+}
+size-=half;
+}
+const order=$cmp(vector[base], value).$disc;
+const index=order<0?base+1:base;
+const found=typeof zero===\"bigint\"?BigInt(index):index;
+return order===0?Result.Ok(found):Result.Err(found);
+},
+};
+",
+    ),
+    (
+        "$iter",
+        &["$cmp", "$clone"],
+        "function $some(option){
+return option?.$tag===\"Some\"?option[0]:option;
+}
+class $Iter{
+constructor(iterator){
+this.$iterator=iterator;
+this.$peeked=undefined;
+}
+$step(){
+const step=this.$peeked??this.$iterator.next();
+this.$peeked=undefined;
+return step;
+}
+*$each(){
+for (let step=this.$step(); !step.done; step=this.$step()){
+yield step.value;
+}
+}
+$then(generator){
+return new $Iter(generator(this.$each()));
+}
+[Symbol.iterator](){
+return this.$each();
+}
+next(){
+const step=this.$step();
+return step.done?Option.None:Option.Some(step.value);
+}
+peek(){
+this.$peeked??=this.$iterator.next();
+return this.$peeked.done?Option.None:Option.Some(this.$peeked.value);
+}
+peekable(){
+return this;
+}
+by_ref(){
+return this;
+}
+map(f){
+return this.$then(function*(values){
+for (const value of values){
+yield f(value);
+}
+});
+}
+filter(f){
+return this.$then(function*(values){
+for (const value of values){
+if (f(value)){
+yield value;
+}
+}
+});
+}
+filter_map(f){
+return this.$then(function*(values){
+for (const value of values){
+const option=f(value);
+if (option!==Option.None){
+yield $some(option);
+}
+}
+});
+}
+flat_map(f){
+return this.map(f).flatten();
+}
+flatten(){
+return this.$then(function*(values){
+for (const value of values){
+yield* $iter(value);
+}
+});
+}
+take(n){
+const source=this;
+return new $Iter((function*(){
+for (let i=0; i<n; i++){
+const step=source.$step();
+if (step.done){
+return;
+}
+yield step.value;
+}
+})());
+}
+skip(n){
+return this.$then(function*(values){
+let i=0;
+for (const value of values){
+if (i++>=n){
+yield value;
+}
+}
+});
+}
+take_while(f){
+return this.$then(function*(values){
+for (const value of values){
+if (!f(value)){
+return;
+}
+yield value;
+}
+});
+}
+skip_while(f){
+return this.$then(function*(values){
+let skipping=true;
+for (const value of values){
+skipping&&=f(value);
+if (!skipping){
+yield value;
+}
+}
+});
+}
+map_while(f){
+return this.$then(function*(values){
+for (const value of values){
+const option=f(value);
+if (option===Option.None){
+return;
+}
+yield $some(option);
+}
+});
+}
+step_by(n){
+return this.$then(function*(values){
+let i=0;
+for (const value of values){
+if (i++%n===0){
+yield value;
+}
+}
+});
+}
+enumerate(start=0){
+return this.$then(function*(values){
+let i=start;
+for (const value of values){
+yield [i++, value];
+}
+});
+}
+zip(other){
+const source=this;
+const others=$iter(other);
+return new $Iter((function*(){
+for (let step=source.$step(); !step.done; step=source.$step()){
+const pair=others.$step();
+if (pair.done){
+return;
+}
+yield [step.value, pair.value];
+}
+})());
+}
+chain(other){
+return this.$then(function*(values){
+yield* values;
+yield* $iter(other);
+});
+}
+rev(){
+return $iter([...this].reverse());
+}
+inspect(f){
+return this.map((value)=>{
+f(value);
+return value;
+});
+}
+cloned(){
+return this.map($clone);
+}
+copied(){
+return this;
+}
+cycle(){
+return this.$then(function*(values){
+const seen=[];
+for (const value of values){
+seen.push(value);
+yield value;
+}
+while (seen.length>0){
+yield* seen;
+}
+});
+}
+collect(){
+return [...this];
+}
+collect_result(){
+const values=[];
+for (const value of this){
+if (value.$tag===\"Err\"){
+return value;
+}
+values.push(value[0]);
+}
+return Result.Ok(values);
+}
+collect_option(){
+const values=[];
+for (const value of this){
+if (value===Option.None){
+return Option.None;
+}
+values.push($some(value));
+}
+return Option.Some(values);
+}
+count(){
+let count=0;
+for (const _ of this){
+count++;
+}
+return count;
+}
+sum(zero=0){
+return this.fold(zero, (a, b)=>a+b);
+}
+product(one=1){
+return this.fold(one, (a, b)=>a*b);
+}
+fold(init, f){
+let accumulator=init;
+for (const value of this){
+accumulator=f(accumulator, value);
+}
+return accumulator;
+}
+reduce(f){
+const first=this.$step();
+return first.done?Option.None:Option.Some(this.fold(first.value, f));
+}
+for_each(f){
+for (const value of this){
+f(value);
+}
+}
+any(f){
+for (const value of this){
+if (f(value)){
+return true;
+}
+}
+return false;
+}
+all(f){
+for (const value of this){
+if (!f(value)){
+return false;
+}
+}
+return true;
+}
+position(f, start=0){
+let i=start;
+for (const value of this){
+if (f(value)){
+return Option.Some(i);
+}
+i++;
+}
+return Option.None;
+}
+find(f){
+for (const value of this){
+if (f(value)){
+return Option.Some(value);
+}
+}
+return Option.None;
+}
+find_map(f){
+for (const value of this){
+const option=f(value);
+if (option!==Option.None){
+return option;
+}
+}
+return Option.None;
+}
+last(){
+let last=Option.None;
+for (const value of this){
+last=Option.Some(value);
+}
+return last;
+}
+nth(n){
+return this.skip(n).next();
+}
+$best(key, compare, keep){
+let best=Option.None;
+let bestKey;
+for (const value of this){
+const valueKey=key(value);
+if (best===Option.None||keep(compare(valueKey, bestKey).$disc)){
+best=Option.Some(value);
+bestKey=valueKey;
+}
+}
+return best;
+}
+max(){
+return this.$best((value)=>value, $cmp, (order)=>order>=0);
+}
+min(){
+return this.$best((value)=>value, $cmp, (order)=>order<0);
+}
+max_by_key(f){
+return this.$best(f, $cmp, (order)=>order>=0);
+}
+min_by_key(f){
+return this.$best(f, $cmp, (order)=>order<0);
+}
+max_by(f){
+return this.$best((value)=>value, f, (order)=>order>=0);
+}
+min_by(f){
+return this.$best((value)=>value, f, (order)=>order<0);
+}
+partition(f){
+const yes=[];
+const no=[];
+for (const value of this){
+(f(value)?yes:no).push(value);
+}
+return [yes, no];
+}
+unzip(){
+const left=[];
+const right=[];
+for (const [a, b] of this){
+left.push(a);
+right.push(b);
+}
+return [left, right];
+}
+}
+function $iter(values){
+if (values instanceof $Iter){
+return values;
+}
+if (values?.$tag===\"Some\"){
+return $iter([values[0]]);
+}
+if (values?.$tag===\"None\"){
+return $iter([]);
+}
+return new $Iter(values[Symbol.iterator]());
+}
+function $range(start, end){
+return new $Iter((function*(){
+for (let i=start; i<end; i++){
+yield i;
+}
+})());
+}
+",
+    ),
+    (
+        "$key",
+        &[],
+        "function $key(value){
+if (typeof value===\"string\"){
+return JSON.stringify(value);
+}
+if (value===null||typeof value!==\"object\"){
+return String(value);
+}
+if (Array.isArray(value)){
+return \"[\"+value.map($key).join(\",\")+\"]\";
+}
+if (value instanceof Map||value instanceof Set||value.constructor.$collection!==undefined){
+return value.constructor.name+$key([...value]);
+}
+return value.constructor.name+\"{\"+Object.keys(value).map((key)=>key+\":\"+$key(value[key])).join(\",\")+\"}\";
+}
+",
+    ),
+    (
+        "$KeyMap",
+        &["$key"],
+        "class $KeyMap extends Map{
+get(key){
+return super.get($key(key))?.[1];
+}
+set(key, value){
+return super.set($key(key), [key, value]);
+}
+has(key){
+return super.has($key(key));
+}
+delete(key){
+return super.delete($key(key));
+}
+*keys(){
+for (const [key] of super.values()){
+yield key;
+}
+}
+*values(){
+for (const [, value] of super.values()){
+yield value;
+}
+}
+entries(){
+return super.values();
+}
+[Symbol.iterator](){
+return super.values();
+}
+}
+",
+    ),
+    (
+        "$KeySet",
+        &["$key"],
+        "class $KeySet extends Set{
+constructor(values=[]){
+super();
+this.$values=new Map();
+for (const value of values){
+this.add(value);
+}
+}
+add(value){
+const key=$key(value);
+if (!this.$values.has(key)){
+this.$values.set(key, value);
+}
+return super.add(key);
+}
+has(value){
+return super.has($key(value));
+}
+delete(value){
+const key=$key(value);
+this.$values.delete(key);
+return super.delete(key);
+}
+clear(){
+this.$values.clear();
+super.clear();
+}
+values(){
+return this.$values.values();
+}
+keys(){
+return this.$values.values();
+}
+[Symbol.iterator](){
+return this.$values.values();
+}
+}
+",
+    ),
+    (
+        "$map",
+        &["$panic", "$cell"],
+        "class $Entry{
+constructor(map, key){
+this.map=map;
+this.key=key;
+}
+or_insert(value){
+if (!this.map.has(this.key)){
+this.map.set(this.key, value);
+}
+return this.map.get(this.key);
+}
+or_insert_with(f){
+if (!this.map.has(this.key)){
+this.map.set(this.key, f());
+}
+return this.map.get(this.key);
+}
+and_modify(f){
+if (this.map.has(this.key)){
+const value=f(this.map.get(this.key));
+if (value!==undefined){
+this.map.set(this.key, value);
+}
+}
+return this;
+}
+set(value){
+this.map.set(this.key, value);
+}
+place(value){
+this.or_insert(value);
+return $cell(()=>this.map.get(this.key), ($v)=>this.map.set(this.key, $v));
+}
+place_with(f){
+return this.place(this.map.has(this.key)?undefined:f());
+}
+}
+const $map={
+insert(map, key, value){
+const old=map.has(key)?Option.Some(map.get(key)):Option.None;
+map.set(key, value);
+return old;
+},
+get(map, key){
+return map.has(key)?Option.Some(map.get(key)):Option.None;
+},
+remove(map, key){
+const old=$map.get(map, key);
+map.delete(key);
+return old;
+},
+index(map, key){
+if (!map.has(key)){
+$panic(\"key not found\");
+}
+return map.get(key);
+},
+entry(map, key){
+return new $Entry(map, key);
+},
+extend(map, entries){
+for (const [key, value] of entries){
+map.set(key, value);
+}
+},
+retain(map, keep){
+for (const [key, value] of [...map]){
+if (!keep(key, value)){
+map.delete(key);
+}
+}
+},
+};
+",
+    ),
+    (
+        "$set",
+        &[],
+        "const $set={
+insert(set, value){
+const added=!set.has(value);
+set.add(value);
+return added;
+},
+extend(set, values){
+for (const value of values){
+set.add(value);
+}
+},
+retain(set, keep){
+for (const value of [...set]){
+if (!keep(value)){
+set.delete(value);
+}
+}
+},
+union(a, b){
+return [...a, ...[...b].filter((value)=>!a.has(value))];
+},
+intersection(a, b){
+return [...a].filter((value)=>b.has(value));
+},
+difference(a, b){
+return [...a].filter((value)=>!b.has(value));
+},
+symmetric_difference(a, b){
+return [...$set.difference(a, b), ...$set.difference(b, a)];
+},
+is_subset(a, b){
+return [...a].every((value)=>b.has(value));
+},
+is_superset(a, b){
+return [...b].every((value)=>a.has(value));
+},
+is_disjoint(a, b){
+return [...a].every((value)=>!b.has(value));
+},
+};
+",
+    ),
+    (
+        "BTreeMap",
+        &["$cmp"],
+        "class BTreeMap{
+static $collection=\"map\";
+constructor(entries=[]){
+this.$keys=[];
+this.$values=[];
+for (const [key, value] of entries){
+this.set(key, value);
+}
+}
+$find(key){
+let low=0;
+let high=this.$keys.length;
+while (low<high){
+const middle=(low+high)>>1;
+const order=$cmp(this.$keys[middle], key).$disc;
+if (order===0){
+return [true, middle];
+}
+if (order<0){
+low=middle+1;
+}
+else{
+high=middle;
+}
+}
+return [false, low];
+}
+get size(){
+return this.$keys.length;
+}
+has(key){
+return this.$find(key)[0];
+}
+get(key){
+const [found, index]=this.$find(key);
+return found?this.$values[index]:undefined;
+}
+set(key, value){
+const [found, index]=this.$find(key);
+if (found){
+this.$values[index]=value;
+}
+else{
+this.$keys.splice(index, 0, key);
+this.$values.splice(index, 0, value);
+}
+return this;
+}
+delete(key){
+const [found, index]=this.$find(key);
+if (found){
+this.$keys.splice(index, 1);
+this.$values.splice(index, 1);
+}
+return found;
+}
+clear(){
+this.$keys.length=0;
+this.$values.length=0;
+}
+keys(){
+return this.$keys.values();
+}
+values(){
+return this.$values.values();
+}
+*entries(){
+for (let i=0; i<this.$keys.length; i++){
+yield [this.$keys[i], this.$values[i]];
+}
+}
+[Symbol.iterator](){
+return this.entries();
+}
+}
+",
+    ),
+    (
+        "BTreeSet",
+        &["BTreeMap"],
+        "class BTreeSet{
+static $collection=\"set\";
+constructor(values=[]){
+this.$map=new BTreeMap();
+for (const value of values){
+this.add(value);
+}
+}
+get size(){
+return this.$map.size;
+}
+has(value){
+return this.$map.has(value);
+}
+add(value){
+this.$map.set(value, true);
+return this;
+}
+delete(value){
+return this.$map.delete(value);
+}
+clear(){
+this.$map.clear();
+}
+values(){
+return this.$map.keys();
+}
+[Symbol.iterator](){
+return this.$map.keys();
+}
+}
+",
+    ),
+    (
+        "VecDeque",
+        &["$panic"],
+        "class VecDeque{
+static $collection=\"list\";
+constructor(){
+this.$buffer=new Array(4);
+this.$head=0;
+this.$length=0;
+}
+static from(values){
+const deque=new VecDeque();
+for (const value of values){
+deque.push_back(value);
+}
+return deque;
+}
+$grow(){
+if (this.$length===this.$buffer.length){
+const values=[...this];
+this.$buffer=new Array(this.$buffer.length*2);
+values.forEach((value, i)=>this.$buffer[i]=value);
+this.$head=0;
+}
+}
+$at(index){
+return this.$buffer[(this.$head+index)%this.$buffer.length];
+}
+$index(index){
+if (index>=this.$length){
+$panic(\"Out of bounds access\");
+}
+return this.$at(index);
+}
+len(){
+return this.$length;
+}
+is_empty(){
+return this.$length===0;
+}
+push_back(value){
+this.$grow();
+this.$buffer[(this.$head+this.$length)%this.$buffer.length]=value;
+this.$length++;
+}
+push_front(value){
+this.$grow();
+this.$head=(this.$head+this.$buffer.length-1)%this.$buffer.length;
+this.$buffer[this.$head]=value;
+this.$length++;
+}
+pop_back(){
+if (this.$length===0){
+return Option.None;
+}
+this.$length--;
+const index=(this.$head+this.$length)%this.$buffer.length;
+const value=this.$buffer[index];
+this.$buffer[index]=undefined;
+return Option.Some(value);
+}
+pop_front(){
+if (this.$length===0){
+return Option.None;
+}
+const value=this.$buffer[this.$head];
+this.$buffer[this.$head]=undefined;
+this.$head=(this.$head+1)%this.$buffer.length;
+this.$length--;
+return Option.Some(value);
+}
+front(){
+return this.$length===0?Option.None:Option.Some(this.$at(0));
+}
+back(){
+return this.$length===0?Option.None:Option.Some(this.$at(this.$length-1));
+}
+get(index){
+return index<this.$length?Option.Some(this.$at(index)):Option.None;
+}
+contains(value){
+return [...this].includes(value);
+}
+clear(){
+this.$buffer=new Array(4);
+this.$head=0;
+this.$length=0;
+}
+iter(){
+return [...this];
+}
+*[Symbol.iterator](){
+for (let i=0; i<this.$length; i++){
+yield this.$at(i);
+}
+}
+}
+",
+    ),
+    (
+        "$str",
+        &["$panic"],
+        "class ParseIntError{
+constructor(kind, $message){
+this.kind=Object.freeze({$tag:kind});
+this.$message=$message;
+}
+toString(){
+return this.$message;
+}
+}
+class ParseFloatError{
+constructor(kind, $message){
+this.kind=Object.freeze({$tag:kind});
+this.$message=$message;
+}
+toString(){
+return this.$message;
+}
+}
+class ParseCharError{
+constructor(kind, $message){
+this.kind=Object.freeze({$tag:kind});
+this.$message=$message;
+}
+toString(){
+return this.$message;
+}
+}
+class ParseBoolError{
+toString(){
+return \"provided string was not `true` or `false`\";
+}
+}
+const $str={
+len(text){
+let length=0;
+for (const char of text){
+const code=char.codePointAt(0);
+length+=code<0x80?1:code<0x800?2:code<0x10000?3:4;
+}
+return length;
+},
+bytes(text){
+return [...new TextEncoder().encode(text)];
+},
+char_indices(text){
+const indices=[];
+let index=0;
+for (const char of text){
+indices.push([index, char]);
+index+=$str.len(char);
+}
+return indices;
+},
+slice(text, start, end){
+const bytes=new TextEncoder().encode(text);
+end??=bytes.length;
+if (start>bytes.length||end>bytes.length){
+$panic(`byte index ${start>bytes.length?start:end} is out of bounds of \\`${text}\\``);
+}
+if (start>end){
+$panic(`begin <= end (${start} <= ${end}) when slicing \\`${text}\\``);
+}
+for (const index of [start, end]){
+if ((bytes[index]&0xc0)===0x80){
+let first=index;
+while ((bytes[first]&0xc0)===0x80){
+first--;
+}
+let last=index+1;
+while ((bytes[last]&0xc0)===0x80){
+last++;
+}
+const char=new TextDecoder().decode(bytes.subarray(first, last));
+$panic(`byte index ${index} is not a char boundary; it is inside '${char}' (bytes ${first}..${last}) of \\`${text}\\``);
+}
+}
+return new TextDecoder().decode(bytes.subarray(start, end));
+},
+test(pattern){
+if (typeof pattern===\"function\"){
+return pattern;
+}
+return typeof pattern===\"string\"?(char)=>char===pattern:(char)=>pattern.includes(char);
+},
+matches(text, pattern){
+const found=[];
+if (pattern===\"\"){
+found.push([0, 0]);
+for (const char of text){
+const at=found[found.length-1][1]+char.length;
+found.push([at, at]);
+}
+return found;
+}
+if (typeof pattern===\"string\"&&[...pattern].length!==1){
+for (let at=text.indexOf(pattern); at>=0; at=text.indexOf(pattern, at+pattern.length)){
+found.push([at, at+pattern.length]);
+}
+return found;
+}
+const test=$str.test(pattern);
+for (let i=0; i<text.length;){
+const char=String.fromCodePoint(text.codePointAt(i));
+if (test(char)){
+found.push([i, i+char.length]);
+}
+i+=char.length;
+}
+return found;
+},
+contains(text, pattern){
+return $str.matches(text, pattern).length>0;
+},
+starts_with(text, pattern){
+return typeof pattern===\"string\"?text.startsWith(pattern):text!==\"\"&&$str.test(pattern)(String.fromCodePoint(text.codePointAt(0)));
+},
+ends_with(text, pattern){
+return typeof pattern===\"string\"?text.endsWith(pattern):text!==\"\"&&$str.test(pattern)([...text].pop());
+},
+split(text, pattern){
+return $str.splitn(text, Infinity, pattern);
+},
+splitn(text, count, pattern){
+if (count===0){
+return [];
+}
+const parts=[];
+let start=0;
+for (const [from, to] of $str.matches(text, pattern)){
+if (parts.length===count-1){
+break;
+}
+parts.push(text.slice(start, from));
+start=to;
+}
+parts.push(text.slice(start));
+return parts;
+},
+rsplit(text, pattern){
+return $str.split(text, pattern).reverse();
+},
+split_once(text, pattern){
+const [match]=$str.matches(text, pattern);
+return match===undefined?Option.None:Option.Some([text.slice(0, match[0]), text.slice(match[1])]);
+},
+rsplit_once(text, pattern){
+const match=typeof pattern===\"string\"&&pattern.length>1?[text.lastIndexOf(pattern), text.lastIndexOf(pattern)+pattern.length]:$str.matches(text, pattern).pop();
+return match===undefined||match[0]<0?Option.None:Option.Some([text.slice(0, match[0]), text.slice(match[1])]);
+},
+trim_matches(text, pattern, start, end){
+if (typeof pattern===\"string\"&&[...pattern].length!==1){
+while (pattern!==\"\"&&start&&text.startsWith(pattern)){
+text=text.slice(pattern.length);
+}
+while (pattern!==\"\"&&end&&text.endsWith(pattern)){
+text=text.slice(0, text.length-pattern.length);
+}
+return text;
+}
+const test=$str.test(pattern);
+const chars=[...text];
+let first=0;
+let last=chars.length;
+while (start&&first<last&&test(chars[first])){
+first++;
+}
+while (end&&last>first&&test(chars[last-1])){
+last--;
+}
+return chars.slice(first, last).join(\"\");
+},
+insert(text, index, value, fast){
+if (!fast){
+const bytes=new TextEncoder().encode(text);
+if (index>bytes.length||(bytes[index]&0xc0)===0x80){
+$panic(\"assertion failed: self.is_char_boundary(idx)\");
+}
+index=new TextDecoder().decode(bytes.subarray(0, index)).length;
+} else if (index>text.length){
+$panic(\"assertion failed: self.is_char_boundary(idx)\");
+}
+return text.slice(0, index)+value+text.slice(index);
+},
+split_whitespace(text){
+return text.split(/\\s+/).filter((part)=>part!==\"\");
+},
+lines(text){
+const lines=text.split(\"\\n\");
+if (lines[lines.length-1]===\"\"){
+lines.pop();
+}
+return lines.map((line)=>line.endsWith(\"\\r\")?line.slice(0, -1):line);
+},
+replace(text, from, to){
+return $str.split(text, from).join(to);
+},
+find(text, pattern, fast, last, zero=0){
+let at=-1;
+if (typeof pattern!==\"string\"){
+const matches=$str.matches(text, pattern);
+at=(last?matches.pop():matches[0])?.[0]??-1;
+} else {
+at=last?text.lastIndexOf(pattern):text.indexOf(pattern);
+}
+if (at<0){
+return Option.None;
+}
+const index=fast?at:$str.len(text.slice(0, at));
+return Option.Some(typeof zero===\"bigint\"?BigInt(index):index);
+},
+parse(text, type, radix=10){
+if (type===\"bool\"){
+return text===\"true\"||text===\"false\"?Result.Ok(text===\"true\"):Result.Err(new ParseBoolError());
+}
+if (type===\"char\"){
+const chars=[...text];
+if (chars.length===1){
+return Result.Ok(chars[0]);
+}
+return Result.Err(chars.length===0?new ParseCharError(\"EmptyString\", \"cannot parse char from empty string\"):new ParseCharError(\"TooManyChars\", \"too many characters in string\"));
+}
+if (type===\"String\"){
+return Result.Ok(text);
+}
+const integer=/^[iu](\\d+|size)$/.exec(type??\"\");
+if (type===\"f32\"||type===\"f64\"||!integer&&!/^[+-]?\\d+$/.test(text)&&/^[+-]?(\\d|\\.\\d)/.test(text)){
+if (text===\"\"){
+return Result.Err(new ParseFloatError(\"Empty\", \"cannot parse float from empty string\"));
+}
+if (!/^[+-]?(inf|infinity|nan|(\\d+\\.?\\d*|\\.\\d+)(e[+-]?\\d+)?)$/i.test(text)){
+return Result.Err(new ParseFloatError(\"Invalid\", \"invalid float literal\"));
+}
+const special=text.replace(/^[+-]/, \"\").toLowerCase();
+const value=special===\"nan\"?NaN:special.startsWith(\"inf\")?(text[0]===\"-\"?-Infinity:Infinity):Number(text);
+return Result.Ok(type===\"f32\"?Math.fround(value):value);
+}
+if (text===\"\"){
+return Result.Err(new ParseIntError(\"Empty\", \"cannot parse integer from empty string\"));
+}
+const signed=type==null||type[0]===\"i\";
+let digits=text;
+if (digits[0]===\"+\"||digits[0]===\"-\"&&signed){
+digits=digits.slice(1);
+}
+let value=0n;
+for (const char of digits){
+const digit=parseInt(char, radix);
+if (Number.isNaN(digit)){
+return Result.Err(new ParseIntError(\"InvalidDigit\", \"invalid digit found in string\"));
+}
+value=value*BigInt(radix)+BigInt(digit);
+}
+if (digits===\"\"){
+return Result.Err(new ParseIntError(\"InvalidDigit\", \"invalid digit found in string\"));
+}
+if (text[0]===\"-\"){
+value=-value;
+}
+const bits=BigInt(integer?integer[1]===\"size\"?64:Number(integer[1]):32);
+const max=signed?2n**(bits-1n)-1n:2n**bits-1n;
+if (value>max){
+return Result.Err(new ParseIntError(\"PosOverflow\", \"number too large to fit in target type\"));
+}
+if (value<(signed?-(2n**(bits-1n)):0n)){
+return Result.Err(new ParseIntError(\"NegOverflow\", \"number too small to fit in target type\"));
+}
+return Result.Ok(bits>32n&&!type.endsWith(\"size\")?value:Number(value));
+},
+};
+",
+    ),
+    (
+        "$display",
+        &[],
+        "function $float(value, type, debug){
+if (Number.isNaN(value)){
+return \"NaN\";
+}
+if (!Number.isFinite(value)){
+return value<0?\"-inf\":\"inf\";
+}
+const sign=value<0||Object.is(value, -0)?\"-\":\"\";
+let magnitude=Math.abs(value);
+if (type===\"f32\"){
+for (let precision=1; precision<=9; precision++){
+const shorter=Number(magnitude.toPrecision(precision));
+if (Math.fround(shorter)===magnitude){
+magnitude=shorter;
+break;
+}
+}
+}
+const [mantissa, power]=magnitude.toExponential().split(\"e\");
+const digits=mantissa.replace(\".\", \"\");
+const exponent=Number(power);
+if (debug&&magnitude!==0&&(magnitude<1e-4||magnitude>=1e16)){
+return sign+mantissa+\"e\"+exponent;
+}
+let text;
+if (exponent>=digits.length-1){
+text=digits+\"0\".repeat(exponent-digits.length+1);
+}
+else if (exponent>=0){
+text=digits.slice(0, exponent+1)+\".\"+digits.slice(exponent+1);
+}
+else{
+text=\"0.\"+\"0\".repeat(-exponent-1)+digits;
+}
+if (debug&&!text.includes(\".\")){
+text+=\".0\";
+}
+return sign+text;
+}
+function $fixed(value, precision){
+if (!Number.isFinite(value)){
+return $float(value);
+}
+const sign=value<0||Object.is(value, -0)?\"-\":\"\";
+const magnitude=Math.abs(value);
+if (magnitude>=1e21){
+return sign+$float(magnitude)+(precision>0?\".\"+\"0\".repeat(precision):\"\");
+}
+let text=magnitude.toFixed(precision);
+if (precision<100){
+const longer=magnitude.toFixed(precision+1);
+if (longer.endsWith(\"5\")&&Number(longer)===magnitude){
+const truncated=longer.slice(0, -1).replace(/\\.$/, \"\");
+if (Number(truncated.slice(-1))%2===0){
+text=truncated;
+}
+}
+}
+return sign+text;
+}
+function $number(value, type, spec, debug){
+const kind=spec.type??\"\";
+if (kind===\"x\"||kind===\"X\"||kind===\"o\"||kind===\"b\"){
+let number=BigInt(value);
+if (number<0n){
+const bits=type===\"isize\"||type===\"usize\"?64:Number(/\\d+/.exec(type??\"\")?.[0]??32);
+number=BigInt.asUintN(bits, number);
+}
+let text=number.toString({x:16, X:16, o:8, b:2}[kind]);
+if (kind===\"X\"){
+text=text.toUpperCase();
+}
+return spec.alternate?\"0\"+kind.toLowerCase()+text:text;
+}
+const float=type===\"f64\"||type===\"f32\"||type==null&&typeof value===\"number\"&&!Number.isInteger(value);
+let text;
+if (kind===\"e\"||kind===\"E\"){
+text=spec.precision===undefined?Number(value).toExponential():Number(value).toExponential(spec.precision);
+text=text.replace(\"e+\", \"e\");
+if (kind===\"E\"){
+text=text.toUpperCase();
+}
+}
+else if (float&&spec.precision!==undefined){
+text=$fixed(value, spec.precision);
+}
+else if (float){
+text=$float(value, type, debug);
+}
+else{
+text=String(value);
+}
+if (spec.sign&&!text.startsWith(\"-\")){
+text=\"+\"+text;
+}
+return text;
+}
+function $pad(text, spec, numeric){
+const length=[...text].length;
+if (spec.width===undefined||length>=spec.width){
+return text;
+}
+const padding=spec.width-length;
+if (spec.zero&&numeric){
+const prefix=/^[+-]?(0[xob])?/.exec(text)[0];
+return prefix+\"0\".repeat(padding)+text.slice(prefix.length);
+}
+const fill=spec.fill??\" \";
+const align=spec.align??(numeric?\">\":\"<\");
+if (align===\"<\"){
+return text+fill.repeat(padding);
+}
+if (align===\">\"){
+return fill.repeat(padding)+text;
+}
+const left=Math.floor(padding/2);
+return fill.repeat(left)+text+fill.repeat(padding-left);
+}
+function $display(value, type, spec={}){
+if (typeof value?.$display===\"function\"){
+const formatter=new $Formatter(spec);
+value.$display(formatter);
+return formatter.$text;
+}
+if (typeof value===\"number\"||typeof value===\"bigint\"){
+return $pad($number(value, type, spec, false), spec, true);
+}
+let text=String(value);
+if (spec.precision!==undefined){
+text=[...text].slice(0, spec.precision).join(\"\");
+}
+return $pad(text, spec, false);
+}
+",
+    ),
+    (
+        "$debug",
+        &["$display"],
+        "function $escape(text, quote){
+let escaped=\"\";
+for (const char of text){
+const code=char.codePointAt(0);
+if (char===\"\\\\\"||char===quote){
+escaped+=\"\\\\\"+char;
+}
+else if (char===\"\\n\"){
+escaped+=\"\\\\n\";
+}
+else if (char===\"\\r\"){
+escaped+=\"\\\\r\";
+}
+else if (char===\"\\t\"){
+escaped+=\"\\\\t\";
+}
+else if (char===\"\\0\"){
+escaped+=\"\\\\0\";
+}
+else if (code<32||code===127){
+escaped+=\"\\\\u{\"+code.toString(16)+\"}\";
+}
+else{
+escaped+=char;
+}
+}
+return escaped;
+}
+function $debug_fields(name, fields, named, spec, open, close){
+if (fields.length===0){
+return name===\"\"?open+close:name;
+}
+const prefix=name===\"\"?\"\":name+(named?\" \":\"\");
+const entries=fields.map(([key, value, type])=>(named?key+\": \":\"\")+$debug(value, type, spec));
+if (spec.alternate){
+const body=entries.map((entry)=>\"    \"+entry.replaceAll(\"\\n\", \"\\n    \")+\",\\n\").join(\"\");
+return prefix+open+\"\\n\"+body+close;
+}
+if (named&&name!==\"\"){
+return prefix+open+\" \"+entries.join(\", \")+\" \"+close;
+}
+return prefix+open+entries.join(\", \")+(name===\"\"&&open===\"(\"&&fields.length===1?\",\":\"\")+close;
+}
+function $debug(value, type, spec={}){
+if (type?.option!==undefined){
+return value===null?\"None\":$debug_fields(\"Some\", [[0, value, type.option]], false, spec, \"(\", \")\");
+}
+if (typeof value?.$debug===\"function\"){
+const formatter=new $Formatter(spec);
+value.$debug(formatter);
+return formatter.$text;
+}
+if (typeof value===\"number\"||typeof value===\"bigint\"){
+const number={...spec, type:spec.type===\"x?\"?\"x\":spec.type===\"X?\"?\"X\":undefined};
+return $pad($number(value, type, number, true), spec, true);
+}
+if (typeof value===\"string\"){
+return type===\"char\"?\"'\"+$escape(value, \"'\")+\"'\":\"\\\"\"+$escape(value, \"\\\"\")+\"\\\"\";
+}
+if (typeof value===\"boolean\"){
+return $pad(String(value), spec, false);
+}
+if (value===null||value===undefined){
+return \"()\";
+}
+if (typeof value===\"function\"){
+return \"<closure>\";
+}
+const collection=value instanceof Map?\"map\":value instanceof Set?\"set\":value.constructor?.$collection;
+if (collection===\"map\"){
+return $debug_fields(\"\", [...value].map(([key, entry])=>[$debug(key, type?.map?.[0], spec), entry, type?.map?.[1]]), true, spec, \"{\", \"}\");
+}
+if (collection===\"set\"||collection===\"list\"){
+const [open, close]=collection===\"set\"?[\"{\", \"}\"]:[\"[\", \"]\"];
+return $debug_fields(\"\", [...value].map((element)=>[0, element, type?.[0]]), false, spec, open, close);
+}
+if (Array.isArray(value)){
+if (type?.tuple!==undefined){
+return $debug_fields(\"\", value.map((element, n)=>[n, element, type.tuple[n]]), false, spec, \"(\", \")\");
+}
+return $debug_fields(\"\", value.map((element)=>[0, element, type?.[0]]), false, spec, \"[\", \"]\");
+}
+let types=type??value.constructor.$types;
+let name=value.constructor.name;
+if (value.$tag!==undefined){
+name=value.$tag;
+types=types?.[value.$tag];
+}
+const keys=Object.keys(value).filter((key)=>!key.startsWith(\"$\"));
+const named=keys.some((key)=>!/^\\d+$/.test(key));
+return $debug_fields(name, keys.map((key)=>[key, value[key], types?.[key]]), named, spec, named?\"{\":\"(\", named?\"}\":\")\");
+}
+",
+    ),    (
+        "$Formatter",
+        &["Result", "$display"],
+        "class $Formatter{
+constructor(spec){
+this.$spec=spec;
+this.$text=\"\";
+}
+write_str(text){
+this.$text+=text;
+return Result.Ok();
+}
+pad(text){
+return this.write_str($display(text, undefined, this.$spec));
+}
+alternate(){
+return this.$spec.alternate===true;
+}
+sign_plus(){
+return this.$spec.sign===true;
+}
+debug_struct(name){
+return new $DebugBuilder(this, name, \"struct\");
+}
+debug_tuple(name){
+return new $DebugBuilder(this, name, \"tuple\");
+}
+debug_list(){
+return new $DebugBuilder(this, \"\", \"list\");
+}
+debug_set(){
+return new $DebugBuilder(this, \"\", \"set\");
+}
+debug_map(){
+return new $DebugBuilder(this, \"\", \"map\");
+}
+}
+",
+    ),
+    (
+        "$DebugBuilder",
+        &["$Formatter", "$debug"],
+        "class $DebugBuilder{
+constructor(formatter, name, kind){
+this.$formatter=formatter;
+this.$name=name;
+this.$kind=kind;
+this.$fields=[];
+}
+field(...input){
+this.$fields.push(this.$kind===\"struct\"?input:[0, ...input]);
+return this;
+}
+entry(...input){
+if (this.$kind===\"map\"){
+const [key, value, types]=input;
+this.$fields.push([$debug(key, types?.[0], this.$formatter.$spec), value, types?.[1]]);
+}
+else{
+this.$fields.push([0, ...input]);
+}
+return this;
+}
+entries(values, type){
+for (const value of values){
+if (this.$kind===\"map\"){
+this.entry(value[0], value[1], type?.tuple);
+}
+else{
+this.entry(value, type);
+}
+}
+return this;
+}
+finish(){
+const [open, close]={struct:[\"{\", \"}\"], tuple:[\"(\", \")\"], list:[\"[\", \"]\"], set:[\"{\", \"}\"], map:[\"{\", \"}\"]}[this.$kind];
+const named=this.$kind===\"struct\"||this.$kind===\"map\";
+return this.$formatter.write_str($debug_fields(this.$name, this.$fields, named, this.$formatter.$spec, open, close));
+}
+}
+",
+    ),
+];
+
+// Iterator adapters and consumers, arrays get them through the `$iter` runtime
+const ITERATOR_METHODS: [&str; 45] = [
+    "map",
+    "filter",
+    "filter_map",
+    "flat_map",
+    "flatten",
+    "take",
+    "skip",
+    "take_while",
+    "skip_while",
+    "map_while",
+    "step_by",
+    "enumerate",
+    "zip",
+    "chain",
+    "rev",
+    "inspect",
+    "cloned",
+    "copied",
+    "cycle",
+    "peekable",
+    "by_ref",
+    "collect",
+    "sum",
+    "product",
+    "fold",
+    "reduce",
+    "for_each",
+    "any",
+    "all",
+    "position",
+    "find",
+    "find_map",
+    "nth",
+    "max",
+    "min",
+    "max_by_key",
+    "min_by_key",
+    "max_by",
+    "min_by",
+    "partition",
+    "unzip",
+    "next",
+    "peek",
+    "count",
+    "last",
+];
+
+// Methods of `Option` that get called through `$Option` when options are nullable
+const OPTION_METHODS: [&str; 17] = [
+    "is_some",
+    "is_none",
+    "unwrap",
+    "expect",
+    "unwrap_or",
+    "unwrap_or_else",
+    "unwrap_or_default",
+    "as_ref",
+    "as_mut",
+    "map",
+    "map_or",
+    "and_then",
+    "filter",
+    "or",
+    "or_else",
+    "ok_or",
+    "ok_or_else",
+];
+
+// Methods of arrays that change them in place
+const MUTATING_METHODS: [&str; 13] = [
+    "sort",
+    "sort_by",
+    "sort_by_key",
+    "sort_unstable",
+    "sort_unstable_by",
+    "sort_unstable_by_key",
+    "reverse",
+    "swap",
+    "fill",
+    "iter_mut",
+    "rotate_left",
+    "rotate_right",
+    "copy_from_slice",
+];
+
+// Settings for the generated code
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    // `Option<T>` becomes `T | null` when no option in the program could be ambiguous
+    pub nullable_option: bool,
+    // Strings are measured and sliced in UTF-16 units like JS does, skipping the UTF-8 runtime
+    pub fast_strings: bool,
+    // Simple iterator chains over arrays and ranges become plain loops instead of lazy iterators
+    pub fuse_iterators: bool,
+    // Integer overflow wraps like a release build instead of panicking like a debug one
+    pub release: bool,
+    // `usize` and `isize` are BigInts like the 64 bit types instead of numbers exact to 53 bits
+    pub bigint_usize: bool,
+}
+
+pub struct Program {
+    pub code: String,
+    pub warnings: Vec<String>,
+}
+
+pub fn code_generator(ast: Vec<Node>, options: &Options) -> Result<Program, Box<dyn Error>> {
+    let mut generator = Generator::new(&ast, options);
+    let code = generator.statements(&ast, &Target::Statement)?;
+    let mut program = String::new();
+    for (name, _, helper) in RUNTIME {
+        if generator.runtime.contains(&name) {
+            program.push_str(helper);
+        }
+    }
+    Ok(Program {
+        code: program + &code,
+        warnings: generator.warnings,
+    })
+}
+
+// Where the value of a block or branching expression goes, an assigned place can
+// have a known type
+enum Target {
+    Statement,
+    Return,
+    Assign(String, Option<Type>),
+    // A local that is a `&mut` to a scalar, it is assigned the cell of the place
+    Reference(String),
+}
+
+#[derive(Debug, Clone)]
+struct Local {
+    ty: Type,
+    // What the name compiles to, shadowed names get renamed
+    js: String,
+    // Integers initialised from unsuffixed literals, Rust would infer their width from
+    // later uses, so it is not known here
+    inferred: bool,
+    // The cell a `&mut` to a scalar is, `js` reads its value
+    cell: Option<String>,
+}
+
+// A name bound by a pattern, the code reading it and its type
+// A name a pattern binds, the code reading it, its type and whether the name is a `&mut`
+// to the place the code reads
+type Binding = (String, String, Type, bool);
+
+// The case labels of a switch arm and whether they compare enum tags
+type Labels = (Vec<String>, bool);
+
+// A generic function that takes the types of some generics as leading arguments
+#[derive(Debug, Clone)]
+struct Witnessed {
+    generics: Vec<String>,
+    // The generics it takes witnesses for with the static methods it calls on them
+    witnesses: Vec<(String, Vec<String>)>,
+    // The turbofish calls in its body, which can pass its own witnesses on
+    calls: Vec<(String, Vec<Type>)>,
+    input: Vec<Parameter>,
+    output: Option<Type>,
+}
+
+// A `{...}` of a format string, arguments left out take the next positional one
+#[derive(Debug, Default)]
+struct Placeholder {
+    argument: Option<Argument>,
+    fill: Option<char>,
+    align: Option<char>,
+    sign: bool,
+    alternate: bool,
+    zero: bool,
+    width: Option<Count>,
+    precision: Option<Count>,
+    // `?`, `x`, `X`, `o`, `b`, `e`, `E`, `x?` or `X?`, empty for Display
+    kind: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Argument {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug)]
+enum Count {
+    Literal(usize),
+    Argument(Argument),
+    // `.*` takes the precision from the next positional argument
+    Next,
+}
+
+#[derive(Debug)]
+enum Piece {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+// A pattern reduced to what exhaustiveness checking needs
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Or(Vec<Pat>),
+    Constructor(Constructor, Vec<Pat>),
+    // The prefix and, when the pattern has a `..`, the suffix of a slice pattern
+    Slice(Vec<Pat>, Option<Vec<Pat>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Constructor {
+    // The enum and the index of the variant
+    Variant(String, usize),
+    Bool(bool),
+    // An inclusive range of integers
+    Int(i128, i128),
+    // Tuples and structs, with the name of the struct
+    Single(Option<String>),
+    // The length and whether longer slices are included
+    Slice(usize, bool),
+    // Values that can only be compared, like strings and floats
+    Opaque(String),
+    // Every value no opaque constructor names
+    Other,
+}
+
+struct Loop {
+    label: String,
+    used: bool,
+    switches: usize,
+}
+
+struct Generator {
+    enums: HashMap<String, Vec<Variant>>,
+    structs: HashMap<String, Fields>,
+    impls: HashMap<String, Vec<Node>>,
+    // The traits each type implements
+    traits: HashMap<String, Vec<String>>,
+    // The names of the generic parameters of each type
+    type_parameters: HashMap<String, Vec<String>>,
+    // The supertraits and methods of each trait
+    trait_definitions: HashMap<String, (Vec<String>, Vec<Node>)>,
+    // Functions that take the types of some generics as leading arguments, because
+    // they call static methods like `T::default()` on them
+    witnessed: HashMap<String, Witnessed>,
+    // The witnesses of the function being generated
+    witness_scope: Vec<String>,
+    functions: HashMap<String, Option<Type>>,
+    // Methods that share their name with a field of their type
+    renamed_methods: HashMap<String, Vec<String>>,
+    scopes: Vec<HashMap<String, Local>>,
+    // Statements that have to run before the statement currently being generated
+    hoisted: Vec<String>,
+    temporaries: usize,
+    loops: Vec<Loop>,
+    current_impl: Option<String>,
+    warnings: Vec<String>,
+    // The runtime helpers the program uses
+    runtime: Vec<&'static str>,
+    // Whether options are `T | null`
+    nullable: bool,
+    // Strings use JS lengths and indices instead of UTF-8 ones
+    fast_strings: bool,
+    // Iterator chains are fused into loops where possible
+    fuse_iterators: bool,
+    // Integer overflow wraps instead of panicking
+    release: bool,
+    // `usize` and `isize` are BigInts
+    bigint_usize: bool,
+    // The declared output of the function being generated, `None` in closures
+    output: Option<Type>,
+    // Integer literals are BigInt literals, while generating one that is coerced to a BigInt
+    bigint_literals: bool,
+    // The annotated type of the `let` whose value is being generated
+    expected: Option<Type>,
+    // Methods that take `&mut self`
+    mutating_methods: Vec<String>,
+    // The primitive types whose impls were generated, as one class for all of them
+    primitive_impls: Vec<String>,
+    // For each function, whether it changes or keeps each of its parameters, the
+    // callers of the ones that do pass copies of `Copy` values
+    parameter_changes: HashMap<String, Vec<(String, bool)>>,
+    // The types of the parameters of each function besides `self`, keyed the same way
+    parameter_types: HashMap<String, Vec<Type>>,
+    // Which parameters of a user function are `&mut` scalars, passed as cells
+    cell_parameters: HashMap<String, Vec<bool>>,
+    // What the function being generated does with its variables
+    changes: Changes,
+}
+
+// The variables of a function that change in place, that it returns and that it owns,
+// to decide where `Copy` values need copying
+#[derive(Debug, Clone, Default)]
+struct Changes {
+    mutated: Vec<String>,
+    returned: Vec<String>,
+    owned: Vec<String>,
+}
+
+impl Generator {
+    fn new(ast: &[Node], options: &Options) -> Generator {
+        let mut generator = Generator {
+            enums: HashMap::new(),
+            structs: HashMap::new(),
+            impls: HashMap::new(),
+            traits: HashMap::new(),
+            type_parameters: HashMap::new(),
+            trait_definitions: HashMap::new(),
+            witnessed: HashMap::new(),
+            witness_scope: Vec::new(),
+            functions: HashMap::new(),
+            renamed_methods: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            hoisted: Vec::new(),
+            temporaries: 0,
+            loops: Vec::new(),
+            current_impl: None,
+            warnings: Vec::new(),
+            runtime: Vec::new(),
+            nullable: false,
+            fast_strings: options.fast_strings,
+            fuse_iterators: options.fuse_iterators,
+            release: options.release,
+            bigint_usize: options.bigint_usize,
+            output: None,
+            bigint_literals: false,
+            expected: None,
+            mutating_methods: Vec::new(),
+            primitive_impls: Vec::new(),
+            parameter_changes: HashMap::new(),
+            parameter_types: HashMap::new(),
+            cell_parameters: HashMap::new(),
+            changes: Changes::default(),
+        };
+        // The prelude enums, user definitions replace them
+        let prelude = [("Option", "Some", "None"), ("Result", "Ok", "Err")];
+        for (name, first, second) in prelude {
+            let fields = if name == "Option" {
+                Fields::Unit
+            } else {
+                Fields::Tuple(vec![Type::Unknown])
+            };
+            let variants = vec![
+                Variant {
+                    name: first.to_string(),
+                    fields: Fields::Tuple(vec![Type::Unknown]),
+                    discriminant: None,
+                    default: false,
+                },
+                Variant {
+                    name: second.to_string(),
+                    fields,
+                    discriminant: None,
+                    default: false,
+                },
+            ];
+            generator.enums.insert(name.to_string(), variants);
+        }
+        let ordering = [("Less", -1), ("Equal", 0), ("Greater", 1)];
+        let variants = ordering.map(|(name, discriminant)| Variant {
+            name: name.to_string(),
+            fields: Fields::Unit,
+            discriminant: Some(discriminant),
+            default: false,
+        });
+        generator
+            .enums
+            .insert("Ordering".to_string(), variants.to_vec());
+        // Items can be used before they are declared
+        for node in ast {
+            match node {
+                Node::Enum {
+                    name,
+                    generics,
+                    variants,
+                    derives,
+                } => {
+                    generator
+                        .traits
+                        .entry(name.to_string())
+                        .or_default()
+                        .extend(derives.iter().cloned());
+                    generator.enums.insert(name.to_string(), variants.to_vec());
+                    generator.type_parameters.insert(
+                        name.to_string(),
+                        generics.iter().map(|g| g.name.to_string()).collect(),
+                    );
+                }
+                Node::Struct {
+                    name,
+                    generics,
+                    fields,
+                    derives,
+                } => {
+                    generator
+                        .traits
+                        .entry(name.to_string())
+                        .or_default()
+                        .extend(derives.iter().cloned());
+                    generator.structs.insert(name.to_string(), fields.clone());
+                    generator.type_parameters.insert(
+                        name.to_string(),
+                        generics.iter().map(|g| g.name.to_string()).collect(),
+                    );
+                }
+                Node::Impl {
+                    name,
+                    trait_name,
+                    body,
+                } => {
+                    generator
+                        .impls
+                        .entry(name.to_string())
+                        .or_default()
+                        .extend(formatting_impl(
+                            trait_name.as_deref(),
+                            associated_types(body),
+                        ));
+                    if let Some(trait_name) = trait_name {
+                        generator
+                            .traits
+                            .entry(name.to_string())
+                            .or_default()
+                            .push(trait_name.to_string());
+                    }
+                }
+                Node::Trait {
+                    name,
+                    supertraits,
+                    body,
+                } => {
+                    generator
+                        .trait_definitions
+                        .insert(name.to_string(), (supertraits.clone(), body.clone()));
+                }
+                Node::Function { name, output, .. } => {
+                    generator.functions.insert(name.to_string(), output.clone());
+                }
+                _ => {}
+            }
+        }
+        let mut functions: Vec<(String, &Node)> = Vec::new();
+        for node in ast {
+            match node {
+                Node::Function { name, .. } => functions.push((name.to_string(), node)),
+                Node::Impl {
+                    name: owner, body, ..
+                } => {
+                    for node in body {
+                        if let Node::Function { name, .. } = node {
+                            functions.push((format!("{owner}::{name}"), node));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let trait_methods = generator
+            .trait_definitions
+            .values()
+            .flat_map(|(_, body)| body);
+        for node in functions.iter().map(|(_, node)| *node).chain(trait_methods) {
+            if let Node::Function { name, input, .. } = node {
+                if input.first().is_some_and(|p| p.name == "self" && p.mutable) {
+                    generator.mutating_methods.push(name.to_string());
+                }
+            }
+        }
+        for (path, node) in &functions {
+            if let Node::Function { input, body, .. } = node {
+                let changes = changes(input, body, &generator.mutating_methods);
+                // What `self` returns is copied on the way out, `&self` has to stay shared
+                let parameters = input
+                    .iter()
+                    .map(|p| {
+                        let kept = p.name != "self" && changes.returned.contains(&p.name);
+                        let changed = !p.mutable && (changes.mutated.contains(&p.name) || kept);
+                        (p.name.to_string(), changed)
+                    })
+                    .collect();
+                generator
+                    .parameter_changes
+                    .insert(path.to_string(), parameters);
+                let types = input
+                    .iter()
+                    .filter(|p| p.name != "self")
+                    .map(|p| p.ty.clone())
+                    .collect();
+                generator.parameter_types.insert(path.to_string(), types);
+                let cells = input
+                    .iter()
+                    .filter(|p| p.name != "self")
+                    .map(|p| p.mutable && is_scalar(&p.ty))
+                    .collect();
+                generator.cell_parameters.insert(path.to_string(), cells);
+            }
+        }
+        for (path, node) in functions {
+            if let Node::Function {
+                generics,
+                input,
+                output,
+                body,
+                ..
+            } = node
+            {
+                let owner = path.rsplit_once("::").map(|(owner, _)| owner);
+                let mut uses = Vec::new();
+                for node in body {
+                    generic_uses(node, &mut uses);
+                }
+                let mut witnesses = Vec::new();
+                let mut calls = Vec::new();
+                for (name, turbofish) in uses {
+                    let name = match (name.strip_prefix("Self::"), owner) {
+                        (Some(rest), Some(owner)) => format!("{owner}::{rest}"),
+                        _ => name,
+                    };
+                    let used = name.split_once("::").filter(|(generic, _)| {
+                        turbofish.is_empty() && generics.iter().any(|g| g.name == *generic)
+                    });
+                    match used {
+                        Some((generic, method)) => {
+                            add_witness(&mut witnesses, generic, method);
+                        }
+                        None if !turbofish.is_empty() => calls.push((name, turbofish)),
+                        None => {}
+                    }
+                }
+                generator.witnessed.insert(
+                    path,
+                    Witnessed {
+                        generics: generics.iter().map(|g| g.name.to_string()).collect(),
+                        witnesses,
+                        calls,
+                        input: input.clone(),
+                        output: output.clone(),
+                    },
+                );
+            }
+        }
+        // Generic functions calling each other pass their witnesses on, so they
+        // need the static methods of the functions they call too
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let functions = generator.witnessed.clone();
+            for (path, function) in &functions {
+                for (callee, types) in &function.calls {
+                    let Some(callee) = functions.get(callee) else {
+                        continue;
+                    };
+                    for (ty, parameter) in types.iter().zip(&callee.generics) {
+                        let Some(generic) = ty
+                            .name()
+                            .filter(|name| function.generics.iter().any(|g| g == name))
+                        else {
+                            continue;
+                        };
+                        let methods = callee
+                            .witnesses
+                            .iter()
+                            .filter(|(name, _)| name == parameter)
+                            .flat_map(|(_, methods)| methods);
+                        for method in methods {
+                            let witnesses = &mut generator
+                                .witnessed
+                                .get_mut(path)
+                                .expect("collected above")
+                                .witnesses;
+                            changed |= add_witness(witnesses, generic, method);
+                        }
+                    }
+                }
+            }
+        }
+        generator
+            .witnessed
+            .retain(|_, function| !function.witnesses.is_empty());
+        for (name, body) in &generator.impls {
+            let fields = generator.field_names(name);
+            let renamed: Vec<String> = body
+                .iter()
+                .filter_map(|node| match node {
+                    Node::Function { name, .. } if fields.contains(name) => Some(name.to_string()),
+                    _ => None,
+                })
+                .collect();
+            if !renamed.is_empty() {
+                generator.renamed_methods.insert(name.to_string(), renamed);
+            }
+        }
+        if options.nullable_option {
+            match generator.ambiguous_option(ast) {
+                Some(ty) => generator.warnings.push(format!(
+                    "Code generator Option: Options are not nullable because `{ty}` would be ambiguous"
+                )),
+                None => generator.nullable = true,
+            }
+        }
+        generator
+    }
+
+    // A type or expression that `T | null` can not represent, like `Option<Option<i32>>`
+    fn ambiguous_option(&self, ast: &[Node]) -> Option<String> {
+        let mut nodes: Vec<&Node> = ast.iter().collect();
+        while let Some(node) = nodes.pop() {
+            let mut types = Vec::new();
+            match node {
+                Node::Veriable { ty: Some(ty), .. } | Node::Cast { ty, .. } => {
+                    types.push(ty.clone())
+                }
+                Node::Function { input, output, .. } => {
+                    types.extend(input.iter().map(|p| p.ty.clone()));
+                    types.extend(output.clone());
+                }
+                Node::Closure { input, .. } => {
+                    types.extend(input.iter().filter_map(|(_, ty)| ty.clone()))
+                }
+                Node::Struct { fields, .. } => types.extend(field_types(fields)),
+                Node::Enum { variants, .. } => {
+                    types.extend(variants.iter().flat_map(|v| field_types(&v.fields)))
+                }
+                Node::FunctionCall { name, input, .. } if name == "Some" => {
+                    if let [Node::FunctionCall { name, .. } | Node::VeriableCall(name)] = &input[..]
+                    {
+                        if name == "Some" || name == "None" {
+                            return Some(format!("Some({name}(..))"));
+                        }
+                    }
+                }
+                _ => {}
+            }
+            if let Some(ty) = types.iter().find_map(ambiguous_option_type) {
+                return Some(ty);
+            }
+            nodes.extend(children(node));
+        }
+        None
+    }
+
+    fn field_names(&self, ty: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut add = |fields: &Fields| {
+            if let Fields::Named(fields) = fields {
+                names.extend(fields.iter().map(|(name, _)| name.to_string()));
+            }
+        };
+        if let Some(fields) = self.structs.get(ty) {
+            add(fields);
+        }
+        for variant in self.enums.get(ty).into_iter().flatten() {
+            add(&variant.fields);
+        }
+        names
+    }
+
+    // Marks a runtime helper as used and returns its name
+    fn runtime(&mut self, helper: &'static str) -> &'static str {
+        if !self.runtime.contains(&helper) {
+            self.runtime.push(helper);
+            for (name, dependencies, _) in RUNTIME {
+                if name == helper {
+                    for dependency in dependencies {
+                        self.runtime(dependency);
+                    }
+                }
+            }
+        }
+        helper
+    }
+
+    // Marks the prelude enum a resolved path uses
+    fn prelude(&mut self, resolved: &str) {
+        match resolved.split("::").next() {
+            Some("Option") if self.nullable => {
+                self.runtime("$Option");
+            }
+            Some("Option") => {
+                self.runtime("Option");
+            }
+            Some("Result") => {
+                self.runtime("Result");
+            }
+            Some("Ordering") => {
+                self.runtime("Ordering");
+            }
+            _ => {}
+        }
+    }
+
+    // The JS name for a new local, names that are already visible get renamed
+    fn fresh_name(&mut self, name: &str) -> String {
+        if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+            self.temporaries += 1;
+            format!("{name}${}", self.temporaries)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn insert(&mut self, name: &str, ty: Type, js: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(
+                name.to_string(),
+                Local {
+                    ty,
+                    js: js.to_string(),
+                    inferred: false,
+                    cell: None,
+                },
+            );
+        }
+    }
+
+    fn insert_cell(&mut self, name: &str, ty: Type, cell: &str) {
+        self.insert(name, ty, &format!("{cell}.value"));
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+            local.cell = Some(cell.to_string());
+        }
+    }
+
+    fn declare(&mut self, name: &str, ty: Type) -> String {
+        let js = self.fresh_name(name);
+        self.insert(name, ty, &js);
+        js
+    }
+
+    fn local(&self, name: &str) -> Option<&Local> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    // The integer type Rust infers for a local initialised from an unsuffixed literal,
+    // from the first use in `rest` that pins it down. `expected` is the type the value
+    // of the block goes to
+    fn literal_width(
+        &mut self,
+        name: &str,
+        rest: &[Node],
+        expected: Option<&Type>,
+    ) -> Option<Type> {
+        self.scopes.push(HashMap::new());
+        self.insert(name, Type::named("i32"), name);
+        self.infer_later(name);
+        let mut width = None;
+        for node in rest {
+            width = self.width_constraint(name, node);
+            let shadowed = matches!(node, Node::Veriable { name: other, .. } if other == name);
+            if width.is_some() || shadowed {
+                break;
+            }
+        }
+        if let (None, Some(last), Some(expected)) = (&width, rest.last(), expected) {
+            if is_expression(last) && carries(last, name) && is_integer(expected) {
+                width = Some(expected.clone());
+            }
+        }
+        self.scopes.pop();
+        width
+    }
+
+    // Looks for a use pinning the width of `name` in `node`, declaring the locals it
+    // passes so their types are known
+    fn width_constraint(&mut self, name: &str, node: &Node) -> Option<Type> {
+        if let Some(ty) = self.constraint(name, node) {
+            return is_integer(&ty).then_some(ty);
+        }
+        match node {
+            Node::Veriable {
+                name: local,
+                ty,
+                value,
+            } => {
+                if let Some(width) = value
+                    .as_deref()
+                    .and_then(|v| self.width_constraint(name, v))
+                {
+                    return Some(width);
+                }
+                let ty = match (ty, value) {
+                    (Some(ty), _) => ty.clone(),
+                    (None, Some(value)) => self.type_of(value),
+                    (None, None) => Type::Unknown,
+                };
+                if local != name {
+                    self.insert(local, ty, local);
+                }
+                None
+            }
+            Node::ForLoop {
+                pattern,
+                iterable,
+                body,
+            } => {
+                if let Some(width) = self.width_constraint(name, iterable) {
+                    return Some(width);
+                }
+                self.scopes.push(HashMap::new());
+                if let Pattern::Binding { name: local, .. } = pattern.as_ref() {
+                    let item = self.item_of(&self.type_of(iterable));
+                    self.insert(local, item, local);
+                }
+                let width = body
+                    .iter()
+                    .find_map(|node| self.width_constraint(name, node));
+                self.scopes.pop();
+                width
+            }
+            node => children(node)
+                .into_iter()
+                .find_map(|child| self.width_constraint(name, child)),
+        }
+    }
+
+    // The type a use of `name` in `node` itself gives it, before looking inside
+    fn constraint(&self, name: &str, node: &Node) -> Option<Type> {
+        let width = |node: &Node| self.integer_type(node).map(|ty| Type::named(&ty));
+        let position = |input: &[Node]| input.iter().position(|node| carries(node, name));
+        match node {
+            Node::Binary {
+                left,
+                operator,
+                right,
+            } if !matches!(
+                operator,
+                Operator::And | Operator::Or | Operator::ShiftLeft | Operator::ShiftRight
+            ) =>
+            {
+                if carries(left, name) {
+                    width(right)
+                } else if carries(right, name) {
+                    width(left)
+                } else {
+                    None
+                }
+            }
+            Node::Assignment { target, value } | Node::CompoundAssignment { target, value, .. }
+                if !matches!(
+                    node,
+                    Node::CompoundAssignment {
+                        operator: Operator::ShiftLeft | Operator::ShiftRight,
+                        ..
+                    }
+                ) =>
+            {
+                if carries(target, name) {
+                    width(value)
+                } else if carries(value, name) {
+                    width(target)
+                } else {
+                    None
+                }
+            }
+            Node::Veriable {
+                ty: Some(ty),
+                value: Some(value),
+                ..
+            } if carries(value, name) => Some(ty.clone()),
+            Node::Return(Some(value)) if carries(value, name) => self.output.clone(),
+            Node::Range {
+                start: Some(start),
+                end: Some(end),
+                ..
+            } => {
+                if carries(start, name) {
+                    width(end)
+                } else if carries(end, name) {
+                    width(start)
+                } else {
+                    None
+                }
+            }
+            Node::Index { expression, index } if carries(index, name) => {
+                let ty = pointee(self.type_of(expression));
+                if is_map(&ty) {
+                    Some(generic_argument(&ty, 0))
+                } else {
+                    Some(Type::named("usize"))
+                }
+            }
+            Node::FunctionCall {
+                name: path, input, ..
+            } => {
+                let n = position(input)?;
+                self.argument_types(&self.resolve(path)).get(n).cloned()
+            }
+            Node::MethodCall {
+                expression,
+                method,
+                input,
+                ..
+            } => {
+                let n = position(input)?;
+                let ty = pointee(self.type_of(expression));
+                match method.as_str() {
+                    "push" | "contains" | "insert" if is_vec(&ty) => Some(element_type(&ty)),
+                    _ => {
+                        let owner = ty.name()?;
+                        self.argument_types(&format!("{owner}::{method}"))
+                            .get(n)
+                            .cloned()
+                    }
+                }
+            }
+            Node::StructLiteral { name: path, fields } => {
+                let (field, _) = fields.iter().find(|(_, value)| carries(value, name))?;
+                let fields = self.pattern_fields(&self.resolve(path)).ok()?;
+                Some(field_type(&fields, field))
+            }
+            _ => None,
+        }
+    }
+
+    fn infer_later(&mut self, name: &str) {
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+            local.inferred = true;
+        }
+    }
+
+    fn temporary(&mut self, prefix: &str) -> String {
+        self.temporaries += 1;
+        format!("${prefix}{}", self.temporaries)
+    }
+
+    // Runs `generate` and puts the statements hoisted out of its expressions in front of it
+    fn flushed(
+        &mut self,
+        generate: impl FnOnce(&mut Generator) -> Result<String, Box<dyn Error>>,
+    ) -> Result<String, Box<dyn Error>> {
+        let outer = std::mem::take(&mut self.hoisted);
+        let code = generate(self);
+        let hoisted = std::mem::replace(&mut self.hoisted, outer);
+        Ok(hoisted.concat() + &code?)
+    }
+
+    // Lowers a branching expression to statements assigning a temporary and returns its name
+    fn hoist(&mut self, node: &Node) -> Result<String, Box<dyn Error>> {
+        let name = self.temporary("t");
+        let code = self.tail(node, &Target::Assign(name.clone(), None))?;
+        self.hoisted.push(format!("let {name};\n{code}"));
+        Ok(name)
+    }
+
+    fn block(&mut self, body: &[Node], target: &Target) -> Result<String, Box<dyn Error>> {
+        self.scopes.push(HashMap::new());
+        let program = self.statements(body, target);
+        self.scopes.pop();
+        program
+    }
+
+    fn statements(&mut self, ast: &[Node], target: &Target) -> Result<String, Box<dyn Error>> {
+        let tail = ast.len().checked_sub(1).filter(|&n| is_expression(&ast[n]));
+        let mut program = String::new();
+        for (n, node) in ast.iter().enumerate() {
+            if Some(n) == tail {
+                program.push_str(&self.tail(node, target)?);
+                continue;
+            }
+            // `let x = 0;` gets its width from how the rest of the block uses `x`
+            let annotated;
+            let node = match node {
+                Node::Veriable {
+                    name,
+                    ty: None,
+                    value: Some(value),
+                } if is_integer(&self.type_of(value)) && self.integer_type(value).is_none() => {
+                    let expected = match target {
+                        Target::Return => self.output.clone(),
+                        Target::Assign(_, ty) => ty.clone(),
+                        Target::Statement | Target::Reference(_) => None,
+                    };
+                    let ty = self
+                        .literal_width(name, &ast[n + 1..], expected.as_ref())
+                        .unwrap_or_else(|| Type::named("i32"));
+                    annotated = Node::Veriable {
+                        name: name.to_string(),
+                        ty: Some(ty),
+                        value: Some(value.clone()),
+                    };
+                    &annotated
+                }
+                node => node,
+            };
+            // Block-like statements need no semicolon
+            if matches!(node, Node::NewLine) && program.ends_with("}\n") {
+                continue;
+            }
+            let code = self.flushed(|generator| generator.statement(node))?;
+            program.push_str(&code);
+        }
+        Ok(program)
+    }
+
+    // Generates the last expression of a block, sending its value to `target`
+    fn tail(&mut self, node: &Node, target: &Target) -> Result<String, Box<dyn Error>> {
+        self.flushed(|generator| match node {
+            Node::Match { expression, arms } => generator.match_statement(expression, arms, target),
+            Node::IfStatement {
+                condition,
+                pattern,
+                body,
+                else_body: Some(else_body),
+            } => {
+                generator.if_statement(condition, pattern.as_deref(), body, Some(else_body), target)
+            }
+            Node::Block(body) => Ok(format!("{{\n{}}}\n", generator.block(body, target)?)),
+            Node::IfStatement { .. } | Node::Return(_) | Node::Break | Node::Continue => {
+                let code = generator.statement(node)?;
+                if code.ends_with("}\n") {
+                    Ok(code)
+                } else {
+                    Ok(code + ";\n")
+                }
+            }
+            _ if matches!(target, Target::Reference(_)) => {
+                let Target::Reference(name) = target else {
+                    unreachable!()
+                };
+                match node {
+                    Node::MutableReference(_) | Node::VeriableCall(_) => {
+                        Ok(format!("{name}={};\n", generator.cell(node)?))
+                    }
+                    // `panic!()` and the like
+                    Node::Macro { .. } => Ok(format!("{};\n", generator.expression(node)?.0)),
+                    _ => Err("Code generator Reference: Expected a &mut to a place".into()),
+                }
+            }
+            _ => {
+                let code = match target {
+                    Target::Return => generator.returned(node)?,
+                    Target::Assign(_, Some(ty)) => generator.stored(node, ty, false)?,
+                    _ => generator.expression(node)?,
+                };
+                Ok(match target {
+                    Target::Statement => format!("{};\n", code.0),
+                    Target::Return => format!("return {};\n", code.0),
+                    Target::Assign(name, _) | Target::Reference(name) => {
+                        format!("{name}={};\n", wrap(code, ASSIGNMENT))
+                    }
+                })
+            }
+        })
+    }
+
+    fn statement(&mut self, node: &Node) -> Result<String, Box<dyn Error>> {
+        Ok(match node {
+            Node::Veriable { name, ty, value } => {
+                let ty = match (ty, value) {
+                    (Some(ty), _) => ty.clone(),
+                    (None, Some(value)) => self.type_of(value),
+                    (None, None) => Type::Unknown,
+                };
+                let js = self.fresh_name(name);
+                if let Some(Node::MutableReference(place)) = value.as_deref() {
+                    if is_scalar(&ty) {
+                        let cell = self.cell(place)?;
+                        self.insert_cell(name, ty, &js);
+                        return Ok(format!("let {js}={cell}"));
+                    }
+                }
+                if let Some(cell) = value.as_deref().map(|value| self.unwrapped_cell(value)) {
+                    if let Some(cell) = cell? {
+                        self.insert_cell(name, ty, &js);
+                        return Ok(format!("let {js}={cell}"));
+                    }
+                }
+                // `let r = if c { &mut a } else { &mut b };`
+                if let Some(value) = value.as_deref().filter(|value| {
+                    is_branching(value) && is_scalar(&ty) && returns_reference(value)
+                }) {
+                    let code = self.tail(value, &Target::Reference(js.clone()))?;
+                    self.insert_cell(name, ty, &js);
+                    return Ok(format!("let {js};\n{code}"));
+                }
+                let code = match value {
+                    Some(value) if is_branching(value) => format!(
+                        "let {js};\n{}",
+                        self.tail(value, &Target::Assign(js.clone(), Some(ty.clone())))?
+                    ),
+                    Some(value) => {
+                        let outer = self.expected.replace(ty.clone());
+                        let changes = self.changes.mutated.contains(name)
+                            || self.changes.returned.contains(name)
+                            || self.changed(value);
+                        let value = self.stored(value, &ty, changes);
+                        self.expected = outer;
+                        format!("let {js}={}", wrap(value?, ASSIGNMENT))
+                    }
+                    None => format!("let {js}"),
+                };
+                self.insert(name, ty, &js);
+                code
+            }
+            Node::Assignment { target, value } if is_branching(value) => {
+                let ty = self.type_of(target);
+                let target = wrap(self.expression(target)?, ATOM);
+                self.tail(value, &Target::Assign(target, Some(ty)))?
+            }
+            Node::IfStatement {
+                condition,
+                pattern,
+                body,
+                else_body,
+            } => self.if_statement(
+                condition,
+                pattern.as_deref(),
+                body,
+                else_body.as_deref(),
+                &Target::Statement,
+            )?,
+            Node::Match { expression, arms } => {
+                self.match_statement(expression, arms, &Target::Statement)?
+            }
+            Node::Block(body) => format!("{{\n{}}}\n", self.block(body, &Target::Statement)?),
+            Node::WhileLoop {
+                condition,
+                pattern,
+                body,
+            } => {
+                let outer = std::mem::take(&mut self.hoisted);
+                let mut body = body.to_vec();
+                let condition = self.place_binding(condition, pattern.as_deref()).and_then(
+                    |place| match place {
+                        Some((test, binding)) => {
+                            body.splice(0..0, [binding, Node::NewLine]);
+                            self.condition(&test, None)
+                        }
+                        None => self.condition(condition, pattern.as_deref()),
+                    },
+                );
+                let hoisted = std::mem::replace(&mut self.hoisted, outer);
+                let (condition, bindings) = condition?;
+                let (label, body) = self.loop_body(&bindings, &body)?;
+                if hoisted.is_empty() {
+                    format!("{label}while ({}){{\n{}}}\n", condition, body)
+                } else {
+                    // The condition needs statements, they have to run on every iteration
+                    format!(
+                        "{label}while (true){{\n{}if (!({condition})){{\nbreak;\n}}\n{body}}}\n",
+                        hoisted.concat()
+                    )
+                }
+            }
+            Node::ForLoop {
+                pattern,
+                iterable,
+                body,
+            } => self.for_loop(pattern, iterable, body)?,
+            Node::LetPattern {
+                pattern,
+                value,
+                else_body,
+            } => self.let_pattern(pattern, value, else_body.as_deref())?,
+            Node::Function {
+                name,
+                input,
+                output,
+                body,
+                ..
+            } => format!(
+                "function {name}{}",
+                self.function(name, input, output, body)?
+            ),
+            Node::Enum { name, variants, .. } => {
+                self.enums.insert(name.to_string(), variants.to_vec());
+                let mut types = Vec::new();
+                for variant in variants {
+                    if let Some(fields) = self.field_descriptors(&variant.fields) {
+                        types.push(format!("{}:{fields}", variant.name));
+                    }
+                }
+                let mut methods = self.methods(name)?;
+                if !types.is_empty() {
+                    methods = format!("static $types={{{}}};\n{methods}", types.join(", "));
+                }
+                enumeration(name, variants, &methods)
+            }
+            Node::Struct { name, fields, .. } => {
+                let mut methods = self.methods(name)?;
+                if let Some(types) = self.field_descriptors(fields) {
+                    methods = format!("static $types={types};\n{methods}");
+                }
+                // Named fields are set in declaration order, which is the order Debug prints them in
+                let constructor = match fields {
+                    Fields::Named(fields) => fields
+                        .iter()
+                        .map(|(field, _)| format!("this.{field}=$fields.{field};\n"))
+                        .collect(),
+                    _ => "Object.assign(this, $fields);\n".to_string(),
+                };
+                format!("class {name}{{\nconstructor($fields){{\n{constructor}}}\n{methods}}}\n")
+            }
+            // The methods of a primitive type are static methods of a class named after it,
+            // `self` is the `this` they are called with
+            Node::Impl { name, .. } if is_primitive(&Type::named(name)) => {
+                if self.primitive_impls.contains(name) {
+                    return Ok(String::new());
+                }
+                self.primitive_impls.push(name.to_string());
+                format!("class {name}{{\n{}}}\n", self.methods(name)?)
+            }
+            Node::Impl {
+                name, trait_name, ..
+            } if !self.structs.contains_key(name) && !self.enums.contains_key(name) => {
+                let name = match trait_name {
+                    Some(trait_name) => format!("{trait_name} for {name}"),
+                    None => name.to_string(),
+                };
+                return Err(format!("Code generator Impl: Can not implement {name}").into());
+            }
+            // The methods are generated with their type
+            // Default methods are mixed into the classes that implement the trait
+            Node::Impl { .. } | Node::Trait { .. } | Node::TypeAlias { .. } => String::new(),
+            Node::Return(Some(value)) if is_branching(value) => {
+                self.tail(value, &Target::Return)?
+            }
+            Node::Return(Some(value)) => format!("return {}", self.returned(value)?.0),
+            Node::Return(None) => "return".to_string(),
+            Node::Break => match self.loops.last_mut() {
+                // A plain `break` would only leave the switch a match was lowered to
+                Some(current) if current.switches > 0 => {
+                    current.used = true;
+                    format!("break {}", current.label)
+                }
+                _ => "break".to_string(),
+            },
+            Node::Continue => "continue".to_string(),
+            Node::NewLine => ";\n".to_string(),
+            _ => self.expression(node)?.0,
+        })
+    }
+
+    // Ranges become counting loops, everything else is iterated with `for of`
+    fn for_loop(
+        &mut self,
+        pattern: &Pattern,
+        iterable: &Node,
+        body: &[Node],
+    ) -> Result<String, Box<dyn Error>> {
+        let pattern = &self.normalize(pattern);
+        // `(a..b).rev()` counts down
+        let (range, reverse) = match iterable {
+            Node::MethodCall {
+                expression,
+                method,
+                input,
+                ..
+            } if method == "rev" && input.is_empty() => (expression.as_ref(), true),
+            _ => (iterable, false),
+        };
+        if let (
+            Node::Range {
+                start,
+                end: Some(end),
+                inclusive,
+            },
+            Pattern::Binding {
+                name,
+                pattern: None,
+            },
+        ) = (range, pattern)
+        {
+            let known = start
+                .iter()
+                .chain([end])
+                .find_map(|bound| self.integer_type(bound));
+            let ty = match (&known, start) {
+                (Some(known), _) => Type::named(known),
+                (None, Some(start)) => self.type_of(start),
+                (None, None) => self.type_of(end),
+            };
+            // `0..10` counts in whatever the body uses the counter as
+            let ty = match known {
+                None if is_integer(&ty) => self
+                    .literal_width(name, body, None)
+                    .unwrap_or_else(|| Type::named("i32")),
+                _ => ty,
+            };
+            // BigInt counters count in BigInts
+            let suffix = if self.is_big(&ty) { "n" } else { "" };
+            let first = match start {
+                Some(start) => self.evaluated_once(start, &ty)?,
+                None => format!("0{suffix}"),
+            };
+            let last = self.evaluated_once(end, &ty)?;
+            self.scopes.push(HashMap::new());
+            let js = self.declare(name, ty);
+            let body = self.loop_body(&[], body);
+            self.scopes.pop();
+            let (label, body) = body?;
+            let header = match (reverse, inclusive) {
+                (false, false) => format!("let {js}={first}; {js}<{last}; {js}++"),
+                (false, true) => format!("let {js}={first}; {js}<={last}; {js}++"),
+                (true, false) => format!("let {js}={last}-1{suffix}; {js}>={first}; {js}--"),
+                (true, true) => format!("let {js}={last}; {js}>={first}; {js}--"),
+            };
+            return Ok(format!("{label}for ({header}){{\n{body}}}\n"));
+        }
+
+        // Scalars are copied out of arrays, so `*x += 1` on them writes through an index
+        let (elements, enumerated) = match iterable {
+            Node::MethodCall {
+                expression,
+                method,
+                input,
+                ..
+            } if method == "enumerate" && input.is_empty() => {
+                (self.scalar_elements(expression), true)
+            }
+            _ => (self.scalar_elements(iterable), false),
+        };
+        let names = match (enumerated, pattern) {
+            (
+                false,
+                Pattern::Binding {
+                    name,
+                    pattern: None,
+                },
+            ) => Some((None, name)),
+            (true, Pattern::Tuple(elements)) => match &elements[..] {
+                [Pattern::Binding {
+                    name: position,
+                    pattern: None,
+                }, Pattern::Binding {
+                    name,
+                    pattern: None,
+                }] => Some((Some(position), name)),
+                [Pattern::Wildcard, Pattern::Binding {
+                    name,
+                    pattern: None,
+                }] => Some((None, name)),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let (Some(elements), Some((position, name))) = (elements, names) {
+            let item = element_type(&pointee(self.type_of(elements)));
+            let values = self.elements_once(elements)?;
+            let index = self.temporary("i");
+            self.scopes.push(HashMap::new());
+            if let Some(position) = position {
+                let ty = Type::named("usize");
+                let js = if self.is_big(&ty) {
+                    format!("BigInt({index})")
+                } else {
+                    index.clone()
+                };
+                self.insert(position, ty, &js);
+            }
+            self.insert(name, item, &format!("{values}[{index}]"));
+            let body = self.loop_body(&[], body);
+            self.scopes.pop();
+            let (label, body) = body?;
+            return Ok(format!(
+                "{label}for (let {index}=0; {index}<{values}.length; {index}++){{\n{body}}}\n"
+            ));
+        }
+        if let Some(code) = self.map_values_mut(pattern, iterable, body)? {
+            return Ok(code);
+        }
+        let item = self.item_of(&self.type_of(iterable));
+        let values = wrap(self.expression(iterable)?, ASSIGNMENT);
+        self.scopes.push(HashMap::new());
+        let (js, bindings) = match pattern {
+            Pattern::Binding {
+                name,
+                pattern: None,
+            } => (self.declare(name, item), Ok(Vec::new())),
+            _ => {
+                // Destructured at the start of the body
+                let js = self.temporary("i");
+                // The elements of `iter_mut` are shared, their fields are places
+                let reference = match iterable {
+                    Node::MethodCall { method, .. } => method == "iter_mut",
+                    iterable => matches!(iterable, Node::MutableReference(_)),
+                };
+                let bindings = self.pattern_bindings(pattern, &js, &item, reference);
+                (js, bindings)
+            }
+        };
+        let body = bindings.and_then(|bindings| self.loop_body(&bindings, body));
+        self.scopes.pop();
+        let (label, body) = body?;
+        Ok(format!("{label}for (let {js} of {values}){{\n{body}}}\n"))
+    }
+
+    // The vec behind `values.iter_mut()` or `&mut values` when its elements are scalars,
+    // which JS copies out of the array
+    fn scalar_elements<'a>(&self, node: &'a Node) -> Option<&'a Node> {
+        let elements = match node {
+            Node::MethodCall {
+                expression,
+                method,
+                input,
+                ..
+            } if method == "iter_mut" && input.is_empty() => expression.as_ref(),
+            Node::MutableReference(expression) => expression.as_ref(),
+            _ => return None,
+        };
+        let ty = pointee(self.type_of(elements));
+        (is_vec(&ty) && is_scalar(&element_type(&ty))).then_some(elements)
+    }
+
+    // `for (key, value) in map.iter_mut()` and `map.values_mut()` on scalars, JS copies
+    // the values out of the map so `value` is a cell over the entry
+    fn map_values_mut(
+        &mut self,
+        pattern: &Pattern,
+        iterable: &Node,
+        body: &[Node],
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let Node::MethodCall {
+            expression: map,
+            method,
+            input,
+            ..
+        } = iterable
+        else {
+            return Ok(None);
+        };
+        let ty = pointee(self.type_of(map));
+        let value_type = generic_argument(&ty, 1);
+        if !is_map(&ty) || !is_scalar(&value_type) || !input.is_empty() {
+            return Ok(None);
+        }
+        let (key, value) = match (method.as_str(), pattern) {
+            ("iter_mut", Pattern::Tuple(elements)) if elements.len() == 2 => {
+                (&elements[0], &elements[1])
+            }
+            ("values_mut", pattern) => (&Pattern::Wildcard, pattern),
+            _ => return Ok(None),
+        };
+        let name = match value {
+            Pattern::Binding {
+                name,
+                pattern: None,
+            } => Some(name),
+            Pattern::Wildcard => None,
+            _ => return Ok(None),
+        };
+        let values = self.elements_once(map)?;
+        let entry = self.temporary("k");
+        self.scopes.push(HashMap::new());
+        let bindings = self.pattern_bindings(key, &entry, &generic_argument(&ty, 0), false);
+        let mut program = String::new();
+        if let Some(name) = name {
+            let js = self.fresh_name(name);
+            let helper = self.runtime("$cell");
+            program = format!(
+                "let {js}={helper}(()=>{values}.get({entry}), ($v)=>{values}.set({entry}, $v));\n"
+            );
+            self.insert_cell(name, value_type, &js);
+        }
+        let body = bindings.and_then(|bindings| self.loop_body(&bindings, body));
+        self.scopes.pop();
+        let (label, body) = body?;
+        Ok(Some(format!(
+            "{label}for (const [{entry}] of {values}){{\n{program}{body}}}\n"
+        )))
+    }
+
+    // The array that is indexed on every iteration, hoisted when it is not a name
+    fn elements_once(&mut self, elements: &Node) -> Result<String, Box<dyn Error>> {
+        let values = wrap(self.expression(elements)?, ATOM);
+        if is_identifier(&values) {
+            return Ok(values);
+        }
+        let name = self.temporary("e");
+        self.hoisted.push(format!("const {name}={values};\n"));
+        Ok(name)
+    }
+
+    // `values.iter_mut().for_each(|x| ..)` on scalars, `x` stands for the element in place
+    fn for_each_mut(
+        &mut self,
+        elements: &Node,
+        input: &[Node],
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        let parameters = match input {
+            [Node::Closure { input, body, .. }] => match &input[..] {
+                [(
+                    Pattern::Binding {
+                        name,
+                        pattern: None,
+                    },
+                    _,
+                )] => Some((name, body)),
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some((name, body)) = parameters else {
+            return Err("Code generator for_each: Writing through iter_mut needs a closure with one named parameter".into());
+        };
+        let item = element_type(&pointee(self.type_of(elements)));
+        let values = self.elements_once(elements)?;
+        let index = self.temporary("i");
+        self.scopes.push(HashMap::new());
+        self.insert(name, item, &format!("{values}[{index}]"));
+        let outer_loops = std::mem::take(&mut self.loops);
+        let outer_output = self.output.take();
+        let body = self.arm_body(&[], body, &Target::Statement);
+        self.output = outer_output;
+        self.loops = outer_loops;
+        self.scopes.pop();
+        Ok((
+            format!("{values}.forEach((_, {index})=>{{\n{}}})", body?),
+            ATOM,
+        ))
+    }
+
+    // The code of an expression that is read more than once, hoisted when it is not a plain value
+    fn evaluated_once(&mut self, node: &Node, ty: &Type) -> Result<String, Box<dyn Error>> {
+        let code = wrap(self.stored(node, ty, false)?, ASSIGNMENT);
+        let number = code.strip_suffix('n').unwrap_or(&code);
+        if is_identifier(&code) || number.parse::<f64>().is_ok() {
+            return Ok(code);
+        }
+        let name = self.temporary("e");
+        self.hoisted.push(format!("const {name}={code};\n"));
+        Ok(name)
+    }
+
+    // Generates a loop body, the label is only emitted when a `break` needs it
+    fn loop_body(
+        &mut self,
+        bindings: &[Binding],
+        body: &[Node],
+    ) -> Result<(String, String), Box<dyn Error>> {
+        let label = self.temporary("l");
+        self.loops.push(Loop {
+            label,
+            used: false,
+            switches: 0,
+        });
+        let body = self.bound_block(bindings, body, &Target::Statement);
+        let current = self.loops.pop();
+        let label = match current {
+            Some(Loop {
+                label, used: true, ..
+            }) => format!("{label}:"),
+            _ => String::new(),
+        };
+        Ok((label, body?))
+    }
+
+    // The test of an `if` or `while` and, for `if let` and `while let`, the bindings of the pattern
+    fn condition(
+        &mut self,
+        condition: &Node,
+        pattern: Option<&Pattern>,
+    ) -> Result<(String, Vec<Binding>), Box<dyn Error>> {
+        let Some(pattern) = pattern else {
+            return Ok((self.expression(condition)?.0, Vec::new()));
+        };
+        let pattern = &self.normalize(pattern);
+        let ty = self.type_of(condition);
+        let reference = self.is_reference(condition);
+        let access = self.matched(condition, [pattern])?;
+        let tests = self.pattern_test(pattern, &access, &ty)?;
+        let bindings = self.pattern_bindings(pattern, &access, &ty, reference)?;
+        Ok((conjunction(&tests), bindings))
+    }
+
+    // The place a `&mut` that is matched points to, `&mut value` or `value.as_mut()`
+    fn reference_place<'a>(&self, node: &'a Node) -> Option<&'a Node> {
+        match node {
+            Node::MutableReference(place) => Some(place),
+            Node::MethodCall {
+                expression,
+                method,
+                input,
+                ..
+            } if method == "as_mut"
+                && input.is_empty()
+                && self.type_of(expression).name() == Some("Option") =>
+            {
+                Some(expression)
+            }
+            _ => None,
+        }
+    }
+
+    // Whether a matched value is a `&mut`, or an `Option` of one from `get_mut` and the
+    // like. The scalar those give is a copy in the `Option`, it is not bound as a place
+    fn is_reference(&self, node: &Node) -> bool {
+        if self.reference_place(node).is_some() {
+            return true;
+        }
+        let Node::MethodCall { method, .. } = node else {
+            return false;
+        };
+        let ty = self.type_of(node);
+        let payload = generic_argument(&ty, 0);
+        // A nullable `Option` is copied like the scalar in it
+        let nullable = self.nullable && payload.name() == Some("Option");
+        method.ends_with("_mut") && ty.name() == Some("Option") && !is_scalar(&payload) && !nullable
+    }
+
+    // The code reading a matched value. Bindings that are `&mut`s need the place itself,
+    // other values are evaluated once
+    fn matched<'a>(
+        &mut self,
+        node: &Node,
+        patterns: impl IntoIterator<Item = &'a Pattern>,
+    ) -> Result<String, Box<dyn Error>> {
+        let place = match self.reference_place(node) {
+            Some(place) => place,
+            None if patterns.into_iter().any(has_ref_mut) => node,
+            None => return self.scrutinee(node),
+        };
+        let place = self.stable_place(place)?;
+        if let Some((values, index)) = self.checked_index(&place)? {
+            // Reading it checks the bounds once
+            let helper = self.runtime("$index");
+            self.hoisted
+                .push(format!("{helper}.at({values}, {index});\n"));
+            return Ok(format!("{values}[{index}]"));
+        }
+        let code = wrap(self.expression(&place)?, ATOM);
+        if is_place_code(&code) {
+            return Ok(code);
+        }
+        // A temporary, which the bindings can still write to
+        let name = self.temporary("m");
+        self.hoisted.push(format!("let {name}={code};\n"));
+        Ok(name)
+    }
+
+    // Evaluates a matched value once, returns the code reading it
+    fn scrutinee(&mut self, node: &Node) -> Result<String, Box<dyn Error>> {
+        let code = wrap(self.expression(node)?, ATOM);
+        if is_identifier(&code) {
+            return Ok(code);
+        }
+        let name = self.temporary("m");
+        self.hoisted.push(format!("const {name}={code};\n"));
+        Ok(name)
+    }
+
+    // `let pattern = value else { ... };`, the bindings are declared in the current scope
+    fn let_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Node,
+        else_body: Option<&[Node]>,
+    ) -> Result<String, Box<dyn Error>> {
+        let pattern = &self.normalize(pattern);
+        let ty = self.type_of(value);
+        let reference = self.is_reference(value);
+        if else_body.is_none() {
+            let arm = Arm {
+                pattern: pattern.clone(),
+                guard: None,
+                body: Node::Block(Vec::new()),
+            };
+            self.check_match(&ty, &[arm])?;
+        }
+        if self.pattern_names(pattern).is_empty() && else_body.is_none() {
+            return Ok(self.expression(value)?.0);
+        }
+        let access = self.matched(value, [pattern])?;
+        let tests = self.pattern_test(pattern, &access, &ty)?;
+        let bindings = self.pattern_bindings(pattern, &access, &ty, reference)?;
+        let mut program = String::new();
+        if let (false, Some(else_body)) = (tests.is_empty(), else_body) {
+            program.push_str(
+                format!(
+                    "if (!({})){{\n{}}}\n",
+                    conjunction(&tests),
+                    self.block(else_body, &Target::Statement)?
+                )
+                .as_str(),
+            );
+        }
+        let mut declarations = Vec::new();
+        for binding in &bindings {
+            declarations.push(self.bind(binding));
+        }
+        Ok(program + &declarations.join(";\n"))
+    }
+
+    fn if_statement(
+        &mut self,
+        condition: &Node,
+        pattern: Option<&Pattern>,
+        body: &[Node],
+        else_body: Option<&[Node]>,
+        target: &Target,
+    ) -> Result<String, Box<dyn Error>> {
+        if let Some((test, binding)) = self.place_binding(condition, pattern)? {
+            let body = [vec![binding, Node::NewLine], body.to_vec()].concat();
+            return self.if_statement(&test, None, &body, else_body, target);
+        }
+        let (condition, bindings) = self.condition(condition, pattern)?;
+        let body = self.bound_block(&bindings, body, target)?;
+        let mut program = format!("if ({condition}){{\n{body}}}\n");
+        match else_body {
+            Some([chained @ Node::IfStatement { .. }]) => {
+                let code = self.tail(chained, target)?;
+                // Hoisted statements of the condition have to stay inside the else
+                if code.starts_with("if (") {
+                    program.push_str(format!("else {code}").as_str());
+                } else {
+                    program.push_str(format!("else{{\n{code}}}\n").as_str());
+                }
+            }
+            Some(else_body) => {
+                program.push_str(format!("else{{\n{}}}\n", self.block(else_body, target)?).as_str())
+            }
+            None => {}
+        }
+        Ok(program)
+    }
+
+    // Returns `(parameters){ body }`, the caller adds the keyword and name
+    fn function(
+        &mut self,
+        path: &str,
+        input: &[Parameter],
+        output: &Option<Type>,
+        body: &[Node],
+    ) -> Result<String, Box<dyn Error>> {
+        self.scopes.push(HashMap::new());
+        // The witness of `T` is named `T`, so `T::default()` calls it
+        let mut parameters: Vec<String> = match self.witnessed.get(path) {
+            Some(function) => function
+                .witnesses
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        let outer_witnesses = std::mem::replace(&mut self.witness_scope, parameters.clone());
+        let changes = changes(input, body, &self.mutating_methods);
+        let outer_changes = std::mem::replace(&mut self.changes, changes);
+        for parameter in input {
+            if parameter.name == "self" {
+                let ty = Type::named(self.current_impl.as_deref().unwrap_or("Self"));
+                self.insert("self", ty, "this");
+            } else if parameter.mutable && is_scalar(&parameter.ty) {
+                let js = self.fresh_name(&parameter.name);
+                self.insert_cell(&parameter.name, parameter.ty.clone(), &js);
+                parameters.push(js);
+            } else {
+                parameters.push(self.declare(&parameter.name, parameter.ty.clone()));
+            }
+        }
+        let target = if returns_value(output) {
+            Target::Return
+        } else {
+            Target::Statement
+        };
+        let outer_loops = std::mem::take(&mut self.loops);
+        let outer_output = std::mem::replace(&mut self.output, output.clone());
+        let body = self.statements(body, &target);
+        self.output = outer_output;
+        self.loops = outer_loops;
+        self.witness_scope = outer_witnesses;
+        self.changes = outer_changes;
+        self.scopes.pop();
+        Ok(format!("({}){{\n{}}}\n", parameters.join(", "), body?))
+    }
+
+    // The methods of every impl block for a type, as class members
+    fn methods(&mut self, name: &str) -> Result<String, Box<dyn Error>> {
+        let mut body = self.impls.get(name).cloned().unwrap_or_default();
+        // Default methods the impls do not override
+        for trait_name in self.traits.get(name).cloned().unwrap_or_default() {
+            let Some((_, methods)) = self.trait_definitions.get(&trait_name) else {
+                continue;
+            };
+            for method in methods {
+                if let Node::Function {
+                    name: method_name,
+                    body: method_body,
+                    ..
+                } = method
+                {
+                    if !method_body.is_empty() && !self.has_method(name, method_name) {
+                        body.push(method.clone());
+                    }
+                }
+            }
+        }
+        let outer = self.current_impl.replace(name.to_string());
+        let primitive = is_primitive(&Type::named(name));
+        let mut program = String::new();
+        for node in &body {
+            if let Node::Function {
+                name: method,
+                input,
+                output,
+                body,
+                ..
+            } = node
+            {
+                // A number or string can not be changed through `this`
+                if primitive && input.first().is_some_and(|p| p.name == "self" && p.mutable) {
+                    return Err(format!(
+                        "Code generator Impl: {name}::{method} can not take a mutable self"
+                    )
+                    .into());
+                }
+                let code = self.function(&format!("{name}::{method}"), input, output, body)?;
+                if input.first().is_some_and(|p| p.name == "self") && !primitive {
+                    let method = self.method_name(Some(name), method);
+                    program.push_str(format!("{method}{code}").as_str());
+                } else {
+                    program.push_str(format!("static {method}{code}").as_str());
+                }
+            }
+        }
+        // `value[i] = x` goes through a setter that assigns to the place `index_mut` returns
+        if let Some(Node::Function {
+            input,
+            output,
+            body,
+            ..
+        }) = self.find_method(name, "index_mut")
+        {
+            let value = Node::VeriableCall("$value".to_string());
+            let mut input = input.clone();
+            input.push(Parameter {
+                name: "$value".to_string(),
+                ty: output.unwrap_or(Type::Unknown),
+                mutable: false,
+            });
+            let path = format!("{name}::index_mut");
+            let code = self.function(&path, &input, &None, &assign_tail(&body, &value))?;
+            program.push_str(format!("$set_index{code}").as_str());
+        }
+        if self.has_method(name, "$display") {
+            self.runtime("$Formatter");
+            program.push_str("toString(){\nreturn $display(this);\n}\n");
+        }
+        if self.has_method(name, "$debug") {
+            self.runtime("$DebugBuilder");
+        }
+        program.push_str(&self.derived(name)?);
+        // Iterators can be used by `for of` and the `$iter` runtime
+        if self.implements(name, "Iterator") {
+            self.iterator_runtime();
+            program.push_str(
+                "*[Symbol.iterator](){\nfor (let item=this.next(); item!==Option.None; item=this.next()){\nyield $some(item);\n}\n}\n",
+            );
+        }
+        self.current_impl = outer;
+        Ok(program)
+    }
+
+    // The methods `#[derive(...)]` generates that the impls do not define themselves
+    fn derived(&mut self, name: &str) -> Result<String, Box<dyn Error>> {
+        let derives = |trait_name: &str, method: &str| {
+            self.implements(name, trait_name) && !self.has_method(name, method)
+        };
+        let (clone, eq, partial_cmp, cmp, hash, default) = (
+            derives("Clone", "clone"),
+            derives("PartialEq", "eq"),
+            derives("PartialOrd", "partial_cmp"),
+            derives("Ord", "cmp"),
+            derives("Hash", "hash"),
+            derives("Default", "default"),
+        );
+        // Enums order their variants by declaration first
+        let variants = match self.enums.get(name) {
+            Some(variants) => {
+                let names: Vec<String> =
+                    variants.iter().map(|v| format!("\"{}\"", v.name)).collect();
+                format!(", [{}]", names.join(", "))
+            }
+            None => String::new(),
+        };
+        let mut program = String::new();
+        if clone {
+            let helper = self.runtime("$clone");
+            let method = self.method_name(Some(name), "clone");
+            program.push_str(&format!(
+                "{method}(){{\nreturn {helper}_fields(this);\n}}\n"
+            ));
+        }
+        if eq {
+            let helper = self.runtime("$eq");
+            let method = self.method_name(Some(name), "eq");
+            program.push_str(&format!(
+                "{method}(other){{\nreturn {helper}_fields(this, other);\n}}\n"
+            ));
+        }
+        if partial_cmp {
+            self.prelude("Option");
+            self.prelude("Ordering");
+            self.runtime("$cmp_fields");
+            let method = self.method_name(Some(name), "partial_cmp");
+            program.push_str(&format!(
+                "{method}(other){{\nreturn $partial_cmp_fields(this, other{variants});\n}}\n"
+            ));
+        }
+        if cmp {
+            self.prelude("Ordering");
+            let helper = self.runtime("$cmp_fields");
+            let method = self.method_name(Some(name), "cmp");
+            program.push_str(&format!(
+                "{method}(other){{\nreturn {helper}(this, other{variants});\n}}\n"
+            ));
+        }
+        // The provided methods of `Ord`
+        if self.implements(name, "Ord") {
+            let cmp = self.method_name(Some(name), "cmp");
+            for (method, code) in [
+                ("max", "(other){\nreturn this.CMP(other)===Ordering.Greater?this:other;\n}\n"),
+                ("min", "(other){\nreturn this.CMP(other)===Ordering.Greater?other:this;\n}\n"),
+                ("clamp", "(min, max){\nreturn this.CMP(min)===Ordering.Less?min:this.CMP(max)===Ordering.Greater?max:this;\n}\n"),
+            ] {
+                if !self.has_method(name, method) {
+                    let method = self.method_name(Some(name), method);
+                    program.push_str(&format!("{method}{}", code.replace("CMP", &cmp)));
+                }
+            }
+        }
+        if hash {
+            let helper = self.runtime("$key");
+            let method = self.method_name(Some(name), "hash");
+            program.push_str(&format!(
+                "{method}(state){{\nstate.write_str({helper}(this));\n}}\n"
+            ));
+        }
+        if default {
+            let value = match (
+                self.structs.get(name).cloned(),
+                self.enums.get(name).cloned(),
+            ) {
+                (Some(Fields::Unit), _) => format!("new {name}({{}})"),
+                (Some(Fields::Named(fields)), _) => {
+                    let mut values = Vec::new();
+                    for (field, ty) in &fields {
+                        values.push(format!("{field}:{}", self.default_value(ty)?));
+                    }
+                    format!("new {name}({{{}}})", values.join(", "))
+                }
+                (Some(Fields::Tuple(types)), _) => {
+                    let mut values = Vec::new();
+                    for (n, ty) in types.iter().enumerate() {
+                        values.push(format!("{n}:{}", self.default_value(ty)?));
+                    }
+                    format!("new {name}({{{}}})", values.join(", "))
+                }
+                (None, Some(variants)) => match variants.iter().find(|v| v.default) {
+                    Some(variant) if matches!(variant.fields, Fields::Unit) => {
+                        format!("{name}.{}", variant.name)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Code generator Derive: {name} needs a #[default] unit variant"
+                        )
+                        .into())
+                    }
+                },
+                (None, None) => String::new(),
+            };
+            program.push_str(&format!("static default(){{\nreturn {value};\n}}\n"));
+        }
+        Ok(program)
+    }
+
+    fn implements(&self, name: &str, trait_name: &str) -> bool {
+        self.traits
+            .get(name)
+            .is_some_and(|traits| traits.iter().any(|t| t == trait_name))
+    }
+
+    fn compound_assignment(
+        &mut self,
+        target: &Node,
+        operator: Operator,
+        value: &Node,
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        if let Node::Deref(target) = target {
+            self.written_reference(target)?;
+            return self.compound_assignment(target, operator, value);
+        }
+        let ty = self.type_of(target);
+        if matches!(target, Node::MethodCall { .. }) && is_scalar(&ty) {
+            let target = self.method_place(target)?;
+            return self.compound_assignment(&target, operator, value);
+        }
+        // `AddAssign` and the other operator assignment traits change the value in place
+        if let Some(method) = self.assign_overload(&ty, operator) {
+            let (_, trait_method) = operator_trait(operator).unwrap_or_default();
+            let owner = ty.name().unwrap_or_default().to_string();
+            let receiver = self.receiver(target)?;
+            let argument = self.call_arguments(
+                &format!("{owner}::{trait_method}_assign"),
+                std::slice::from_ref(value),
+            )?;
+            return Ok((format!("{receiver}.{method}({argument})"), ATOM));
+        }
+        // Elements of a `Vec` are written back through `$index.set` below
+        if operator == Operator::Add && is_string(&ty) && !matches!(target, Node::Index { .. }) {
+            let target = wrap(self.expression(target)?, ATOM);
+            let value = wrap(self.expression(value)?, ASSIGNMENT);
+            return Ok((format!("{target}+={value}"), ASSIGNMENT));
+        }
+        // `a -= b` is `a = a - b`, which checks for overflow like the operator does
+        let target = self.stable_place(target)?;
+        self.expression(&Node::Assignment {
+            target: Box::new(target.clone()),
+            value: Box::new(Node::Binary {
+                left: Box::new(target.clone()),
+                operator,
+                right: Box::new(value.clone()),
+            }),
+        })
+    }
+
+    // A place that can be read and then written without evaluating its calls and index
+    // expressions twice, those are hoisted into temporaries
+    fn stable_place(&mut self, node: &Node) -> Result<Node, Box<dyn Error>> {
+        Ok(match node {
+            Node::Deref(expression) => Node::Deref(Box::new(self.stable_place(expression)?)),
+            Node::FieldAccess { expression, field } => Node::FieldAccess {
+                expression: Box::new(self.stable_place(expression)?),
+                field: field.clone(),
+            },
+            Node::Index { expression, index } => Node::Index {
+                expression: Box::new(self.stable_place(expression)?),
+                index: Box::new(self.stable_value(index)?),
+            },
+            _ => self.stable_value(node)?,
+        })
+    }
+
+    fn stable_value(&mut self, node: &Node) -> Result<Node, Box<dyn Error>> {
+        if matches!(
+            node,
+            Node::VeriableCall(_)
+                | Node::NumberLiteral(_)
+                | Node::StringLiteral(_)
+                | Node::CharLiteral(_)
+        ) {
+            return Ok(node.clone());
+        }
+        let ty = self.type_of(node);
+        let code = wrap(self.expression(node)?, ASSIGNMENT);
+        let name = self.temporary("e");
+        self.hoisted.push(format!("const {name}={code};\n"));
+        self.insert(&name, ty, &name);
+        Ok(Node::VeriableCall(name))
+    }
+
+    // The method of the `AddAssign` like trait of an operator that a type implements
+    fn assign_overload(&self, ty: &Type, operator: Operator) -> Option<String> {
+        let (trait_name, method) = operator_trait(operator)?;
+        self.overloaded(
+            ty,
+            &format!("{trait_name}Assign"),
+            &format!("{method}_assign"),
+        )
+    }
+
+    // The method a user type implements an operator with, primitives keep the JS operator
+    fn overloaded(&self, ty: &Type, trait_name: &str, method: &str) -> Option<String> {
+        let ty = pointee(ty.clone());
+        let name = ty.name()?;
+        // `Ord` types compare through `cmp`
+        let implemented = self.implements(name, trait_name)
+            || trait_name == "PartialOrd" && self.implements(name, "Ord");
+        implemented.then(|| self.method_name(Some(name), method))
+    }
+
+    // The definition of `method` on a type or trait, from its impls or its traits
+    fn find_method(&self, owner: &str, method: &str) -> Option<Node> {
+        let mut owners = vec![owner.to_string()];
+        let mut seen = Vec::new();
+        while let Some(owner) = owners.pop() {
+            if seen.contains(&owner) {
+                continue;
+            }
+            let body = match self.trait_definitions.get(&owner) {
+                Some((supertraits, body)) => {
+                    owners.extend(supertraits.iter().rev().cloned());
+                    body.clone()
+                }
+                None => {
+                    owners.extend(self.traits.get(&owner).into_iter().flatten().rev().cloned());
+                    self.impls.get(&owner).cloned().unwrap_or_default()
+                }
+            };
+            let found = body
+                .into_iter()
+                .find(|node| matches!(node, Node::Function { name, .. } if name == method));
+            if found.is_some() {
+                return found;
+            }
+            seen.push(owner);
+        }
+        None
+    }
+
+    // The type of a witness argument, a class or a dictionary of the builtin's static methods
+    fn witness_of(&mut self, ty: &Type, methods: &[String]) -> Result<String, Box<dyn Error>> {
+        let name = ty.name().unwrap_or_default();
+        // A generic of the calling function passes its own witness on
+        if self.witness_scope.iter().any(|witness| witness == name)
+            || self.structs.contains_key(name)
+            || self.impls.contains_key(name) && !is_primitive(ty)
+        {
+            return Ok(name.to_string());
+        }
+        let mut entries = Vec::new();
+        for method in methods {
+            match method.as_str() {
+                // The class of the impls on a primitive type
+                method if self.find_method(name, method).is_some() => {
+                    entries.push(format!("{method}:{name}.{method}"))
+                }
+                "default" => entries.push(format!("default:()=>{}", self.default_value(ty)?)),
+                "from_str" => {
+                    let Some(target) = self.parse_target(std::slice::from_ref(ty)) else {
+                        return Err(
+                            format!("Code generator Generic: Can not parse a {name}").into()
+                        );
+                    };
+                    self.prelude("Result");
+                    let helper = self.runtime("$str");
+                    entries.push(format!(
+                        "from_str:(text)=>{helper}.parse(text, \"{target}\")"
+                    ));
+                }
+                _ => {
+                    return Err(format!(
+                        "Code generator Generic: {name}::{method} is not supported"
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(format!("{{{}}}", entries.join(", ")))
+    }
+
+    // The witnesses a call to a generic function passes before its arguments
+    fn witness_arguments(
+        &mut self,
+        path: &str,
+        turbofish: &[Type],
+        input: &[Node],
+    ) -> Result<String, Box<dyn Error>> {
+        let Some(function) = self.witnessed.get(path).cloned() else {
+            return Ok(String::new());
+        };
+        let mut witnesses = Vec::new();
+        for (name, methods) in &function.witnesses {
+            let from_turbofish = function
+                .generics
+                .iter()
+                .position(|generic| generic == name)
+                .and_then(|n| turbofish.get(n))
+                .filter(|ty| **ty != Type::named("_"))
+                .cloned();
+            let from_arguments = || {
+                function
+                    .input
+                    .iter()
+                    .zip(input)
+                    .find_map(|(parameter, value)| {
+                        bind_generic(&parameter.ty, &self.type_of(value), name)
+                    })
+            };
+            let from_expected = || match (function.output.as_ref(), self.expected.as_ref()) {
+                (Some(output), Some(expected)) => bind_generic(output, expected, name),
+                _ => None,
+            };
+            let ty = from_turbofish
+                .or_else(from_arguments)
+                .or_else(from_expected);
+            match ty {
+                Some(ty) => witnesses.push(self.witness_of(&ty, methods)?),
+                None => {
+                    return Err(format!(
+                    "Code generator Generic: Can not infer {name} for {path}, annotate the result"
+                )
+                    .into())
+                }
+            }
+        }
+        Ok(witnesses.join(", "))
+    }
+
+    fn has_method(&self, name: &str, method: &str) -> bool {
+        self.impls.get(name).is_some_and(|body| {
+            body.iter()
+                .any(|node| matches!(node, Node::Function { name, .. } if name == method))
+        })
+    }
+
+    // Whether `method` on a value of `ty` is an iterator adapter or consumer
+    fn is_iterator_method(&self, ty: &Type, method: &str) -> bool {
+        if is_iterator(ty) {
+            return true;
+        }
+        let user_iterator = ty.name().is_some_and(|name| {
+            self.implements(name, "Iterator") && !self.has_method(name, method)
+        });
+        let iterable = is_vec(ty) || ty.name() == Some("VecDeque") || user_iterator;
+        iterable && ITERATOR_METHODS.contains(&method)
+    }
+
+    // The values iterating over `ty` produces, user iterators give what their `next` returns
+    fn item_of(&self, ty: &Type) -> Type {
+        match ty.name() {
+            Some(name) if self.implements(name, "Iterator") => {
+                generic_argument(&self.method_output(name, "next"), 0)
+            }
+            _ => item_type(ty),
+        }
+    }
+
+    // Elements of unknown type, like the items of a `map`, are strings when the separator is
+    fn joins_strings(&self, element: &Type, input: &[Node]) -> bool {
+        is_string(element)
+            || matches!(element, Type::Unknown)
+                && input
+                    .first()
+                    .is_some_and(|separator| is_string(&pointee(self.type_of(separator))))
+    }
+
+    // `value?` returns early with the `Err` or `None`, otherwise it unwraps the value
+    // The `Vec` API on JS arrays, `None` for methods it does not cover
+    fn vec_method(
+        &mut self,
+        expression: &Node,
+        method: &str,
+        input: &[Node],
+    ) -> Result<Option<(String, u8)>, Box<dyn Error>> {
+        let receiver = |generator: &mut Generator| generator.receiver(expression);
+        // Elements that are objects are shared anyway, scalars are written through
+        // `element_place`
+        let method = match method {
+            "get_mut" | "first_mut" | "last_mut" => method.trim_end_matches("_mut"),
+            method => method,
+        };
+        // Positions are numbers for the JS methods, values are stored as elements
+        let element = element_type(&pointee(self.type_of(expression)));
+        let arguments = |generator: &mut Generator| -> Result<String, Box<dyn Error>> {
+            let mut arguments = Vec::new();
+            for (n, node) in input.iter().enumerate() {
+                let code = match method {
+                    "insert" | "resize" if n == 0 => generator.position(node)?,
+                    "get" | "remove" | "swap" | "truncate" | "windows" | "chunks" | "split_off"
+                    | "split_at" => generator.position(node)?,
+                    "push" | "contains" | "insert" => generator.stored(node, &element, true)?,
+                    _ => generator.copied(node, true)?,
+                };
+                arguments.push(wrap(code, ASSIGNMENT));
+            }
+            Ok(arguments.join(", "))
+        };
+        Ok(Some(match method {
+            // Iterators are still arrays here
+            "len" | "count" => {
+                let code = format!("{}.length", receiver(self)?);
+                self.length(code)
+            }
+            "is_empty" => (format!("{}.length===0", receiver(self)?), 9),
+            "push" | "reverse" => (
+                format!("{}.{method}({})", receiver(self)?, arguments(self)?),
+                ATOM,
+            ),
+            "contains" => (
+                format!("{}.includes({})", receiver(self)?, arguments(self)?),
+                ATOM,
+            ),
+            "truncate" => (
+                format!("{}.splice({})", receiver(self)?, arguments(self)?),
+                ATOM,
+            ),
+            "clear" => (format!("{}.length=0", receiver(self)?), ASSIGNMENT),
+            "for_each" if self.scalar_elements(expression).is_some() => {
+                let elements = self.scalar_elements(expression).unwrap_or(expression);
+                self.for_each_mut(elements, input)?
+            }
+            "iter_mut" if is_scalar(&element) => {
+                return Err("Code generator iter_mut: Writing through the elements of a vec of scalars is only supported in for loops and for_each".into())
+            }
+            // Arrays can already be iterated and sliced
+            "iter" | "iter_mut" | "into_iter" | "as_slice" | "as_mut_slice" => {
+                self.expression(expression)?
+            }
+            "pop" | "get" | "first" | "last" | "insert" | "remove" | "swap" | "extend"
+            | "dedup" | "retain" | "sort" | "sort_unstable" | "sort_by" | "sort_by_key"
+            | "windows" | "chunks" => {
+                if matches!(method, "pop" | "get" | "first" | "last") {
+                    self.prelude("Option");
+                }
+                let method = method.strip_suffix("_unstable").unwrap_or(method);
+                let mut values = vec![wrap(self.expression(expression)?, ASSIGNMENT)];
+                if !input.is_empty() {
+                    values.push(arguments(self)?);
+                }
+                let helper = self.runtime("$vec");
+                (format!("{helper}.{method}({})", values.join(", ")), ATOM)
+            }
+            "split_off" | "split_at" | "resize" => {
+                let helper = self.runtime("$vec");
+                let vector = wrap(self.expression(expression)?, ASSIGNMENT);
+                (
+                    format!("{helper}.{method}({vector}, {})", arguments(self)?),
+                    ATOM,
+                )
+            }
+            // The removed elements, the range is numbers like the other positions
+            "drain" => {
+                let helper = self.runtime("$vec");
+                let mut values = vec![wrap(self.expression(expression)?, ASSIGNMENT)];
+                match input {
+                    [Node::Range {
+                        start,
+                        end,
+                        inclusive,
+                    }] => {
+                        values.push(match start {
+                            Some(start) => wrap(self.position(start)?, ASSIGNMENT),
+                            None => "undefined".to_string(),
+                        });
+                        if let Some(end) = end {
+                            let end = self.position(end)?;
+                            values.push(if *inclusive {
+                                format!("{}+1", wrap(end, 12))
+                            } else {
+                                wrap(end, ASSIGNMENT)
+                            });
+                        }
+                    }
+                    _ => {
+                        return Err(
+                            "Code generator Vec: drain is only supported with a range".into()
+                        )
+                    }
+                }
+                (format!("{helper}.drain({})", values.join(", ")), ATOM)
+            }
+            "binary_search" => {
+                self.prelude("Result");
+                let helper = self.runtime("$vec");
+                let mut values = vec![wrap(self.expression(expression)?, ASSIGNMENT)];
+                values.push(arguments(self)?);
+                if self.bigint_usize {
+                    values.push("0n".to_string());
+                }
+                (format!("{helper}.binary_search({})", values.join(", ")), ATOM)
+            }
+            // Strings are joined, vectors are flattened
+            "concat" | "join" if self.joins_strings(&element, input) => {
+                let separator = if method == "join" {
+                    arguments(self)?
+                } else {
+                    "\"\"".to_string()
+                };
+                (format!("{}.join({separator})", receiver(self)?), ATOM)
+            }
+            "concat" => (format!("{}.flat()", receiver(self)?), ATOM),
+            "to_vec" => {
+                let helper = self.runtime("$clone");
+                let vector = wrap(self.expression(expression)?, ASSIGNMENT);
+                (format!("{helper}({vector})"), ATOM)
+            }
+            // JS arrays grow as needed, their capacity is their length
+            "capacity" => {
+                let code = format!("{}.length", receiver(self)?);
+                self.length(code)
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    // The map and set API on `Map`, `Set` and the sorted runtime classes,
+    // `None` for methods it does not cover
+    fn collection_method(
+        &mut self,
+        expression: &Node,
+        method: &str,
+        input: &[Node],
+        map: bool,
+    ) -> Result<Option<(String, u8)>, Box<dyn Error>> {
+        let receiver = |generator: &mut Generator| generator.receiver(expression);
+        Ok(Some(match method {
+            "len" => {
+                let code = format!("{}.size", receiver(self)?);
+                self.length(code)
+            }
+            "is_empty" => (format!("{}.size===0", receiver(self)?), 9),
+            "clear" => (format!("{}.clear()", receiver(self)?), ATOM),
+            "contains_key" if map => (
+                format!("{}.has({})", receiver(self)?, self.arguments(input)?),
+                ATOM,
+            ),
+            "keys" | "values" | "into_keys" | "into_values" if map => {
+                let method = method.strip_prefix("into_").unwrap_or(method);
+                (format!("[...{}.{method}()]", receiver(self)?), ATOM)
+            }
+            "contains" | "remove" if !map => {
+                let method = if method == "contains" {
+                    "has"
+                } else {
+                    "delete"
+                };
+                (
+                    format!("{}.{method}({})", receiver(self)?, self.arguments(input)?),
+                    ATOM,
+                )
+            }
+            "iter_mut" | "values_mut" if map && is_scalar(&generic_argument(&pointee(self.type_of(expression)), 1)) => {
+                return Err("Code generator iter_mut: Writing through the values of a map of scalars is only supported in for loops".into())
+            }
+            "values_mut" if map => (format!("[...{}.values()]", receiver(self)?), ATOM),
+            // Iterators are still arrays here
+            "iter" | "iter_mut" | "into_iter" => (
+                format!("[...{}]", wrap(self.expression(expression)?, ASSIGNMENT)),
+                ATOM,
+            ),
+            "insert" | "get" | "get_mut" | "remove" | "entry" | "extend" | "retain" if map => {
+                if matches!(method, "insert" | "get" | "get_mut" | "remove") {
+                    self.prelude("Option");
+                }
+                let method = if method == "get_mut" { "get" } else { method };
+                self.helper_call("$map", method, expression, input)?
+            }
+            "insert"
+            | "extend"
+            | "retain"
+            | "union"
+            | "intersection"
+            | "difference"
+            | "symmetric_difference"
+            | "is_subset"
+            | "is_superset"
+            | "is_disjoint" => self.helper_call("$set", method, expression, input)?,
+            _ => return Ok(None),
+        }))
+    }
+
+    // `helper.method(receiver, input)` for the runtime objects that stand in for methods
+    fn helper_call(
+        &mut self,
+        helper: &'static str,
+        method: &str,
+        expression: &Node,
+        input: &[Node],
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        let mut arguments = vec![wrap(self.expression(expression)?, ASSIGNMENT)];
+        if !input.is_empty() {
+            arguments.push(self.arguments(input)?);
+        }
+        let helper = self.runtime(helper);
+        Ok((format!("{helper}.{method}({})", arguments.join(", ")), ATOM))
+    }
+
+    // `new`, `with_capacity`, `default` and `from` of the std collections
+    fn collection(&mut self, name: &str, input: &[Node]) -> Result<(String, u8), Box<dyn Error>> {
+        let resolved = self.resolve(name);
+        let (ty, function) = resolved.split_once("::").unwrap_or_default();
+        // Keys that JS would compare by reference go through their serialised form
+        let mut key = match input {
+            [values] => element_type(&self.type_of(values)),
+            _ => Type::Unknown,
+        };
+        if let (Type::Tuple(types), "HashMap") = (&key, ty) {
+            key = types.first().cloned().unwrap_or(Type::Unknown);
+        }
+        if let (Type::Unknown, Some(expected)) = (&key, &self.expected) {
+            if expected.name() == Some(ty) {
+                key = generic_argument(expected, 0);
+            }
+        }
+        let class = self.collection_class(ty, &key);
+        Ok(match (function, input) {
+            ("new" | "with_capacity" | "default", _) => (format!("new {class}()"), ATOM),
+            ("from" | "from_iter", [values]) if class == "VecDeque" => (
+                format!(
+                    "{class}.from({})",
+                    wrap(self.expression(values)?, ASSIGNMENT)
+                ),
+                ATOM,
+            ),
+            ("from" | "from_iter", [values]) => (
+                format!(
+                    "new {class}({})",
+                    wrap(self.expression(values)?, ASSIGNMENT)
+                ),
+                ATOM,
+            ),
+            _ => {
+                return Err(
+                    format!("Code generator Collection: Unknown function {resolved}").into(),
+                )
+            }
+        })
+    }
+
+    // The JS class a std collection is built with, keys that JS would compare by
+    // reference go through their serialised form
+    fn collection_class(&mut self, ty: &str, key: &Type) -> &'static str {
+        let keyed = !self.is_plain_key(key);
+        match ty {
+            "HashMap" if keyed => self.runtime("$KeyMap"),
+            "HashSet" if keyed => self.runtime("$KeySet"),
+            "HashMap" => "Map",
+            "HashSet" => "Set",
+            "BTreeMap" => self.runtime("BTreeMap"),
+            "BTreeSet" => self.runtime("BTreeSet"),
+            _ => {
+                self.prelude("Option");
+                self.runtime("VecDeque")
+            }
+        }
+    }
+
+    fn iterator_runtime(&mut self) -> &'static str {
+        self.prelude("Option");
+        self.runtime("$iter")
+    }
+
+    // Iterator adapters stay lazy on the `$iter` runtime, simple chains over arrays
+    // and ranges are fused into a single loop when `fuse_iterators` is set
+    fn iterator_method(
+        &mut self,
+        node: &Node,
+        expression: &Node,
+        method: &str,
+        generics: &[Type],
+        input: &[Node],
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        // The elements `iter_mut` gives are `&mut`s, so are the scalars closures destructure
+        let references;
+        let (node, input) = match expression {
+            Node::MethodCall {
+                method: source,
+                input: arguments,
+                ..
+            } if source == "iter_mut"
+                && arguments.is_empty()
+                && matches!(method, "for_each" | "map") =>
+            {
+                let input: Vec<Node> = input.iter().map(reference_parameters).collect();
+                references = Node::MethodCall {
+                    expression: Box::new(expression.clone()),
+                    method: method.to_string(),
+                    generics: generics.to_vec(),
+                    input: input.clone(),
+                };
+                (&references, input)
+            }
+            _ => (node, input.to_vec()),
+        };
+        let input = &input[..];
+        if self.fuse_iterators {
+            if let Some(code) = self.fused(node)? {
+                return Ok(code);
+            }
+        }
+        let ty = self.type_of(expression);
+        let item = self.item_of(&ty);
+        let helper = self.iterator_runtime();
+        let receiver = if is_iterator(&ty) {
+            self.receiver(expression)?
+        } else {
+            format!(
+                "{helper}({})",
+                wrap(self.expression(expression)?, ASSIGNMENT)
+            )
+        };
+        let mut input = self.iterator_arguments(method, input, &item)?.join(", ");
+        match method {
+            "collect" => return self.collect(receiver, generics, &item),
+            "count" => return Ok(self.length(format!("{receiver}.count()"))),
+            // Integer totals overflow like the operator does
+            "sum" | "product" if is_integer(&self.total_type(generics, &item)) => {
+                let ty = self.total_type(generics, &item);
+                let (init, step) = self.total(method, &ty, "a", "b");
+                return Ok((format!("{receiver}.fold({init}, (a, b)=>{step})"), ATOM));
+            }
+            "enumerate" if self.bigint_usize => input = "0n".to_string(),
+            "position" if self.bigint_usize => input.push_str(", 0n"),
+            _ => {}
+        }
+        Ok((format!("{receiver}.{method}({input})"), ATOM))
+    }
+
+    // The type `sum` and `product` add up to, from the turbofish, the annotation of the
+    // `let` or the items
+    fn total_type(&self, generics: &[Type], item: &Type) -> Type {
+        generics
+            .first()
+            .cloned()
+            .or_else(|| self.expected.clone().filter(is_integer))
+            .unwrap_or_else(|| pointee(item.clone()))
+    }
+
+    // The first total of an integer `sum` or `product` and the next one from a value,
+    // which checks for overflow or wraps like `+` and `*` do
+    fn total(&mut self, method: &str, ty: &Type, total: &str, value: &str) -> (String, String) {
+        let (operator, operation, init) = if method == "sum" {
+            (Operator::Add, "add", "0")
+        } else {
+            (Operator::Multiply, "multiply", "1")
+        };
+        let init = if self.is_big(ty) {
+            format!("{init}n")
+        } else {
+            init.to_string()
+        };
+        let name = self.runtime_type(ty.name().unwrap_or_default()).to_string();
+        // The exact product of two 32 bit integers can need more than the 53 bits of a double
+        if self.release && operator == Operator::Multiply && matches!(name.as_str(), "i32" | "u32")
+        {
+            let code = format!("Math.imul({total}, {value})");
+            return (
+                init,
+                if name == "u32" {
+                    format!("{code}>>>0")
+                } else {
+                    code
+                },
+            );
+        }
+        let code = (
+            format!("{total}{}{value}", operator_js(operator)),
+            operator_precedence(operator),
+        );
+        let (step, _) = self.overflowed(code, &name, operation, self.release);
+        (init, step)
+    }
+
+    // Closures given to iterator methods take the item type for their unannotated parameters
+    fn iterator_arguments(
+        &mut self,
+        method: &str,
+        input: &[Node],
+        item: &Type,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let types = match (method, input) {
+            ("fold", [init, _]) => vec![self.type_of(init), item.clone()],
+            ("max_by" | "min_by", _) => vec![item.clone(), item.clone()],
+            _ => vec![item.clone()],
+        };
+        let mut arguments = Vec::new();
+        for node in input {
+            let node = match node {
+                Node::Closure {
+                    input,
+                    body,
+                    capture,
+                } => Node::Closure {
+                    input: input
+                        .iter()
+                        .zip(types.iter().chain(std::iter::repeat(&Type::Unknown)))
+                        .map(|((pattern, ty), item)| {
+                            (pattern.clone(), ty.clone().or(Some(item.clone())))
+                        })
+                        .collect(),
+                    body: body.clone(),
+                    capture: *capture,
+                },
+                node => node.clone(),
+            };
+            arguments.push(wrap(self.expression(&node)?, ASSIGNMENT));
+        }
+        Ok(arguments)
+    }
+
+    // The collection `collect` builds, from its turbofish or the annotation of the `let`
+    fn collect_target(&self, generics: &[Type], item: &Type) -> Type {
+        let collections = [
+            "Vec", "String", "VecDeque", "HashMap", "HashSet", "BTreeMap", "BTreeSet", "Result",
+            "Option",
+        ];
+        let target = generics.first().cloned().or_else(|| {
+            self.expected
+                .clone()
+                .filter(|ty| ty.name().is_some_and(|name| collections.contains(&name)))
+        });
+        let Some(Type::Named { name, generics }) = target else {
+            return Type::Named {
+                name: "Vec".to_string(),
+                generics: vec![item.clone()],
+            };
+        };
+        // `Vec<_>` takes the item type
+        let placeholder = generics
+            .iter()
+            .all(|ty| matches!(ty, Type::Unknown) || ty.name() == Some("_"));
+        let generics = match (&name[..], item) {
+            ("HashMap" | "BTreeMap", Type::Tuple(types)) if placeholder => types.clone(),
+            ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", _) if placeholder => vec![item.clone()],
+            _ => generics,
+        };
+        Type::Named { name, generics }
+    }
+
+    fn collect(
+        &mut self,
+        receiver: String,
+        generics: &[Type],
+        item: &Type,
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        let target = self.collect_target(generics, item);
+        let name = target.name().unwrap_or_default();
+        Ok(match name {
+            "String" => (format!("{receiver}.collect().join(\"\")"), ATOM),
+            "Result" => {
+                self.prelude("Result");
+                (format!("{receiver}.collect_result()"), ATOM)
+            }
+            "Option" => (format!("{receiver}.collect_option()"), ATOM),
+            "HashMap" | "HashSet" | "BTreeMap" | "BTreeSet" | "VecDeque" => {
+                let key = match item {
+                    Type::Tuple(types) if name.ends_with("Map") => {
+                        types.first().cloned().unwrap_or(Type::Unknown)
+                    }
+                    _ => item.clone(),
+                };
+                match self.collection_class(name, &key) {
+                    "VecDeque" => (format!("VecDeque.from({receiver})"), ATOM),
+                    class => (format!("new {class}({receiver})"), ATOM),
+                }
+            }
+            _ => (format!("{receiver}.collect()"), ATOM),
+        })
+    }
+
+    // `source.adapters().consumer()` as one loop, `None` when the chain has other parts
+    fn fused(&mut self, node: &Node) -> Result<Option<(String, u8)>, Box<dyn Error>> {
+        let Node::MethodCall {
+            expression,
+            method,
+            generics,
+            input,
+        } = node
+        else {
+            return Ok(None);
+        };
+        let mut adapters = Vec::new();
+        let mut source = expression.as_ref();
+        while let Node::MethodCall {
+            expression,
+            method,
+            input,
+            ..
+        } = source
+        {
+            let adapter = matches!(
+                method.as_str(),
+                "map"
+                    | "filter"
+                    | "enumerate"
+                    | "take"
+                    | "skip"
+                    | "take_while"
+                    | "skip_while"
+                    | "inspect"
+                    | "copied"
+            );
+            if !adapter {
+                break;
+            }
+            adapters.push((method.as_str(), input.as_slice()));
+            source = expression;
+        }
+        adapters.reverse();
+        // `iter()` of an array is the array itself
+        if let Node::MethodCall {
+            expression,
+            method,
+            input,
+            ..
+        } = source
+        {
+            let iter = matches!(method.as_str(), "iter" | "iter_mut" | "into_iter");
+            if iter && input.is_empty() && is_vec(&self.type_of(expression)) {
+                if self.scalar_elements(source).is_some() {
+                    return Err("Code generator iter_mut: Writing through the elements of a vec of scalars is only supported in for loops and for_each".into());
+                }
+                source = expression;
+            }
+        }
+        let consumer = match method.as_str() {
+            "collect" => matches!(
+                self.collect_target(generics, &Type::Unknown).name(),
+                Some("Vec" | "String")
+            ),
+            "sum" | "product" | "count" | "for_each" | "fold" | "any" | "all" | "find"
+            | "position" => true,
+            _ => false,
+        };
+        let range = matches!(source, Node::Range { end: Some(_), .. });
+        if !consumer || !(range || is_vec(&self.type_of(source))) {
+            return Ok(None);
+        }
+
+        let mut setup = String::new();
+        let mut steps = String::new();
+        let mut checks = String::new();
+        let value = self.temporary("v");
+        let mut item = self.item_of(&self.type_of(source));
+        let header = match source {
+            Node::Range {
+                start,
+                end: Some(end),
+                inclusive,
+            } => {
+                let counter = self.temporary("n");
+                let first = match start {
+                    Some(start) => wrap(self.expression(start)?, ASSIGNMENT),
+                    None => "0".to_string(),
+                };
+                let last = self.temporary("e");
+                setup.push_str(
+                    format!(
+                        "const {last}={};\n",
+                        wrap(self.expression(end)?, ASSIGNMENT)
+                    )
+                    .as_str(),
+                );
+                steps.push_str(format!("let {value}={counter};\n").as_str());
+                let compare = if *inclusive { "<=" } else { "<" };
+                format!("let {counter}={first}; {counter}{compare}{last}; {counter}++")
+            }
+            _ => format!(
+                "let {value} of {}",
+                wrap(self.expression(source)?, ASSIGNMENT)
+            ),
+        };
+        for (adapter, input) in adapters {
+            let argument = self.iterator_arguments(adapter, input, &item)?.concat();
+            // Closures are created once, before the loop
+            let function = |setup: &mut String, generator: &mut Generator| {
+                if is_identifier(&argument) {
+                    return argument.clone();
+                }
+                let name = generator.temporary("f");
+                setup.push_str(format!("const {name}={argument};\n").as_str());
+                name
+            };
+            match adapter {
+                "map" => {
+                    let f = function(&mut setup, self);
+                    steps.push_str(format!("{value}={f}({value});\n").as_str());
+                    item = Type::Unknown;
+                }
+                "filter" => {
+                    let f = function(&mut setup, self);
+                    steps.push_str(format!("if (!{f}({value})){{\ncontinue;\n}}\n").as_str());
+                }
+                "inspect" => {
+                    let f = function(&mut setup, self);
+                    steps.push_str(format!("{f}({value});\n").as_str());
+                }
+                "enumerate" => {
+                    let index = self.temporary("n");
+                    let zero = self.first_index();
+                    setup.push_str(format!("let {index}={zero};\n").as_str());
+                    steps.push_str(format!("{value}=[{index}++, {value}];\n").as_str());
+                    item = Type::Tuple(vec![Type::named("usize"), item]);
+                }
+                // The check runs before anything is pulled, like `Take` does
+                "take" => {
+                    let left = self.temporary("n");
+                    setup.push_str(format!("let {left}={argument};\n").as_str());
+                    checks.push_str(format!("if ({left}<=0){{\nbreak;\n}}\n").as_str());
+                    steps.push_str(format!("{left}--;\n").as_str());
+                }
+                "skip" => {
+                    let left = self.temporary("n");
+                    setup.push_str(format!("let {left}={argument};\n").as_str());
+                    steps.push_str(format!("if ({left}>0){{\n{left}--;\ncontinue;\n}}\n").as_str());
+                }
+                "take_while" => {
+                    let f = function(&mut setup, self);
+                    steps.push_str(format!("if (!{f}({value})){{\nbreak;\n}}\n").as_str());
+                }
+                "skip_while" => {
+                    let f = function(&mut setup, self);
+                    let skipping = self.temporary("s");
+                    setup.push_str(format!("let {skipping}=true;\n").as_str());
+                    steps.push_str(
+                        format!(
+                            "if ({skipping}&&{f}({value})){{\ncontinue;\n}}\n{skipping}=false;\n"
+                        )
+                        .as_str(),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let result = self.temporary("r");
+        let mut arguments = self.iterator_arguments(method, input, &item)?.into_iter();
+        let mut argument = || arguments.next().unwrap_or_default();
+        let output = match method.as_str() {
+            "collect" if self.collect_target(generics, &item).name() == Some("String") => {
+                setup.push_str(format!("let {result}=\"\";\n").as_str());
+                steps.push_str(format!("{result}+={value};\n").as_str());
+                Some(result)
+            }
+            "collect" => {
+                setup.push_str(format!("const {result}=[];\n").as_str());
+                steps.push_str(format!("{result}.push({value});\n").as_str());
+                Some(result)
+            }
+            "sum" | "product" if is_integer(&self.total_type(generics, &item)) => {
+                let ty = self.total_type(generics, &item);
+                let (init, step) = self.total(method, &ty, &result, &value);
+                setup.push_str(format!("let {result}={init};\n").as_str());
+                steps.push_str(format!("{result}={step};\n").as_str());
+                Some(result)
+            }
+            "sum" | "product" | "count" => {
+                let (init, step) = match method.as_str() {
+                    "sum" => ("0", format!("{result}+={value}")),
+                    "product" => ("1", format!("{result}*={value}")),
+                    _ => (self.first_index(), format!("{result}++")),
+                };
+                setup.push_str(format!("let {result}={init};\n").as_str());
+                steps.push_str(format!("{step};\n").as_str());
+                Some(result)
+            }
+            "fold" => {
+                let init = argument();
+                let f = self.temporary("f");
+                setup.push_str(
+                    format!("let {result}={init};\nconst {f}={};\n", argument()).as_str(),
+                );
+                steps.push_str(format!("{result}={f}({result}, {value});\n").as_str());
+                Some(result)
+            }
+            "for_each" => {
+                setup.push_str(format!("const {result}={};\n", argument()).as_str());
+                steps.push_str(format!("{result}({value});\n").as_str());
+                None
+            }
+            "any" | "all" => {
+                let (test, found) = if method == "any" {
+                    ("", "true")
+                } else {
+                    ("!", "false")
+                };
+                setup.push_str(format!("const {result}={};\n", argument()).as_str());
+                steps.push_str(
+                    format!("if ({test}{result}({value})){{\nreturn {found};\n}}\n").as_str(),
+                );
+                Some((if method == "any" { "false" } else { "true" }).to_string())
+            }
+            _ => {
+                self.prelude("Option");
+                setup.push_str(format!("const {result}={};\n", argument()).as_str());
+                if method == "find" {
+                    steps.push_str(
+                        format!("if ({result}({value})){{\nreturn Option.Some({value});\n}}\n")
+                            .as_str(),
+                    );
+                } else {
+                    let index = self.temporary("n");
+                    let zero = self.first_index();
+                    setup.push_str(format!("let {index}={zero};\n").as_str());
+                    steps.push_str(
+                        format!(
+                            "if ({result}({value})){{\nreturn Option.Some({index});\n}}\n{index}++;\n"
+                        )
+                        .as_str(),
+                    );
+                }
+                Some("Option.None".to_string())
+            }
+        };
+        let output = match output {
+            Some(output) => format!("return {output};\n"),
+            None => String::new(),
+        };
+        Ok(Some((
+            format!("(()=>{{\n{setup}for ({header}){{\n{checks}{steps}}}\n{output}}})()"),
+            ATOM,
+        )))
+    }
+
+    // Keys that JS `Map` and `Set` already compare by value
+    fn is_plain_key(&self, ty: &Type) -> bool {
+        match ty.name() {
+            Some(name) => {
+                is_copy(ty) || is_string(ty) || self.is_fieldless_enum(&Type::named(name))
+            }
+            None => false,
+        }
+    }
+
+    // Structs, enums with data and the types lowered to arrays and collections
+    // are JS objects, so `==` has to compare them field by field
+    fn is_structural(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Tuple(types) => !types.is_empty(),
+            Type::Array(..) => true,
+            Type::Named { name, .. } => {
+                matches!(
+                    name.as_str(),
+                    "Vec" | "VecDeque" | "HashMap" | "HashSet" | "BTreeMap" | "BTreeSet" | "Result"
+                ) || (name == "Option" && !self.nullable)
+                    || matches!(name.as_str(), "Box" | "Rc")
+                        && self.is_structural(&generic_argument(ty, 0))
+                    || (self.structs.contains_key(name) || self.enums.contains_key(name))
+                        && !self.is_fieldless_enum(ty)
+            }
+            _ => false,
+        }
+    }
+
+    // The value `Default::default` gives for a type, used by `or_default`
+    fn default_value(&mut self, ty: &Type) -> Result<String, Box<dyn Error>> {
+        let name = ty.name().unwrap_or_default();
+        Ok(match ty {
+            Type::Array(..) => "[]".to_string(),
+            Type::Tuple(types) if types.is_empty() => "undefined".to_string(),
+            _ if self.is_big(ty) => "0n".to_string(),
+            _ if integer_range(name).is_some() || matches!(name, "f32" | "f64") => "0".to_string(),
+            _ if is_string(ty) => "\"\"".to_string(),
+            _ if name == "bool" => "false".to_string(),
+            _ if name == "char" => "\"\\0\"".to_string(),
+            _ if name == "Vec" => "[]".to_string(),
+            _ if name == "Option" => {
+                self.prelude("Option");
+                self.expression(&Node::VeriableCall("None".to_string()))?.0
+            }
+            _ if is_collection(&format!("{name}::")) => {
+                self.collection(&format!("{name}::new"), &[])?.0
+            }
+            Type::Tuple(types) => {
+                let mut values = Vec::new();
+                for ty in types {
+                    values.push(self.default_value(ty)?);
+                }
+                format!("[{}]", values.join(", "))
+            }
+            _ if self.implements(name, "Default") => format!("{name}.default()"),
+            _ => return Err(format!("Code generator Default: No default value for {ty:?}").into()),
+        })
+    }
+
+    // Strings keep Rust's UTF-8 lengths and byte indices unless `fast_strings` is set
+    fn string_method(
+        &mut self,
+        expression: &Node,
+        method: &str,
+        generics: &[Type],
+        input: &[Node],
+    ) -> Result<Option<(String, u8)>, Box<dyn Error>> {
+        // Closures and arrays of chars are matched by the runtime
+        let plain = input.first().is_none_or(|pattern| {
+            let ty = pointee(self.type_of(pattern));
+            is_string(&ty) || ty.name() == Some("char")
+        });
+        let js = match method {
+            "contains" if plain => "includes",
+            "starts_with" if plain => "startsWith",
+            "ends_with" if plain => "endsWith",
+            "trim" => "trim",
+            "trim_start" => "trimStart",
+            "trim_end" => "trimEnd",
+            "to_uppercase" => "toUpperCase",
+            "to_lowercase" => "toLowerCase",
+            _ => "",
+        };
+        if !js.is_empty() {
+            let receiver = self.receiver(expression)?;
+            return Ok(Some((
+                format!("{receiver}.{js}({})", self.arguments(input)?),
+                ATOM,
+            )));
+        }
+        Ok(Some(match method {
+            "len" if self.fast_strings => {
+                let code = format!("{}.length", self.receiver(expression)?);
+                self.length(code)
+            }
+            "is_empty" => (format!("{}.length===0", self.receiver(expression)?), 9),
+            "contains" | "starts_with" | "ends_with" => {
+                let helper = self.runtime("$str");
+                let text = wrap(self.expression(expression)?, ASSIGNMENT);
+                (
+                    format!("{helper}.{method}({text}, {})", self.arguments(input)?),
+                    ATOM,
+                )
+            }
+            "trim_matches" | "trim_start_matches" | "trim_end_matches" => {
+                let helper = self.runtime("$str");
+                let text = wrap(self.expression(expression)?, ASSIGNMENT);
+                let start = method != "trim_end_matches";
+                let end = method != "trim_start_matches";
+                (
+                    format!(
+                        "{helper}.trim_matches({text}, {}, {start}, {end})",
+                        self.arguments(input)?
+                    ),
+                    ATOM,
+                )
+            }
+            // JS strings can not be changed in place, the place gets the new string
+            "insert" | "insert_str" => {
+                let [index, value] = input else {
+                    return Ok(None);
+                };
+                let helper = self.runtime("$str");
+                let place = self.stable_place(expression)?;
+                let text = wrap(self.expression(&place)?, ASSIGNMENT);
+                let index = wrap(self.position(index)?, ASSIGNMENT);
+                let value = wrap(self.expression(value)?, ASSIGNMENT);
+                let code = format!(
+                    "{helper}.insert({text}, {index}, {value}, {})",
+                    self.fast_strings
+                );
+                match self.checked_index(&place)? {
+                    Some((values, index)) => {
+                        let helper = self.runtime("$index");
+                        (format!("{helper}.set({values}, {index}, {code})"), ATOM)
+                    }
+                    None => (
+                        format!("{}={code}", wrap(self.expression(&place)?, ATOM)),
+                        ASSIGNMENT,
+                    ),
+                }
+            }
+            "repeat" if input.len() == 1 => {
+                let count = wrap(self.position(&input[0])?, ASSIGNMENT);
+                (
+                    format!("{}.repeat({count})", self.receiver(expression)?),
+                    ATOM,
+                )
+            }
+            // A byte index, or a UTF-16 one with `fast_strings`
+            "find" | "rfind" => {
+                self.prelude("Option");
+                let helper = self.runtime("$str");
+                let text = wrap(self.expression(expression)?, ASSIGNMENT);
+                let mut arguments = vec![
+                    text,
+                    self.arguments(input)?,
+                    self.fast_strings.to_string(),
+                    (method == "rfind").to_string(),
+                ];
+                if self.bigint_usize {
+                    arguments.push("0n".to_string());
+                }
+                (format!("{helper}.find({})", arguments.join(", ")), ATOM)
+            }
+            "chars" => (
+                format!("[...{}]", wrap(self.expression(expression)?, ASSIGNMENT)),
+                ATOM,
+            ),
+            "to_string" | "to_owned" | "clone" | "as_str" | "into" => {
+                self.expression(expression)?
+            }
+            // JS strings can not be changed in place
+            "push_str" | "push" if input.len() == 1 => {
+                self.compound_assignment(expression, Operator::Add, &input[0])?
+            }
+            // Generics and user types parse through their `FromStr` impl
+            "parse"
+                if self.parsed_type(generics).is_some_and(|ty| {
+                    let name = ty.name().unwrap_or_default();
+                    self.witness_scope.iter().any(|witness| witness == name)
+                        || self.has_method(name, "from_str")
+                }) =>
+            {
+                let ty = self.parsed_type(generics).unwrap_or(Type::Unknown);
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                (
+                    format!("{}.from_str({value})", ty.name().unwrap_or_default()),
+                    ATOM,
+                )
+            }
+            "len" | "bytes" | "as_bytes" | "char_indices" | "split" | "splitn" | "rsplit"
+            | "split_once" | "rsplit_once" | "split_whitespace" | "lines" | "replace" | "parse" => {
+                let mut arguments = vec![wrap(self.expression(expression)?, ASSIGNMENT)];
+                if !input.is_empty() {
+                    arguments.push(self.arguments(input)?);
+                }
+                match method {
+                    "split_once" | "rsplit_once" => self.prelude("Option"),
+                    "parse" => {
+                        self.prelude("Result");
+                        arguments.push(match self.parse_target(generics) {
+                            Some(ty) => format!("\"{ty}\""),
+                            None => "null".to_string(),
+                        });
+                    }
+                    _ => {}
+                }
+                let method = if method == "as_bytes" {
+                    "bytes"
+                } else {
+                    method
+                };
+                let helper = self.runtime("$str");
+                let code = format!("{helper}.{method}({})", arguments.join(", "));
+                if method == "len" {
+                    self.length(code)
+                } else {
+                    (code, ATOM)
+                }
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    // The type `parse` reads, from its turbofish or the annotation of the `let`
+    fn parsed_type(&self, generics: &[Type]) -> Option<Type> {
+        match (generics.first(), &self.expected) {
+            (Some(ty), _) => Some(ty.clone()),
+            (None, Some(Type::Named { name, generics })) if name == "Result" => {
+                generics.first().cloned()
+            }
+            (None, Some(ty)) => Some(ty.clone()),
+            (None, None) => None,
+        }
+    }
+
+    // The primitive `parse` reads, which the `$str` runtime knows how to parse
+    fn parse_target(&self, generics: &[Type]) -> Option<String> {
+        let ty = self.parsed_type(generics)?;
+        let name = ty.name()?;
+        let primitive = matches!(name, "f32" | "f64" | "bool" | "char" | "String");
+        (primitive || integer_range(name).is_some()).then(|| self.runtime_type(name).to_string())
+    }
+
+    // The formatting macros write straight to the console, `write!` appends to a
+    // `String` or goes through the `write_str` of a formatter
+    fn macro_call(&mut self, name: &str, input: &[Node]) -> Result<(String, u8), Box<dyn Error>> {
+        let code = match name {
+            "println" if input.is_empty() => "console.log()".to_string(),
+            "println" => format!("console.log({})", self.format(input, false)?),
+            "print" => format!("process.stdout.write({})", self.format(input, false)?),
+            "eprintln" => format!("console.error({})", self.format(input, false)?),
+            "eprint" => format!("process.stderr.write({})", self.format(input, false)?),
+            "format" => self.format(input, false)?,
+            "vec" => match input {
+                [repeat @ Node::ArrayRepeat { .. }] => return self.expression(repeat),
+                _ => return self.expression(&Node::ArrayLiteral(input.to_vec())),
+            },
+            "panic" if input.is_empty() => {
+                format!("{}(\"explicit panic\")", self.runtime("$panic"))
+            }
+            "panic" => format!("{}({})", self.runtime("$panic"), self.format(input, false)?),
+            "write" | "writeln" => {
+                let Some((destination, input)) = input.split_first() else {
+                    return Err(format!("Code generator Macro: {name}! needs a destination").into());
+                };
+                let text = self.format(input, name == "writeln")?;
+                if self.type_of(destination).name() == Some("String") {
+                    let destination = wrap(self.expression(destination)?, ATOM);
+                    self.prelude("Result");
+                    format!("({destination}+={text}, Result.Ok())")
+                } else {
+                    format!("{}.write_str({text})", self.receiver(destination)?)
+                }
+            }
+            _ => return Err(format!("Code generator Macro: Unknown macro {name}!").into()),
+        };
+        Ok((code, ATOM))
+    }
+
+    // Builds the string of a format string and its arguments. Every argument is
+    // evaluated once in order, the types pick how numbers and options are printed
+    fn format(&mut self, input: &[Node], newline: bool) -> Result<String, Box<dyn Error>> {
+        let end = if newline { "\n" } else { "" };
+        let (template, input) = match input.split_first() {
+            Some((Node::StringLiteral(template), input)) => (template, input),
+            None => return Ok(js_string(end)),
+            Some((node, _)) => {
+                return Err(format!(
+                    "Code generator Format: Expected a string literal got: {:?}",
+                    node
+                )
+                .into())
+            }
+        };
+        let pieces = format_pieces(template)?;
+        let mut arguments: Vec<Node> = Vec::new();
+        let mut names: Vec<Option<String>> = Vec::new();
+        for node in input {
+            match node {
+                Node::Assignment { target, value } => match target.as_ref() {
+                    Node::VeriableCall(name) => {
+                        arguments.push(value.as_ref().clone());
+                        names.push(Some(name.to_string()));
+                    }
+                    _ => return Err("Code generator Format: Expected a named argument".into()),
+                },
+                _ => {
+                    arguments.push(node.clone());
+                    names.push(None);
+                }
+            }
+        }
+        let positional = names.iter().take_while(|name| name.is_none()).count();
+        // Captured names are arguments the format string brings along
+        let captured = arguments.len();
+        for name in format_captures(&[&[Node::StringLiteral(template.to_string())], input].concat())
+        {
+            arguments.push(Node::VeriableCall(name.clone()));
+            names.push(Some(name));
+        }
+        let find = |argument: &Argument| match argument {
+            Argument::Index(n) if *n < positional => Ok(*n),
+            Argument::Index(n) => Err(format!(
+                "Code generator Format: Invalid reference to positional argument {n}"
+            )),
+            Argument::Name(name) => Ok(names
+                .iter()
+                .position(|n| n.as_deref() == Some(name.as_str()))
+                .unwrap_or_default()),
+        };
+        // The argument index of every value, width and precision
+        let mut next = 0;
+        let mut references = Vec::new();
+        for piece in &pieces {
+            if let Piece::Placeholder(placeholder) = piece {
+                let mut take = || {
+                    next += 1;
+                    find(&Argument::Index(next - 1))
+                };
+                let mut count = |count: &Option<Count>| match count {
+                    Some(Count::Argument(argument)) => find(argument).map(Some),
+                    Some(Count::Next) => take().map(Some),
+                    _ => Ok(None),
+                };
+                let width = count(&placeholder.width)?;
+                let precision = count(&placeholder.precision)?;
+                let value = match &placeholder.argument {
+                    Some(argument) => find(argument)?,
+                    None => take()?,
+                };
+                references.push((value, width, precision));
+            }
+        }
+        let mut uses = vec![0; arguments.len()];
+        for (value, width, precision) in &references {
+            for n in [Some(*value), *width, *precision].into_iter().flatten() {
+                uses[n] += 1;
+            }
+        }
+        if let Some(n) = uses[..captured].iter().position(|&uses| uses == 0) {
+            return Err(format!("Code generator Format: Argument {n} is never used").into());
+        }
+        let mut codes = Vec::new();
+        for (argument, uses) in arguments.iter().zip(&uses) {
+            let code = self.expression(argument)?;
+            let literal = matches!(
+                argument,
+                Node::NumberLiteral(_) | Node::StringLiteral(_) | Node::CharLiteral(_)
+            );
+            if *uses > 1 && !literal && !is_identifier(&code.0) {
+                let name = self.temporary("f");
+                self.hoisted.push(format!("const {name}={};\n", code.0));
+                codes.push(name);
+            } else {
+                codes.push(code.0);
+            }
+        }
+        let mut text = String::new();
+        let mut raw = String::new();
+        let mut interpolated = false;
+        let mut references = references.into_iter();
+        for piece in pieces {
+            let placeholder = match piece {
+                Piece::Text(piece) => {
+                    text.push_str(&js_template(&piece));
+                    raw.push_str(&piece);
+                    continue;
+                }
+                Piece::Placeholder(placeholder) => placeholder,
+            };
+            let Some((value, width, precision)) = references.next() else {
+                break;
+            };
+            let ty = self.type_of(&arguments[value]);
+            let mut spec = Vec::new();
+            if let Some(fill) = placeholder.fill {
+                spec.push(format!("fill:{}", js_string(&fill.to_string())));
+            }
+            if let Some(align) = placeholder.align {
+                spec.push(format!("align:\"{align}\""));
+            }
+            for (flag, set) in [
+                ("sign", placeholder.sign),
+                ("alternate", placeholder.alternate),
+                ("zero", placeholder.zero),
+            ] {
+                if set {
+                    spec.push(format!("{flag}:true"));
+                }
+            }
+            for (key, count, argument) in [
+                ("width", &placeholder.width, width),
+                ("precision", &placeholder.precision, precision),
+            ] {
+                match (count, argument) {
+                    (_, Some(n)) => spec.push(format!("{key}:{}", codes[n])),
+                    (Some(Count::Literal(n)), None) => spec.push(format!("{key}:{n}")),
+                    _ => {}
+                }
+            }
+            let kind = placeholder.kind.as_str();
+            if !matches!(kind, "" | "?") {
+                spec.push(format!("type:\"{kind}\""));
+            }
+            let name = ty.name().unwrap_or_default();
+            let plain =
+                matches!(name, "str" | "String" | "bool" | "char") || integer_range(name).is_some();
+            let code = &codes[value];
+            if kind.is_empty() && spec.is_empty() && plain {
+                // Literals are written into the text
+                if let Node::StringLiteral(literal)
+                | Node::CharLiteral(literal)
+                | Node::NumberLiteral(literal) = &arguments[value]
+                {
+                    text.push_str(&js_template(literal));
+                    raw.push_str(literal);
+                    continue;
+                }
+                text.push_str(&format!("${{{code}}}"));
+                interpolated = true;
+                continue;
+            }
+            let helper = if kind.ends_with('?') {
+                self.runtime("$debug")
+            } else {
+                self.runtime("$display")
+            };
+            // Integers only need their type for the two's complement of negative numbers
+            let descriptor = match (integer_range(name), kind) {
+                (Some(_), "x" | "X" | "o" | "b" | "x?" | "X?") => Some(format!("\"{name}\"")),
+                _ => self.descriptor(&ty),
+            };
+            let mut call = vec![code.to_string()];
+            if descriptor.is_some() || !spec.is_empty() {
+                call.push(descriptor.unwrap_or_else(|| "null".to_string()));
+            }
+            if !spec.is_empty() {
+                call.push(format!("{{{}}}", spec.join(", ")));
+            }
+            text.push_str(&format!("${{{helper}({})}}", call.join(", ")));
+            interpolated = true;
+        }
+        if interpolated {
+            Ok(format!("`{text}{}`", js_template(end)))
+        } else {
+            Ok(js_string(&(raw + end)))
+        }
+    }
+
+    // What `$debug` and `$display` need to know about a type that the values do not
+    // show, like floats that hold whole numbers and which arrays are tuples
+    fn descriptor(&self, ty: &Type) -> Option<String> {
+        let generic = |n: usize| match ty {
+            Type::Named { generics, .. } => generics.get(n).and_then(|ty| self.descriptor(ty)),
+            _ => None,
+        };
+        match ty {
+            Type::Named { name, .. } => match name.as_str() {
+                "f32" | "f64" | "char" => Some(format!("\"{name}\"")),
+                "Vec" | "VecDeque" | "HashSet" | "BTreeSet" => {
+                    generic(0).map(|element| format!("[{element}]"))
+                }
+                "HashMap" | "BTreeMap" if generic(0).is_some() || generic(1).is_some() => {
+                    let [key, value] =
+                        [generic(0), generic(1)].map(|d| d.unwrap_or_else(|| "null".to_string()));
+                    Some(format!("{{map:[{key}, {value}]}}"))
+                }
+                "Box" | "Rc" | "RefCell" => generic(0),
+                "Option" if self.nullable => Some(format!(
+                    "{{option:{}}}",
+                    generic(0).unwrap_or_else(|| "null".to_string())
+                )),
+                "Option" => generic(0).map(|value| format!("{{Some:{{0:{value}}}}}")),
+                "Result" => {
+                    let variants: Vec<String> = [("Ok", generic(0)), ("Err", generic(1))]
+                        .into_iter()
+                        .filter_map(|(variant, value)| Some(format!("{variant}:{{0:{}}}", value?)))
+                        .collect();
+                    (!variants.is_empty()).then(|| format!("{{{}}}", variants.join(", ")))
+                }
+                _ => None,
+            },
+            Type::Tuple(types) if !types.is_empty() => {
+                let types: Vec<String> = types
+                    .iter()
+                    .map(|ty| self.descriptor(ty).unwrap_or_else(|| "null".to_string()))
+                    .collect();
+                Some(format!("{{tuple:[{}]}}", types.join(", ")))
+            }
+            Type::Array(element, _) => self
+                .descriptor(element)
+                .map(|element| format!("[{element}]")),
+            _ => None,
+        }
+    }
+
+    // `static $types` of a struct or enum class, for the fields `$debug` can not tell apart
+    fn field_descriptors(&self, fields: &Fields) -> Option<String> {
+        let fields: Vec<String> = match fields {
+            Fields::Unit => Vec::new(),
+            Fields::Tuple(types) => types
+                .iter()
+                .enumerate()
+                .filter_map(|(n, ty)| Some(format!("{n}:{}", self.descriptor(ty)?)))
+                .collect(),
+            Fields::Named(fields) => fields
+                .iter()
+                .filter_map(|(name, ty)| Some(format!("{name}:{}", self.descriptor(ty)?)))
+                .collect(),
+        };
+        (!fields.is_empty()).then(|| format!("{{{}}}", fields.join(", ")))
+    }
+
+    fn try_operator(&mut self, expression: &Node) -> Result<String, Box<dyn Error>> {
+        let ty = self.type_of(expression);
+        let value = wrap(self.expression(expression)?, ASSIGNMENT);
+        let name = self.temporary("t");
+        let (failed, unwrapped) = match (ty.name(), self.nullable) {
+            (Some("Option"), true) => (format!("{name}===null"), name.clone()),
+            (Some("Option"), false) => (format!("{name}.$tag===\"None\""), format!("{name}[0]")),
+            (Some("Result"), _) => (format!("{name}.$tag===\"Err\""), format!("{name}[0]")),
+            (_, true) => (
+                format!("{name}===null||{name}.$tag===\"Err\""),
+                format!("({name}.$tag===\"Ok\"?{name}[0]:{name})"),
+            ),
+            (_, false) => (
+                format!("{name}.$tag===\"None\"||{name}.$tag===\"Err\""),
+                format!("{name}[0]"),
+            ),
+        };
+        self.hoisted.push(format!(
+            "const {name}={value};\nif ({failed}){{\nreturn {name};\n}}\n"
+        ));
+        Ok(unwrapped)
+    }
+
+    // Closures become arrow functions, `move` closures get the captured values
+    // passed in so later changes to the variables are not seen
+    fn closure(
+        &mut self,
+        input: &[(Pattern, Option<Type>)],
+        body: &Node,
+        capture: bool,
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        let mut captured = Vec::new();
+        if capture {
+            let mut names = Vec::new();
+            identifiers(body, &mut names);
+            for name in names {
+                if let Some(local) = self.local(&name) {
+                    if local.js != "this" && !captured.iter().any(|(_, js, _)| *js == local.js) {
+                        // A cell is a moved `&mut`, which stays shared
+                        let copy = local.cell.is_none() && self.is_copy(&local.ty);
+                        captured.push((name, local.js.clone(), copy));
+                    }
+                }
+            }
+        }
+
+        self.scopes.push(HashMap::new());
+        let mut parameters = Vec::new();
+        let mut bindings = Vec::new();
+        for (pattern, ty) in input {
+            let ty = ty.clone().unwrap_or(Type::Unknown);
+            match pattern {
+                Pattern::Binding {
+                    name,
+                    pattern: None,
+                } => parameters.push(self.declare(name, ty)),
+                _ => {
+                    // Destructured at the start of the body
+                    let name = self.temporary("p");
+                    bindings.extend(self.pattern_bindings(pattern, &name, &ty, false)?);
+                    parameters.push(name);
+                }
+            }
+        }
+        let outer_loops = std::mem::take(&mut self.loops);
+        let outer_output = self.output.take();
+        let body = self.arm_body(&bindings, body, &Target::Return);
+        self.output = outer_output;
+        self.loops = outer_loops;
+        self.scopes.pop();
+        let body = body?;
+
+        let parameters = parameters.join(", ");
+        let mut code = match body.strip_prefix("return ") {
+            // A single returned expression needs no block
+            Some(value) if body.matches('\n').count() == 1 => {
+                format!("({parameters})=>{}", value.trim_end_matches(";\n"))
+            }
+            _ => format!("({parameters})=>{{\n{body}}}"),
+        };
+        if !captured.is_empty() {
+            let mut values = Vec::new();
+            for (name, js, copy) in &captured {
+                // `Copy` values are copied into the closure in Rust but shared in JS
+                if *copy {
+                    let value = self.copied(&Node::VeriableCall(name.clone()), true)?;
+                    values.push(wrap(value, ASSIGNMENT));
+                } else {
+                    values.push(js.to_string());
+                }
+            }
+            let names: Vec<&str> = captured.iter().map(|(_, js, _)| js.as_str()).collect();
+            code = format!("(({})=>{code})({})", names.join(", "), values.join(", "));
+            return Ok((code, ATOM));
+        }
+        Ok((code, ASSIGNMENT))
+    }
+
+    // Methods named like a field of their type would be hidden by the field in JS
+    fn method_name(&self, ty: Option<&str>, method: &str) -> String {
+        let renamed = match ty.filter(|ty| self.impls.contains_key(*ty)) {
+            Some(ty) => self
+                .renamed_methods
+                .get(ty)
+                .is_some_and(|methods| methods.iter().any(|m| m == method)),
+            None => self
+                .renamed_methods
+                .values()
+                .any(|methods| methods.iter().any(|m| m == method)),
+        };
+        if renamed {
+            format!("{method}$")
+        } else {
+            method.to_string()
+        }
+    }
+
+    // Replaces `Self` in a path and turns it into JS member accesses
+    fn path(&self, name: &str) -> String {
+        let name = match (name.strip_prefix("Self"), &self.current_impl) {
+            (Some(rest), Some(current)) if rest.is_empty() || rest.starts_with("::") => {
+                format!("{current}{rest}")
+            }
+            // The variants of the prelude enums are used without their enum
+            _ if matches!(name, "Some" | "None") => format!("Option::{name}"),
+            _ if matches!(name, "Ok" | "Err") => format!("Result::{name}"),
+            _ => name.to_string(),
+        };
+        name.replace("::", ".")
+    }
+
+    fn resolve(&self, name: &str) -> String {
+        self.path(name).replace('.', "::")
+    }
+
+    // Generates an expression together with the precedence of the generated code
+    fn expression(&mut self, node: &Node) -> Result<(String, u8), Box<dyn Error>> {
+        Ok(match node {
+            Node::NumberLiteral(n) if self.bigint_literals && !is_float_literal(n) => {
+                (format!("{n}n"), ATOM)
+            }
+            Node::NumberLiteral(n) => (n.to_string(), ATOM),
+            Node::StringLiteral(s) | Node::CharLiteral(s) => (js_string(s), ATOM),
+            Node::VeriableCall(c) => {
+                if let Some(local) = self.local(c) {
+                    return Ok((local.js.clone(), ATOM));
+                }
+                let name = self.resolve(c);
+                if let Some(Fields::Unit) = self.structs.get(&name) {
+                    return Ok((format!("new {name}({{}})"), ATOM));
+                }
+                if self.is_nullable(&name) {
+                    return Ok(("null".to_string(), ATOM));
+                }
+                if let Some((code, _)) = self.numeric_constant(&name) {
+                    let precedence = if code.starts_with('-') { UNARY } else { ATOM };
+                    return Ok((code, precedence));
+                }
+                // `char::is_whitespace` passed as a function
+                if let Some(class) = name.strip_prefix("char::").and_then(char_class) {
+                    return Ok((format!("(char)=>{class}.test(char)"), ASSIGNMENT));
+                }
+                // `Vec::new` passed as a function, as in `or_insert_with(Vec::new)`
+                if matches!(name.as_str(), "Vec::new" | "String::new")
+                    || is_collection(&name) && name.ends_with("::new")
+                {
+                    let call = Node::FunctionCall {
+                        name: name.clone(),
+                        generics: Vec::new(),
+                        input: Vec::new(),
+                    };
+                    return Ok((format!("()=>{}", self.expression(&call)?.0), ASSIGNMENT));
+                }
+                if name == "String::from" {
+                    return Ok(("(value)=>value".to_string(), ASSIGNMENT));
+                }
+                self.prelude(&name);
+                (self.path(c), ATOM)
+            }
+            Node::FunctionCall { name, .. }
+                if matches!(
+                    self.resolve(name).as_str(),
+                    "Vec::new" | "Vec::with_capacity"
+                ) =>
+            {
+                ("[]".to_string(), ATOM)
+            }
+            Node::FunctionCall { name, input, .. } if is_collection(&self.resolve(name)) => {
+                self.collection(name, input)?
+            }
+            Node::FunctionCall { name, input, .. }
+                if self.resolve(name).starts_with("String::") =>
+            {
+                match (self.resolve(name).as_str(), &input[..]) {
+                    ("String::new" | "String::with_capacity", _) => ("\"\"".to_string(), ATOM),
+                    ("String::from", [value]) => self.expression(value)?,
+                    _ => {
+                        return Err(format!("Code generator String: Unknown function {name}").into())
+                    }
+                }
+            }
+            Node::FunctionCall { name, input, .. } if name.ends_with("::from_str_radix") => {
+                let [text, radix] = &input[..] else {
+                    return Err(format!("Code generator {name}: Expected two arguments").into());
+                };
+                let owner = name.trim_end_matches("::from_str_radix");
+                let ty = self.runtime_type(owner).to_string();
+                self.prelude("Result");
+                let helper = self.runtime("$str");
+                let text = wrap(self.expression(text)?, ASSIGNMENT);
+                let radix = wrap(self.expression(radix)?, ASSIGNMENT);
+                (format!("{helper}.parse({text}, \"{ty}\", {radix})"), ATOM)
+            }
+            // Boxes and reference counts are the value itself
+            Node::FunctionCall { name, input, .. }
+                if matches!(name.as_str(), "Box::new" | "Rc::new") =>
+            {
+                match &input[..] {
+                    [value] => self.expression(value)?,
+                    _ => return Err(format!("Code generator {name}: Expected one argument").into()),
+                }
+            }
+            Node::FunctionCall { name, input, .. } if name == "Default::default" => {
+                match self.expected.clone() {
+                    Some(ty) => (self.default_value(&ty)?, ATOM),
+                    None => {
+                        return Err("Code generator Default: Can not infer the type of Default::default(), annotate it".into())
+                    }
+                }
+            }
+            Node::FunctionCall {
+                name,
+                generics,
+                input,
+            } => {
+                let resolved = self.resolve(name);
+                // `Trait::method(value, ..)` is `value.method(..)`
+                if let Some((owner, method)) = resolved.rsplit_once("::") {
+                    let takes_self = matches!(self.find_method(owner, method),
+                        Some(Node::Function { input, .. }) if input.first().is_some_and(|p| p.name == "self"));
+                    if let (true, Some((value, rest))) = (takes_self, input.split_first()) {
+                        let receiver = self.call_receiver(&resolved, value)?;
+                        let arguments = self.call_arguments(&resolved, rest)?;
+                        let method = self.method_name(Some(owner), method);
+                        return Ok((format!("{receiver}.{method}({arguments})"), ATOM));
+                    }
+                }
+                if let Some(Fields::Tuple(types)) = self.structs.get(&resolved).cloned() {
+                    let mut fields = Vec::new();
+                    for (n, value) in input.iter().enumerate() {
+                        let ty = types.get(n).cloned().unwrap_or(Type::Unknown);
+                        fields.push(format!(
+                            "{n}:{}",
+                            wrap(self.stored(value, &ty, true)?, ASSIGNMENT)
+                        ));
+                    }
+                    return Ok((format!("new {resolved}({{{}}})", fields.join(", ")), ATOM));
+                }
+                let function = match self.local(name) {
+                    Some(local) => local.js.clone(),
+                    None => self.path(name),
+                };
+                // A nullable `Some` is just its value
+                if let (true, [value]) = (self.is_nullable(&resolved), &input[..]) {
+                    return self.expression(value);
+                }
+                self.prelude(&resolved);
+                let mut arguments = self.witness_arguments(&resolved, generics, input)?;
+                if !arguments.is_empty() && !input.is_empty() {
+                    arguments.push_str(", ");
+                }
+                arguments.push_str(&self.call_arguments(&resolved, input)?);
+                (format!("{function}({arguments})"), ATOM)
+            }
+            Node::Call { callee, input } => {
+                let callee = wrap(self.expression(callee)?, ATOM);
+                let arguments = self.call_arguments("", input)?;
+                (format!("{callee}({arguments})"), ATOM)
+            }
+            Node::CompoundAssignment {
+                target,
+                operator,
+                value,
+            } => self.compound_assignment(target, *operator, value)?,
+            Node::Assignment { target, value } if matches!(target.as_ref(), Node::Deref(_)) => {
+                let Node::Deref(target) = target.as_ref() else {
+                    unreachable!()
+                };
+                self.written_reference(target)?;
+                self.expression(&Node::Assignment {
+                    target: target.clone(),
+                    value: value.clone(),
+                })?
+            }
+            // `*map.entry(key).or_insert(value) = other` overwrites the entry
+            Node::Assignment { target, value }
+                if matches!(target.as_ref(), Node::MethodCall { expression, method, .. }
+                    if self.type_of(expression).name() == Some("Entry")
+                        && matches!(method.as_str(), "or_insert" | "or_insert_with" | "or_default")) =>
+            {
+                let Node::MethodCall { expression, .. } = target.as_ref() else {
+                    unreachable!()
+                };
+                let entry = self.receiver(expression)?;
+                let value = wrap(self.expression(value)?, ASSIGNMENT);
+                (format!("{entry}.set({value})"), ATOM)
+            }
+            Node::Assignment { target, value }
+                if matches!(target.as_ref(), Node::MethodCall { .. }) =>
+            {
+                let target = self.method_place(target)?;
+                self.expression(&Node::Assignment {
+                    target: Box::new(target),
+                    value: value.clone(),
+                })?
+            }
+            Node::Assignment { target, value }
+                if matches!(target.as_ref(), Node::Index { expression, .. }
+                    if self.overloaded(&self.type_of(expression), "IndexMut", "index_mut").is_some()) =>
+            {
+                let Node::Index { expression, index } = target.as_ref() else {
+                    unreachable!()
+                };
+                let owner = self
+                    .type_of(expression)
+                    .name()
+                    .unwrap_or_default()
+                    .to_string();
+                let receiver = self.receiver(expression)?;
+                let index = self
+                    .call_arguments(&format!("{owner}::index_mut"), std::slice::from_ref(index))?;
+                let value = wrap(self.expression(value)?, ASSIGNMENT);
+                (format!("{receiver}.$set_index({index}, {value})"), ATOM)
+            }
+            Node::Assignment { target, value } => {
+                let changes = self.changed(value)
+                    || place_root(target).is_none_or(|root| {
+                        !matches!(target.as_ref(), Node::VeriableCall(_))
+                            || self.changes.mutated.iter().any(|name| name == root)
+                            || self.changes.returned.iter().any(|name| name == root)
+                    });
+                let ty = self.type_of(target);
+                if let Some((values, index)) = self.checked_index(target)? {
+                    let helper = self.runtime("$index");
+                    let value = wrap(self.stored(value, &ty, changes)?, ASSIGNMENT);
+                    return Ok((format!("{helper}.set({values}, {index}, {value})"), ATOM));
+                }
+                let target = wrap(self.expression(target)?, ATOM);
+                let value = wrap(self.stored(value, &ty, changes)?, ASSIGNMENT);
+                (format!("{target}={value}"), ASSIGNMENT)
+            }
+            // Comparisons of `PartialOrd` types go through `partial_cmp`, a `None` makes them false
+            Node::Binary {
+                left,
+                operator,
+                right,
+            } if operator_trait(*operator).is_some_and(|(trait_name, method)| {
+                self.overloaded(&self.type_of(left), trait_name, method)
+                    .is_some()
+            }) =>
+            {
+                let (trait_name, trait_method) = operator_trait(*operator).unwrap_or_default();
+                let left_type = self.type_of(left);
+                let method = self
+                    .overloaded(&left_type, trait_name, trait_method)
+                    .unwrap_or_default();
+                if trait_name != "PartialOrd" {
+                    let owner = left_type.name().unwrap_or_default();
+                    let path = format!("{owner}::{trait_method}");
+                    let left = self.receiver(left)?;
+                    let right = self.call_arguments(&path, std::slice::from_ref(right))?;
+                    return Ok((format!("{left}.{method}({right})"), ATOM));
+                }
+                self.prelude("Option");
+                let helper = self.runtime("$compare");
+                let left = wrap(self.expression(left)?, ASSIGNMENT);
+                let right = wrap(self.expression(right)?, ASSIGNMENT);
+                let precedence = operator_precedence(*operator);
+                (
+                    format!("{helper}({left}, {right}){}0", operator_js(*operator)),
+                    precedence,
+                )
+            }
+            Node::Binary {
+                left,
+                operator: operator @ (Operator::Equal | Operator::NotEqual),
+                right,
+            } if self.is_structural(&self.type_of(left))
+                || self.is_structural(&self.type_of(right)) =>
+            {
+                let helper = self.runtime("$eq");
+                let left = wrap(self.expression(left)?, ASSIGNMENT);
+                let right = wrap(self.expression(right)?, ASSIGNMENT);
+                let code = format!("{helper}({left}, {right})");
+                if *operator == Operator::Equal {
+                    (code, ATOM)
+                } else {
+                    (format!("!{code}"), UNARY)
+                }
+            }
+            Node::Binary {
+                left,
+                operator:
+                    operator @ (Operator::Add
+                    | Operator::Subtract
+                    | Operator::Multiply
+                    | Operator::Divide
+                    | Operator::Modulo),
+                right,
+            } if is_integer(&self.type_of(node)) => {
+                let ty = self.integer_type(node);
+                self.integer_arithmetic(left, *operator, right, ty.as_deref(), self.release)?
+            }
+            Node::Binary {
+                left,
+                operator:
+                    operator @ (Operator::BitAnd
+                    | Operator::BitOr
+                    | Operator::BitXor
+                    | Operator::ShiftLeft
+                    | Operator::ShiftRight),
+                right,
+            } => self.bitwise(left, *operator, right)?,
+            Node::Binary {
+                left,
+                operator:
+                    operator @ (Operator::Add
+                    | Operator::Subtract
+                    | Operator::Multiply
+                    | Operator::Divide
+                    | Operator::Modulo),
+                right,
+            } if self.is_f32(node) => {
+                let code = self.binary(left, *operator, right)?;
+                self.rounded(node, code)
+            }
+            Node::Binary {
+                left,
+                operator,
+                right,
+            } => self.binary(left, *operator, right)?,
+            Node::Unary {
+                operator,
+                expression,
+            } if {
+                let (trait_name, method) = unary_trait(*operator);
+                self.overloaded(&self.type_of(expression), trait_name, method)
+                    .is_some()
+            } =>
+            {
+                let (trait_name, method) = unary_trait(*operator);
+                let method = self
+                    .overloaded(&self.type_of(expression), trait_name, method)
+                    .unwrap_or_default();
+                (format!("{}.{method}()", self.receiver(expression)?), ATOM)
+            }
+            // `-i32::MIN` overflows
+            Node::Unary {
+                operator: Symbol::Minus,
+                expression,
+            } if !is_literal_cast(expression)
+                && self.integer_type(expression).is_some_and(|ty| {
+                    matches!(ty.as_str(), "i8" | "i16" | "i32")
+                        || ty.starts_with('i') && self.is_big(&Type::named(&ty))
+                }) =>
+            {
+                let ty = self.integer_type(expression).unwrap_or_default();
+                let code = self.negated(expression)?;
+                self.overflowed(code, &ty, "negate", self.release)
+            }
+            Node::Unary {
+                operator: Symbol::Minus,
+                expression,
+            } => self.negated(expression)?,
+            // `!` on an integer flips its bits
+            Node::Unary { expression, .. } if is_integer(&self.type_of(expression)) => {
+                let ty = self
+                    .integer_type(expression)
+                    .unwrap_or_else(|| "i32".to_string());
+                let value = self.expression(expression)?;
+                match self.runtime_type(&ty) {
+                    "usize" => (format!("Number.MAX_SAFE_INTEGER-{}", wrap(value, 13)), 12),
+                    "isize" => (format!("-1-{}", wrap(value, 13)), 12),
+                    ty => {
+                        let code = (format!("~{}", wrap(value, UNARY)), UNARY);
+                        if ty.starts_with('i') {
+                            code
+                        } else {
+                            self.overflowed(code, ty, "", true)
+                        }
+                    }
+                }
+            }
+            Node::Unary { expression, .. } => {
+                let mut code = wrap(self.expression(expression)?, UNARY);
+                if code.starts_with('-') || code.starts_with('!') {
+                    code = format!("({code})");
+                }
+                (format!("!{code}"), UNARY)
+            }
+            Node::MutableReference(expression) => self.expression(expression)?,
+            // A cell is read through its local already
+            Node::Deref(expression) => self.expression(expression)?,
+            Node::Cast { expression, ty } => {
+                // Fieldless enums carry their discriminant
+                if self.is_fieldless_enum(&self.type_of(expression)) {
+                    let code = self.expression(expression)?;
+                    (format!("{}.$disc", wrap(code, ATOM)), ATOM)
+                } else {
+                    self.cast(expression, ty)?
+                }
+            }
+            Node::FieldAccess { expression, field } => {
+                // Tuples are arrays and tuple structs are keyed by position
+                if field.parse::<usize>().is_ok() {
+                    (format!("{}[{field}]", self.receiver(expression)?), ATOM)
+                } else {
+                    (format!("{}.{field}", self.receiver(expression)?), ATOM)
+                }
+            }
+            Node::MethodCall {
+                expression,
+                method,
+                generics,
+                input,
+            } => {
+                let ty = pointee(self.type_of(expression));
+                if is_vec(&ty) {
+                    if let Some(code) = self.vec_method(expression, method, input)? {
+                        return Ok(code);
+                    }
+                }
+                if is_integer(&ty) {
+                    if let Some(code) = self.integer_method(expression, method, input)? {
+                        return Ok(code);
+                    }
+                }
+                if is_float(&ty) {
+                    if let Some(code) = self.float_method(expression, method, input)? {
+                        return Ok(code);
+                    }
+                }
+                if ty.name() == Some("char") {
+                    if let Some(code) = self.char_method(expression, method, input)? {
+                        return Ok(code);
+                    }
+                }
+                if is_string(&ty) {
+                    if let Some(code) = self.string_method(expression, method, generics, input)? {
+                        return Ok(code);
+                    }
+                }
+                if is_map(&ty) || is_set(&ty) {
+                    if let Some(code) =
+                        self.collection_method(expression, method, input, is_map(&ty))?
+                    {
+                        return Ok(code);
+                    }
+                }
+                if self.is_iterator_method(&ty, method) {
+                    return self.iterator_method(node, expression, method, generics, input);
+                }
+                // The debug builders get the descriptors of the values they print
+                if ty.name() == Some("DebugBuilder")
+                    && matches!(method.as_str(), "field" | "entry" | "entries")
+                {
+                    let types = match (method.as_str(), &input[..]) {
+                        ("entries", [values]) => {
+                            self.descriptor(&self.item_of(&self.type_of(values)))
+                        }
+                        ("entry", [key, value]) => {
+                            match (
+                                self.descriptor(&self.type_of(key)),
+                                self.descriptor(&self.type_of(value)),
+                            ) {
+                                (None, None) => None,
+                                (key, value) => Some(format!(
+                                    "[{}, {}]",
+                                    key.as_deref().unwrap_or("undefined"),
+                                    value.as_deref().unwrap_or("undefined")
+                                )),
+                            }
+                        }
+                        (_, [.., value]) => self.descriptor(&self.type_of(value)),
+                        _ => None,
+                    };
+                    let mut arguments = self.arguments(input)?;
+                    if let Some(types) = types {
+                        arguments.push_str(&format!(", {types}"));
+                    }
+                    return Ok((
+                        format!("{}.{method}({arguments})", self.receiver(expression)?),
+                        ATOM,
+                    ));
+                }
+                if ty.name() == Some("Entry") && method == "or_default" {
+                    let value = self.default_value(&generic_argument(&ty, 1))?;
+                    return Ok((
+                        format!("{}.or_insert({value})", self.receiver(expression)?),
+                        ATOM,
+                    ));
+                }
+                let user_type = !is_primitive(&ty)
+                    && ty.name().is_some_and(|name| {
+                        self.structs.contains_key(name) || self.impls.contains_key(name)
+                    });
+                let display = ty.name().is_some_and(|name| {
+                    self.implements(name, "Display") && !self.has_method(name, "to_string")
+                });
+                if method == "to_string" && (!user_type || display) {
+                    let name = ty.name().unwrap_or_default();
+                    if name == "char" {
+                        return self.expression(expression);
+                    }
+                    let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                    if name == "bool" || integer_range(name).is_some() {
+                        return Ok((format!("String({value})"), ATOM));
+                    }
+                    let helper = self.runtime("$display");
+                    return Ok(match self.descriptor(&ty) {
+                        Some(descriptor) => (format!("{helper}({value}, {descriptor})"), ATOM),
+                        None => (format!("{helper}({value})"), ATOM),
+                    });
+                }
+                if matches!(method.as_str(), "cmp" | "partial_cmp") && !user_type {
+                    let helper = if method == "cmp" {
+                        self.runtime("$cmp")
+                    } else {
+                        self.prelude("Option");
+                        // Defined next to `$cmp`
+                        self.runtime("$cmp");
+                        "$partial_cmp"
+                    };
+                    let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                    return Ok((
+                        format!("{helper}({value}, {})", self.arguments(input)?),
+                        ATOM,
+                    ));
+                }
+                // The std types have no `clone` method in JS, they are copied deeply and
+                // an `Rc` shares its value
+                if method == "clone" && input.is_empty() && !user_type {
+                    if is_primitive(&ty) || matches!(ty.name(), Some("Rc" | "Arc")) {
+                        return self.expression(expression);
+                    }
+                    let helper = self.runtime("$clone");
+                    let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                    return Ok((format!("{helper}({value})"), ATOM));
+                }
+                match (ty.name(), method.as_str()) {
+                    (Some("Option"), "ok_or" | "ok_or_else") => self.prelude("Result"),
+                    (Some("Result"), "ok" | "err") => self.prelude("Option"),
+                    _ => {}
+                }
+                // `take` leaves `None` in the place it takes the value from
+                if ty.name() == Some("Option") && method == "take" {
+                    let helper = if self.nullable {
+                        self.runtime("$Option")
+                    } else {
+                        self.prelude("Option");
+                        "Option"
+                    };
+                    let place = self.cell(&Node::MutableReference(expression.clone()))?;
+                    return Ok((format!("{helper}.take({place})"), ATOM));
+                }
+                // The runtime gets the default value of the payload type
+                if ty.name() == Some("Option") && method == "unwrap_or_default" {
+                    let value = self.default_value(&generic_argument(&ty, 0))?;
+                    if !self.nullable {
+                        let receiver = self.receiver(expression)?;
+                        return Ok((format!("{receiver}.unwrap_or_default({value})"), ATOM));
+                    }
+                    let helper = self.runtime("$Option");
+                    let option = wrap(self.expression(expression)?, ASSIGNMENT);
+                    return Ok((
+                        format!("{helper}.unwrap_or_default({option}, {value})"),
+                        ATOM,
+                    ));
+                }
+                // Closures given to `Option` and `Result` methods take the payload type
+                let payload = match (ty.name(), method.as_str()) {
+                    (Some("Option"), "map" | "and_then" | "filter" | "map_or")
+                    | (Some("Result"), "map" | "and_then") => Some(generic_argument(&ty, 0)),
+                    (Some("Result"), "map_err" | "or_else" | "unwrap_or_else") => {
+                        Some(generic_argument(&ty, 1))
+                    }
+                    _ => None,
+                };
+                if let Some(payload) = &payload {
+                    if self.nullable && ty.name() == Some("Option") {
+                        let helper = self.runtime("$Option");
+                        let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                        let mut arguments = vec![value];
+                        arguments.extend(self.iterator_arguments(method, input, payload)?);
+                        return Ok((format!("{helper}.{method}({})", arguments.join(", ")), ATOM));
+                    }
+                    let receiver = self.receiver(expression)?;
+                    let arguments = self.iterator_arguments(method, input, payload)?;
+                    return Ok((
+                        format!("{receiver}.{method}({})", arguments.join(", ")),
+                        ATOM,
+                    ));
+                }
+                if self.nullable && OPTION_METHODS.contains(&method.as_str()) {
+                    let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                    let mut input = self.arguments(input)?;
+                    if !input.is_empty() {
+                        input = format!(", {input}");
+                    }
+                    match ty.name() {
+                        Some("Option") => {
+                            let helper = self.runtime("$Option");
+                            return Ok((format!("{helper}.{method}({value}{input})"), ATOM));
+                        }
+                        // Only known at run time
+                        None => {
+                            let helper = self.runtime("$call");
+                            return Ok((format!("{helper}({value}, \"{method}\"{input})"), ATOM));
+                        }
+                        _ => {}
+                    }
+                }
+                // Plain JS arrays and strings would not have the method
+                let std_type = match &ty {
+                    Type::Array(..) => Some("slice"),
+                    ty if is_vec(ty) || is_string(ty) => ty.name(),
+                    _ => None,
+                };
+                if let Some(name) = std_type {
+                    if !self.has_method(name, method) {
+                        return Err(format!(
+                            "Code generator Method: {name}::{method} is not supported"
+                        )
+                        .into());
+                    }
+                }
+                let path = format!("{}::{method}", ty.name().unwrap_or_default());
+                let receiver = self.call_receiver(&path, expression)?;
+                let arguments = self.call_arguments(&path, input)?;
+                // Numbers and strings have the methods of their impls on a class
+                if let Some(name) = ty
+                    .name()
+                    .filter(|name| is_primitive(&ty) && self.find_method(name, method).is_some())
+                {
+                    let arguments = match arguments.as_str() {
+                        "" => receiver,
+                        arguments => format!("{receiver}, {arguments}"),
+                    };
+                    return Ok((format!("{name}.{method}.call({arguments})"), ATOM));
+                }
+                let method = self.method_name(ty.name(), method);
+                (format!("{receiver}.{method}({arguments})"), ATOM)
+            }
+            Node::Index { expression, index } => match index.as_ref() {
+                Node::Range {
+                    start,
+                    end,
+                    inclusive,
+                } => {
+                    let mut bounds = vec![match start {
+                        Some(start) => wrap(self.position(start)?, ASSIGNMENT),
+                        None => "0".to_string(),
+                    }];
+                    if let Some(end) = end {
+                        let end = self.position(end)?;
+                        bounds.push(if *inclusive {
+                            format!("{}+1", wrap(end, 12))
+                        } else {
+                            wrap(end, ASSIGNMENT)
+                        });
+                    }
+                    if is_string(&self.type_of(expression)) && !self.fast_strings {
+                        bounds.insert(0, wrap(self.expression(expression)?, ASSIGNMENT));
+                        let helper = self.runtime("$str");
+                        (format!("{helper}.slice({})", bounds.join(", ")), ATOM)
+                    } else {
+                        let receiver = self.receiver(expression)?;
+                        (format!("{receiver}.slice({})", bounds.join(", ")), ATOM)
+                    }
+                }
+                _ if is_map(&self.type_of(expression)) => {
+                    self.helper_call("$map", "index", expression, std::slice::from_ref(index))?
+                }
+                _ if self
+                    .overloaded(&self.type_of(expression), "Index", "index")
+                    .is_some() =>
+                {
+                    let method = self
+                        .overloaded(&self.type_of(expression), "Index", "index")
+                        .unwrap_or_default();
+                    let owner = self
+                        .type_of(expression)
+                        .name()
+                        .unwrap_or_default()
+                        .to_string();
+                    (
+                        format!(
+                            "{}.{method}({})",
+                            self.receiver(expression)?,
+                            self.call_arguments(
+                                &format!("{owner}::index"),
+                                std::slice::from_ref(index)
+                            )?
+                        ),
+                        ATOM,
+                    )
+                }
+                _ if self.type_of(expression).name() == Some("VecDeque") => (
+                    format!(
+                        "{}.$index({})",
+                        self.receiver(expression)?,
+                        self.position(index)?.0
+                    ),
+                    ATOM,
+                ),
+                // Out of bounds reads panic like Rust instead of giving `undefined`
+                _ => {
+                    let helper = self.runtime("$index");
+                    let receiver = wrap(self.expression(expression)?, ASSIGNMENT);
+                    let index = wrap(self.expression(index)?, ASSIGNMENT);
+                    (format!("{helper}.at({receiver}, {index})"), ATOM)
+                }
+            },
+            Node::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = match start {
+                    Some(start) => wrap(self.expression(start)?, ASSIGNMENT),
+                    None => "0".to_string(),
+                };
+                let end = match end {
+                    Some(end) if *inclusive => format!("{}+1", wrap(self.expression(end)?, 12)),
+                    Some(end) => wrap(self.expression(end)?, ASSIGNMENT),
+                    None => "Infinity".to_string(),
+                };
+                self.iterator_runtime();
+                (format!("$range({start}, {end})"), ATOM)
+            }
+            // `[2i64, 3, 4]`, the unsuffixed elements take the width of the suffixed one
+            Node::ArrayLiteral(elements)
+                if self.is_big(&element_type(&self.type_of(node)))
+                    && elements.iter().any(is_integer_literal) =>
+            {
+                let ty = self.type_of(node);
+                let mut values = Vec::new();
+                for element in elements {
+                    values.push(wrap(
+                        self.stored(element, &element_type(&ty), true)?,
+                        ASSIGNMENT,
+                    ));
+                }
+                (format!("[{}]", values.join(", ")), ATOM)
+            }
+            Node::ArrayLiteral(elements) | Node::TupleLiteral(elements) if !elements.is_empty() => {
+                (format!("[{}]", self.arguments(elements)?), ATOM)
+            }
+            Node::ArrayLiteral(_) => ("[]".to_string(), ATOM),
+            Node::TupleLiteral(_) => ("undefined".to_string(), ATOM),
+            Node::Closure {
+                input,
+                body,
+                capture,
+            } => self.closure(input, body, *capture)?,
+            Node::Try(expression) => (self.try_operator(expression)?, ATOM),
+            Node::Macro { name, input } => self.macro_call(name, input)?,
+            Node::ArrayRepeat { value, count } => {
+                let ty = self.type_of(value);
+                let fill = format!(
+                    "Array({}).fill({})",
+                    self.position(count)?.0,
+                    wrap(self.expression(value)?, ASSIGNMENT)
+                );
+                // Every element needs its own copy of values JS shares by reference
+                if is_copy(&ty) {
+                    (fill, ATOM)
+                } else {
+                    (format!("{fill}.map({})", self.runtime("$clone")), ATOM)
+                }
+            }
+            Node::StructLiteral { name, fields } => {
+                let resolved = self.resolve(name);
+                let types = match self.pattern_fields(&resolved) {
+                    Ok(Fields::Named(types)) => types,
+                    _ => Vec::new(),
+                };
+                let mut object = Vec::new();
+                for (field, value) in fields {
+                    let ty = types
+                        .iter()
+                        .find(|(name, _)| name == field)
+                        .map_or(Type::Unknown, |(_, ty)| ty.clone());
+                    object.push(format!(
+                        "{field}:{}",
+                        wrap(self.stored(value, &ty, true)?, ASSIGNMENT)
+                    ));
+                }
+                let object = object.join(", ");
+                if self.variant(&resolved).is_some() {
+                    (format!("{}({{{object}}})", self.path(name)), ATOM)
+                } else if self.structs.contains_key(&resolved) {
+                    (format!("new {resolved}({{{object}}})"), ATOM)
+                } else {
+                    return Err(format!("Code generator Struct: Unknown struct {name}").into());
+                }
+            }
+            _ if is_branching(node) => (self.hoist(node)?, ATOM),
+            _ => {
+                return Err(format!(
+                    "Code generator Expression: Expected expression got: {:?}",
+                    node
+                )
+                .into())
+            }
+        })
+    }
+
+    fn binary(
+        &mut self,
+        left: &Node,
+        operator: Operator,
+        right: &Node,
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        let precedence = operator_precedence(operator);
+        let left_code = wrap(self.operand(left, right)?, precedence);
+        let mut right_code = wrap(self.operand(right, left)?, precedence + 1);
+        // `a- -b` must not turn into a decrement
+        if matches!(operator, Operator::Add | Operator::Subtract)
+            && (right_code.starts_with('-') || right_code.starts_with('+'))
+        {
+            right_code = format!("({right_code})");
+        }
+        Ok((
+            format!("{left_code}{}{right_code}", operator_js(operator)),
+            precedence,
+        ))
+    }
+
+    // Integer `& | ^ << >>` at the width of their type. Shifted out bits are lost, shifting
+    // by the width or more panics unless overflow wraps, then only the low bits of it count
+    fn bitwise(
+        &mut self,
+        left: &Node,
+        operator: Operator,
+        right: &Node,
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        // On booleans they are the logical operators without short circuiting
+        if self.type_of(left).name() == Some("bool") {
+            if operator == Operator::BitXor {
+                return self.binary(left, Operator::NotEqual, right);
+            }
+            let code = wrap(self.binary(left, operator, right)?, ASSIGNMENT);
+            return Ok((format!("Boolean({code})"), ATOM));
+        }
+        let shift = matches!(operator, Operator::ShiftLeft | Operator::ShiftRight);
+        let ty = if shift {
+            self.integer_type(left)
+        } else {
+            self.integer_type(left).or_else(|| self.integer_type(right))
+        };
+        // Literals that become BigInts have no width to keep to
+        let Some(ty) = ty.or_else(|| (!self.bigint_literals).then(|| "i32".to_string())) else {
+            return self.binary(left, operator, right);
+        };
+        let big = self.is_big(&Type::named(&ty));
+        let ty = self.runtime_type(&ty).to_string();
+        if !shift {
+            let code = self.binary(left, operator, right)?;
+            return Ok(match ty.as_str() {
+                "u32" => (format!("{}>>>0", wrap(code, 12)), 11),
+                // Beyond 32 bits only BigInts have bitwise operators
+                "usize" | "isize" if !big => {
+                    let left = wrap(self.expression(left)?, ASSIGNMENT);
+                    let right = wrap(self.expression(right)?, ASSIGNMENT);
+                    let operator = operator_js(operator);
+                    (
+                        format!("Number(BigInt({left}){operator}BigInt({right}))"),
+                        ATOM,
+                    )
+                }
+                _ => code,
+            });
+        }
+        let (_, bits) = integer_width(&ty).unwrap_or((true, 32));
+        let literal = match right {
+            Node::NumberLiteral(n) => Some(n),
+            Node::Cast { expression, .. } => match expression.as_ref() {
+                Node::NumberLiteral(n) => Some(n),
+                _ => None,
+            },
+            _ => None,
+        };
+        let amount = match literal {
+            Some(n) if n.parse::<u32>().is_ok_and(|n| n < bits) => {
+                (if big { format!("{n}n") } else { n.to_string() }, ATOM)
+            }
+            _ => {
+                let mut amount = self.expression(right)?;
+                if self.release {
+                    if self.is_big(&self.type_of(right)) {
+                        amount = (format!("Number({})", wrap(amount, ASSIGNMENT)), ATOM);
+                    }
+                    // JS shifts of numbers already only use the low 5 bits
+                    if bits != 32 || big {
+                        amount = (format!("{}&{}", wrap(amount, 9), bits - 1), 8);
+                    }
+                } else {
+                    let helper = self.runtime("$int");
+                    let direction = if operator == Operator::ShiftLeft {
+                        "left"
+                    } else {
+                        "right"
+                    };
+                    amount = (
+                        format!(
+                            "{helper}.shift({}, {bits}, \"{direction}\")",
+                            wrap(amount, ASSIGNMENT)
+                        ),
+                        ATOM,
+                    );
+                }
+                if big {
+                    amount = (format!("BigInt({})", wrap(amount, ASSIGNMENT)), ATOM);
+                }
+                amount
+            }
+        };
+        let value = self.expression(left)?;
+        Ok(match (operator, ty.as_str()) {
+            // Pointer sized numbers are only exact up to 53 bits
+            (Operator::ShiftLeft, "usize" | "isize") if !big => {
+                (format!("{}*2**{}", wrap(value, 13), wrap(amount, ATOM)), 13)
+            }
+            (Operator::ShiftRight, "usize" | "isize") if !big => (
+                format!("Math.floor({}/2**{})", wrap(value, 13), wrap(amount, ATOM)),
+                ATOM,
+            ),
+            (Operator::ShiftLeft, "i32") => {
+                (format!("{}<<{}", wrap(value, 11), wrap(amount, 12)), 11)
+            }
+            (Operator::ShiftLeft, "u32") => (
+                format!("({}<<{})>>>0", wrap(value, 11), wrap(amount, 12)),
+                11,
+            ),
+            (Operator::ShiftLeft, ty) => {
+                let code = (format!("{}<<{}", wrap(value, 11), wrap(amount, 12)), 11);
+                self.overflowed(code, ty, "", true)
+            }
+            // Unsigned numbers shift in zeros, signed ones copies of the sign bit
+            (_, "u32") => (format!("{}>>>{}", wrap(value, 11), wrap(amount, 12)), 11),
+            _ => (format!("{}>>{}", wrap(value, 11), wrap(amount, 12)), 11),
+        })
+    }
+
+    // Converts between numbers and BigInts, a BigInt that does not fit its new type wraps
+    fn cast(&mut self, expression: &Node, ty: &Type) -> Result<(String, u8), Box<dyn Error>> {
+        if let (Node::NumberLiteral(n), true) = (expression, self.is_big(ty)) {
+            if !is_float_literal(n) {
+                return Ok((format!("{n}n"), ATOM));
+            }
+        }
+        let name = ty.name().unwrap_or_default();
+        // A literal already is whatever number it is cast to
+        if matches!(expression, Node::NumberLiteral(n) if !is_float_literal(n)) && name != "char" {
+            return self.expression(expression);
+        }
+        let mut from = self.type_of(expression);
+        let mut code = self.expression(expression)?;
+        // Floats saturate at the bounds of the integer and NaN becomes 0
+        if is_float(&from) && integer_width(name).is_some() {
+            let helper = self.runtime("$int");
+            let ty = self.runtime_type(name);
+            return Ok((
+                format!("{helper}.saturate({}, \"{ty}\")", wrap(code, ASSIGNMENT)),
+                ATOM,
+            ));
+        }
+        // Characters are their code point and booleans 0 or 1
+        match from.name() {
+            Some("char") if name != "char" => {
+                code = (format!("{}.codePointAt(0)", wrap(code, ATOM)), ATOM);
+                from = Type::named("u32");
+            }
+            Some("bool") if name != "bool" => {
+                code = (format!("Number({})", wrap(code, ASSIGNMENT)), ATOM);
+                from = Type::named("u8");
+            }
+            _ => {}
+        }
+        let fits = match (
+            integer_width(from.name().unwrap_or_default()),
+            integer_width(name),
+        ) {
+            (Some((from_signed, from_bits)), Some((signed, bits))) => {
+                from_signed == signed && from_bits <= bits
+                    || !from_signed && signed && from_bits < bits
+            }
+            _ => false,
+        };
+        let wrapped = |code: String| match integer_width(name) {
+            _ if fits => code,
+            Some((true, bits)) => format!("BigInt.asIntN({bits}, {code})"),
+            Some((false, bits)) => format!("BigInt.asUintN({bits}, {code})"),
+            None => code,
+        };
+        Ok(match (self.is_big(&from), self.is_big(ty)) {
+            (true, true) => (wrapped(wrap(code, ASSIGNMENT)), ATOM),
+            (true, false) => (format!("Number({})", wrapped(wrap(code, ASSIGNMENT))), ATOM),
+            (false, true) => (wrapped(format!("BigInt({})", wrap(code, ASSIGNMENT))), ATOM),
+            (false, false) => match name {
+                "char" => (
+                    format!("String.fromCodePoint({})", wrap(code, ASSIGNMENT)),
+                    ATOM,
+                ),
+                "f32" if from.name() != Some("f32") => {
+                    (format!("Math.fround({})", wrap(code, ASSIGNMENT)), ATOM)
+                }
+                _ if integer_width(name).is_some() && is_integer(&from) && !fits => {
+                    self.overflowed(code, name, "", true)
+                }
+                _ => code,
+            },
+        })
+    }
+
+    fn negated(&mut self, expression: &Node) -> Result<(String, u8), Box<dyn Error>> {
+        let mut code = wrap(self.expression(expression)?, UNARY);
+        if code.starts_with('-') || code.starts_with('!') {
+            code = format!("({code})");
+        }
+        Ok((format!("-{code}"), UNARY))
+    }
+
+    // The methods of the integer types, `None` for the ones without a JS counterpart
+    fn integer_method(
+        &mut self,
+        expression: &Node,
+        method: &str,
+        input: &[Node],
+    ) -> Result<Option<(String, u8)>, Box<dyn Error>> {
+        // The width matters here even when it was inferred, an exponent is always a `u32`
+        let ty = self
+            .integer_type(expression)
+            .or_else(|| {
+                input
+                    .first()
+                    .filter(|_| !method.ends_with("pow"))
+                    .and_then(|n| self.integer_type(n))
+            })
+            .or_else(|| self.type_of(expression).name().map(|name| name.to_string()))
+            .unwrap_or_else(|| "i32".to_string());
+        let big = self.is_big(&Type::named(&ty));
+        let runtime_type = self.runtime_type(&ty).to_string();
+        let checked = method.starts_with("checked_");
+        if checked {
+            self.prelude("Option");
+        }
+        match (method, input) {
+            ("abs", []) => {
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                let code = if big {
+                    format!("{}.abs({value})", self.runtime("$math"))
+                } else {
+                    format!("Math.abs({value})")
+                };
+                return Ok(Some(self.overflowed(
+                    (code, ATOM),
+                    &ty,
+                    "negate",
+                    self.release,
+                )));
+            }
+            ("signum", []) if !big => {
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                return Ok(Some((format!("Math.sign({value})"), ATOM)));
+            }
+            ("pow" | "checked_pow" | "saturating_pow" | "wrapping_pow", [exponent]) => {
+                let helper = self.runtime("$int");
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                let exponent = wrap(self.expression(exponent)?, ASSIGNMENT);
+                let mode = match method.split_once('_') {
+                    Some((mode, _)) => format!(", \"{mode}\""),
+                    None => String::new(),
+                };
+                return Ok(Some((
+                    format!("{helper}.pow({value}, {exponent}, \"{runtime_type}\"{mode})"),
+                    ATOM,
+                )));
+            }
+            ("min" | "max", [other]) => {
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                let other = wrap(self.operand(other, expression)?, ASSIGNMENT);
+                let helper = if big { self.runtime("$math") } else { "Math" };
+                return Ok(Some((format!("{helper}.{method}({value}, {other})"), ATOM)));
+            }
+            ("clamp", [min, max]) => {
+                let helper = self.runtime("$math");
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                let min = wrap(self.operand(min, expression)?, ASSIGNMENT);
+                let max = wrap(self.operand(max, expression)?, ASSIGNMENT);
+                return Ok(Some((
+                    format!("{helper}.clamp({value}, {min}, {max})"),
+                    ATOM,
+                )));
+            }
+            ("checked_neg", []) => {
+                let helper = self.runtime("$int");
+                let value = wrap(self.negated(expression)?, ASSIGNMENT);
+                return Ok(Some((
+                    format!("{helper}.checked({value}, \"{runtime_type}\")"),
+                    ATOM,
+                )));
+            }
+            (
+                "checked_add" | "checked_sub" | "checked_mul" | "saturating_add" | "saturating_sub"
+                | "saturating_mul",
+                [right],
+            ) => {
+                let helper = self.runtime("$int");
+                let operator = match &method[method.len() - 3..] {
+                    "add" => Operator::Add,
+                    "sub" => Operator::Subtract,
+                    _ => Operator::Multiply,
+                };
+                let value = wrap(self.binary(expression, operator, right)?, ASSIGNMENT);
+                let function = if checked { "checked" } else { "saturate" };
+                return Ok(Some((
+                    format!("{helper}.{function}({value}, \"{runtime_type}\")"),
+                    ATOM,
+                )));
+            }
+            ("checked_div" | "checked_rem" | "rem_euclid", [right]) => {
+                let helper = self.runtime("$int");
+                let value = wrap(self.operand(expression, right)?, ASSIGNMENT);
+                let right = wrap(self.operand(right, expression)?, ASSIGNMENT);
+                return Ok(Some((
+                    format!("{helper}.{method}({value}, {right}, \"{runtime_type}\")"),
+                    ATOM,
+                )));
+            }
+            ("leading_zeros" | "trailing_zeros" | "count_ones", []) => {
+                let helper = self.runtime("$int");
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                return Ok(Some((
+                    format!("{helper}.{method}({value}, \"{runtime_type}\")"),
+                    ATOM,
+                )));
+            }
+            _ => {}
+        }
+        let operator = match method {
+            "wrapping_neg" => {
+                let code = self.negated(expression)?;
+                return Ok(Some(self.overflowed(code, &ty, "negate", true)));
+            }
+            "wrapping_add" => Operator::Add,
+            "wrapping_sub" => Operator::Subtract,
+            "wrapping_mul" => Operator::Multiply,
+            "wrapping_div" => Operator::Divide,
+            "wrapping_rem" => Operator::Modulo,
+            _ => return Ok(None),
+        };
+        let [right] = input else {
+            return Ok(None);
+        };
+        // `i32::MIN.wrapping_div(-1)` is `i32::MIN`
+        if operator == Operator::Divide {
+            let code = self.integer_arithmetic(expression, operator, right, None, true)?;
+            return Ok(Some(self.overflowed(code, &ty, "divide", true)));
+        }
+        Ok(Some(self.integer_arithmetic(
+            expression,
+            operator,
+            right,
+            Some(&ty),
+            true,
+        )?))
+    }
+
+    // The methods of `char`, which is a one code point string
+    fn char_method(
+        &mut self,
+        expression: &Node,
+        method: &str,
+        input: &[Node],
+    ) -> Result<Option<(String, u8)>, Box<dyn Error>> {
+        if let Some(class) = char_class(method) {
+            let value = wrap(self.expression(expression)?, ASSIGNMENT);
+            return Ok(Some((format!("{class}.test({value})"), ATOM)));
+        }
+        Ok(Some(match method {
+            "to_ascii_uppercase" | "to_ascii_lowercase" => {
+                let helper = self.runtime("$char");
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                (format!("{helper}.{method}({value})"), ATOM)
+            }
+            // The case mappings can give several chars, like `'ß'` to `"SS"`
+            "to_uppercase" | "to_lowercase" => {
+                let js = if method == "to_uppercase" {
+                    "toUpperCase"
+                } else {
+                    "toLowerCase"
+                };
+                (format!("[...{}.{js}()]", self.receiver(expression)?), ATOM)
+            }
+            "len_utf8" => {
+                let helper = self.runtime("$str");
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                self.length(format!("{helper}.len({value})"))
+            }
+            "len_utf16" => {
+                let code = format!("{}.length", self.receiver(expression)?);
+                self.length(code)
+            }
+            "to_digit" | "is_digit" => {
+                self.prelude("Option");
+                let helper = self.runtime("$char");
+                let value = wrap(self.expression(expression)?, ASSIGNMENT);
+                let code = format!("{helper}.to_digit({value}, {})", self.arguments(input)?);
+                if method == "is_digit" {
+                    (format!("{code}!==Option.None"), 9)
+                } else {
+                    (code, ATOM)
+                }
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    // The methods of `f32` and `f64`, `None` for the ones without a JS counterpart
+    fn float_method(
+        &mut self,
+        expression: &Node,
+        method: &str,
+        input: &[Node],
+    ) -> Result<Option<(String, u8)>, Box<dyn Error>> {
+        let function = match method {
+            "abs" | "sqrt" | "cbrt" | "floor" | "ceil" | "trunc" | "exp" | "log10" | "log2"
+            | "sin" | "cos" | "tan" | "asin" | "acos" | "atan" | "atan2" | "sinh" | "cosh"
+            | "tanh" | "hypot" => format!("Math.{method}"),
+            "ln" => "Math.log".to_string(),
+            "powi" | "powf" => "Math.pow".to_string(),
+            "is_nan" => "Number.isNaN".to_string(),
+            "is_finite" => "Number.isFinite".to_string(),
+            "round" | "signum" | "min" | "max" | "clamp" => {
+                format!("{}.{method}", self.runtime("$math"))
+            }
+            "rem_euclid" => format!("{}.rem_euclid", self.runtime("$int")),
+            "to_degrees" | "to_radians" => {
+                let value = wrap(self.expression(expression)?, 13);
+                let factor = if method == "to_degrees" {
+                    "57.29577951308232"
+                } else {
+                    "0.017453292519943295"
+                };
+                let code = (format!("{value}*{factor}"), 13);
+                return Ok(Some(self.rounded(expression, code)));
+            }
+            _ => return Ok(None),
+        };
+        let mut arguments = vec![wrap(self.expression(expression)?, ASSIGNMENT)];
+        for node in input {
+            arguments.push(wrap(self.operand(node, expression)?, ASSIGNMENT));
+        }
+        // The message of a failed `clamp` shows the bounds as floats
+        if method == "clamp" {
+            let ty = self.type_of(expression);
+            arguments.push(format!("\"{}\"", ty.name().unwrap_or("f64")));
+        }
+        let code = (format!("{function}({})", arguments.join(", ")), ATOM);
+        // The results that are not already a value of the input are rounded to `f32`
+        if matches!(
+            method,
+            "abs" | "floor" | "ceil" | "trunc" | "round" | "signum" | "min" | "max" | "clamp"
+        ) || method.starts_with("is_")
+        {
+            return Ok(Some(code));
+        }
+        Ok(Some(self.rounded(expression, code)))
+    }
+
+    // Rounds a float computed in `f64` to `f32` when `node` is one, for `+ - * /` and
+    // `sqrt` that gives the result `f32` arithmetic has
+    fn rounded(&self, node: &Node, code: (String, u8)) -> (String, u8) {
+        if self.is_f32(node) {
+            (format!("Math.fround({})", wrap(code, ASSIGNMENT)), ATOM)
+        } else {
+            code
+        }
+    }
+
+    // Whether a float expression is an `f32`, unsuffixed literals take the type of
+    // what they are used with
+    fn is_f32(&self, node: &Node) -> bool {
+        match node {
+            Node::NumberLiteral(_) => false,
+            Node::Binary {
+                left,
+                operator:
+                    Operator::Add
+                    | Operator::Subtract
+                    | Operator::Multiply
+                    | Operator::Divide
+                    | Operator::Modulo,
+                right,
+            } => self.is_f32(left) || self.is_f32(right),
+            Node::Unary {
+                operator: Symbol::Minus,
+                expression,
+            } => self.is_f32(expression),
+            node => self.type_of(node).name() == Some("f32"),
+        }
+    }
+
+    // Integer `+ - * / %`, `ty` is `None` when the width is left to inference. Division
+    // truncates and panics on zero either way, overflow panics unless `wrapping`
+    fn integer_arithmetic(
+        &mut self,
+        left: &Node,
+        operator: Operator,
+        right: &Node,
+        ty: Option<&str>,
+        wrapping: bool,
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        if matches!(operator, Operator::Divide | Operator::Modulo) {
+            // Dividing by a positive constant can not fail
+            let constant =
+                matches!(right, Node::NumberLiteral(n) if n.parse::<i128>().is_ok_and(|n| n > 0));
+            if !constant {
+                let helper = self.runtime("$int");
+                let method = if operator == Operator::Divide {
+                    "div"
+                } else {
+                    "rem"
+                };
+                let (left, right) = (
+                    wrap(self.operand(left, right)?, ASSIGNMENT),
+                    wrap(self.operand(right, left)?, ASSIGNMENT),
+                );
+                let code = match ty {
+                    Some(ty) => {
+                        let ty = self.runtime_type(ty);
+                        format!("{helper}.{method}({left}, {right}, \"{ty}\")")
+                    }
+                    None => format!("{helper}.{method}({left}, {right})"),
+                };
+                return Ok((code, ATOM));
+            }
+            // BigInt division already truncates
+            let big = self.bigint_literals || ty.is_some_and(|ty| self.is_big(&Type::named(ty)));
+            let (code, precedence) = self.binary(left, operator, right)?;
+            return Ok(match (operator, ty) {
+                (Operator::Modulo, _) => (code, precedence),
+                _ if big => (code, precedence),
+                (_, Some("u32")) => (format!("{code}>>>0"), 11),
+                (_, Some("i8" | "i16" | "i32" | "u8" | "u16")) => (format!("{code}|0"), 6),
+                _ => (format!("Math.trunc({code})"), ATOM),
+            });
+        }
+        let Some(ty) = ty else {
+            return self.binary(left, operator, right);
+        };
+        // The exact product of two 32 bit integers can need more than the 53 bits of a double
+        if wrapping && operator == Operator::Multiply && matches!(ty, "i32" | "u32") {
+            let left = wrap(self.expression(left)?, ASSIGNMENT);
+            let right = wrap(self.expression(right)?, ASSIGNMENT);
+            let code = format!("Math.imul({left}, {right})");
+            return Ok(if ty == "u32" {
+                (format!("{code}>>>0"), 11)
+            } else {
+                (code, ATOM)
+            });
+        }
+        let operation = match operator {
+            Operator::Add => "add",
+            Operator::Subtract => "subtract",
+            _ => "multiply",
+        };
+        let code = self.binary(left, operator, right)?;
+        Ok(self.overflowed(code, ty, operation, wrapping))
+    }
+
+    // The result of an integer operation that can leave the range of `ty`
+    fn overflowed(
+        &mut self,
+        code: (String, u8),
+        ty: &str,
+        operation: &str,
+        wrapping: bool,
+    ) -> (String, u8) {
+        let ty = self.runtime_type(ty);
+        if !wrapping {
+            if !matches!(
+                ty,
+                "i8" | "i16"
+                    | "i32"
+                    | "i64"
+                    | "i128"
+                    | "u8"
+                    | "u16"
+                    | "u32"
+                    | "u64"
+                    | "usize"
+                    | "u128"
+            ) {
+                return code;
+            }
+            let helper = self.runtime("$int");
+            let value = wrap(code, ASSIGNMENT);
+            return (
+                format!("{helper}.check({value}, \"{ty}\", \"{operation}\")"),
+                ATOM,
+            );
+        }
+        match ty {
+            "i8" => (format!("{}<<24>>24", wrap(code, 12)), 11),
+            "i16" => (format!("{}<<16>>16", wrap(code, 12)), 11),
+            "i32" => (format!("{}|0", wrap(code, 7)), 6),
+            "u8" => (format!("{}&255", wrap(code, 8)), 7),
+            "u16" => (format!("{}&65535", wrap(code, 8)), 7),
+            "u32" => (format!("{}>>>0", wrap(code, 12)), 11),
+            "i64" | "i128" => (
+                format!("BigInt.asIntN({}, {})", &ty[1..], wrap(code, ASSIGNMENT)),
+                ATOM,
+            ),
+            "u64" | "u128" => (
+                format!("BigInt.asUintN({}, {})", &ty[1..], wrap(code, ASSIGNMENT)),
+                ATOM,
+            ),
+            _ => code,
+        }
+    }
+
+    // The integer type an expression is known to have, `None` for unsuffixed literals and
+    // what is computed from them, whose width Rust infers from where they end up
+    fn integer_type(&self, node: &Node) -> Option<String> {
+        let ty = match node {
+            Node::NumberLiteral(_) => return None,
+            Node::VeriableCall(name) if self.local(name).is_some_and(|local| local.inferred) => {
+                return None
+            }
+            Node::Binary {
+                left,
+                operator:
+                    Operator::Add
+                    | Operator::Subtract
+                    | Operator::Multiply
+                    | Operator::Divide
+                    | Operator::Modulo
+                    | Operator::BitAnd
+                    | Operator::BitOr
+                    | Operator::BitXor,
+                right,
+            } => {
+                // A BigInt side makes the result one, see `operand`
+                let (left, right) = (self.integer_type(left), self.integer_type(right));
+                return match (left, right) {
+                    (Some(left), Some(right)) if self.is_big(&Type::named(&right)) => {
+                        Some(if self.is_big(&Type::named(&left)) {
+                            left
+                        } else {
+                            right
+                        })
+                    }
+                    (left, right) => left.or(right),
+                };
+            }
+            Node::Binary {
+                left,
+                operator: Operator::ShiftLeft | Operator::ShiftRight,
+                ..
+            } => return self.integer_type(left),
+            Node::Unary {
+                operator: Symbol::Minus | Symbol::Exclamation,
+                expression,
+            } => return self.integer_type(expression),
+            node => self.type_of(node),
+        };
+        ty.name()
+            .filter(|name| integer_range(name).is_some())
+            .map(|name| name.to_string())
+    }
+
+    // The associated constants of the number types, like `i32::MAX` and `f64::consts::PI`
+    fn numeric_constant(&self, name: &str) -> Option<(String, Type)> {
+        let name = name
+            .strip_prefix("std::")
+            .or_else(|| name.strip_prefix("core::"))
+            .unwrap_or(name);
+        let (owner, constant) = name.split_once("::")?;
+        let ty = Type::named(owner);
+        if is_float(&ty) {
+            let f32 = owner == "f32";
+            let constant = constant.strip_prefix("consts::").unwrap_or(constant);
+            let code = match constant {
+                "PI" => "Math.PI",
+                "E" => "Math.E",
+                "TAU" => "6.283185307179586",
+                "FRAC_PI_2" => "1.5707963267948966",
+                "FRAC_PI_4" => "0.7853981633974483",
+                "SQRT_2" => "Math.SQRT2",
+                "FRAC_1_SQRT_2" => "Math.SQRT1_2",
+                "LN_2" => "Math.LN2",
+                "LN_10" => "Math.LN10",
+                "LOG2_E" => "Math.LOG2E",
+                "LOG10_E" => "Math.LOG10E",
+                "INFINITY" => "Infinity",
+                "NEG_INFINITY" => "-Infinity",
+                "NAN" => "NaN",
+                "EPSILON" if f32 => "1.1920928955078125e-7",
+                "EPSILON" => "Number.EPSILON",
+                "MAX" if f32 => "3.4028234663852886e38",
+                "MAX" => "Number.MAX_VALUE",
+                "MIN" if f32 => "-3.4028234663852886e38",
+                "MIN" => "-Number.MAX_VALUE",
+                "MIN_POSITIVE" if f32 => "1.1754943508222875e-38",
+                "MIN_POSITIVE" => "2.2250738585072014e-308",
+                _ => return None,
+            };
+            // The mathematical constants are rounded to the nearest `f32`
+            let exact = matches!(
+                constant,
+                "INFINITY" | "NEG_INFINITY" | "NAN" | "EPSILON" | "MAX" | "MIN" | "MIN_POSITIVE"
+            );
+            let code = if f32 && !exact {
+                format!("Math.fround({code})")
+            } else {
+                code.to_string()
+            };
+            return Some((code, ty));
+        }
+        let (signed, bits) = integer_width(owner)?;
+        let value = match constant {
+            "BITS" => return Some((bits.to_string(), Type::named("u32"))),
+            _ => integer_constant(name)?,
+        };
+        Some(match owner {
+            _ if self.is_big(&ty) => (format!("{value}n"), ty),
+            // Pointer sized numbers are exact up to 53 bits
+            "usize" | "isize" if value != "0" => {
+                let code = match (signed, constant) {
+                    (true, "MIN") => "Number.MIN_SAFE_INTEGER",
+                    _ => "Number.MAX_SAFE_INTEGER",
+                };
+                (code.to_string(), ty)
+            }
+            _ => (value, ty),
+        })
+    }
+
+    // The element type of an array literal, an unsuffixed integer takes the type of the
+    // first element that has one
+    fn element_literal_type(&self, elements: &[Node]) -> Type {
+        let typed = elements.iter().find(|element| !is_integer_literal(element));
+        match typed.or(elements.first()) {
+            Some(element) => self.type_of(element),
+            None => Type::Unknown,
+        }
+    }
+
+    // The 64 and 128 bit integers are BigInts, the pointer sized ones under `bigint_usize`
+    fn is_big(&self, ty: &Type) -> bool {
+        match ty.name() {
+            Some("i64" | "u64" | "i128" | "u128") => true,
+            Some("isize" | "usize") => self.bigint_usize,
+            _ => false,
+        }
+    }
+
+    // The integer type the runtime checks and parses as, pointer sized BigInts are 64 bit ones
+    fn runtime_type<'a>(&self, ty: &'a str) -> &'a str {
+        match ty {
+            "usize" if self.bigint_usize => "u64",
+            "isize" if self.bigint_usize => "i64",
+            ty => ty,
+        }
+    }
+
+    // A value where a `ty` is expected. Integer literals there are BigInt literals and
+    // integers inferred as numbers are converted, `None` when nothing has to change
+    fn coerced(&mut self, node: &Node, ty: &Type) -> Result<Option<(String, u8)>, Box<dyn Error>> {
+        match (node, ty) {
+            (Node::ArrayLiteral(elements), _) if is_vec(ty) => {
+                let element = element_type(ty);
+                if !self.is_big(&element)
+                    && element.name() != Some("f32")
+                    && !matches!(element, Type::Array(..) | Type::Tuple(_))
+                {
+                    return Ok(None);
+                }
+                let mut values = Vec::new();
+                for node in elements {
+                    values.push(wrap(self.stored(node, &element, true)?, ASSIGNMENT));
+                }
+                return Ok(Some((format!("[{}]", values.join(", ")), ATOM)));
+            }
+            (Node::ArrayRepeat { value, count }, _) if is_vec(ty) => {
+                let Some(value) = self.coerced(value, &element_type(ty))? else {
+                    return Ok(None);
+                };
+                let value = wrap(value, ASSIGNMENT);
+                let count = wrap(self.position(count)?, ASSIGNMENT);
+                return Ok(Some((format!("Array({count}).fill({value})"), ATOM)));
+            }
+            (Node::Macro { name, input }, _) if name == "vec" && is_vec(ty) => {
+                return match &input[..] {
+                    [Node::ArrayRepeat { .. }] => self.coerced(&input[0], ty),
+                    _ => self.coerced(&Node::ArrayLiteral(input.to_vec()), ty),
+                };
+            }
+            (Node::TupleLiteral(elements), Type::Tuple(types))
+                if !elements.is_empty() && elements.len() == types.len() =>
+            {
+                let mut values = Vec::new();
+                for (node, ty) in elements.iter().zip(types) {
+                    values.push(wrap(self.stored(node, ty, true)?, ASSIGNMENT));
+                }
+                return Ok(Some((format!("[{}]", values.join(", ")), ATOM)));
+            }
+            // An `f32` literal is written as the `f32` nearest to it
+            (Node::NumberLiteral(n), _) if ty.name() == Some("f32") && is_float_literal(n) => {
+                let value = n
+                    .parse::<f32>()
+                    .map_err(|_| format!("Code generator Literal: Invalid float literal {n}"))?;
+                if value.is_infinite() {
+                    return Ok(Some(("Infinity".to_string(), ATOM)));
+                }
+                return Ok(Some((format!("{:?}", value as f64), ATOM)));
+            }
+            (
+                Node::Unary {
+                    operator: Symbol::Minus,
+                    expression,
+                },
+                _,
+            ) if ty.name() == Some("f32") => {
+                return Ok(self
+                    .coerced(expression, ty)?
+                    .map(|code| (format!("-{}", wrap(code, UNARY)), UNARY)));
+            }
+            _ => {}
+        }
+        if !self.is_big(ty) || self.integer_type(node).is_some() || !is_integer(&self.type_of(node))
+        {
+            return Ok(None);
+        }
+        if is_integer_literal(node) {
+            let outer = std::mem::replace(&mut self.bigint_literals, true);
+            let code = self.expression(node);
+            self.bigint_literals = outer;
+            return Ok(Some(code?));
+        }
+        let code = wrap(self.expression(node)?, ASSIGNMENT);
+        Ok(Some((format!("BigInt({code})"), ATOM)))
+    }
+
+    // A value stored where a `ty` is expected, copied like `copied` does
+    fn stored(
+        &mut self,
+        node: &Node,
+        ty: &Type,
+        changes: bool,
+    ) -> Result<(String, u8), Box<dyn Error>> {
+        match self.coerced(node, ty)? {
+            Some(code) => Ok(code),
+            None => self.copied(node, changes),
+        }
+    }
+
+    // An operand of an integer operator, it becomes a BigInt when the other one is
+    fn operand(&mut self, node: &Node, other: &Node) -> Result<(String, u8), Box<dyn Error>> {
+        let ty = match self.integer_type(other) {
+            Some(ty) => Some(ty),
+            None if self.is_f32(other) => Some("f32".to_string()),
+            None => None,
+        };
+        if let Some(ty) = ty {
+            if let Some(code) = self.coerced(node, &Type::named(&ty))? {
+                return Ok(code);
+            }
+            // Both sides have the same type in Rust, a number next to a BigInt comes from
+            // literals whose width was only pinned down by this use, `a + v[0]` after
+            // `let v = vec![1, 2]`
+            let number = self
+                .integer_type(node)
+                .is_some_and(|width| !self.is_big(&Type::named(&width)));
+            if number && self.is_big(&Type::named(&ty)) {
+                let code = wrap(self.expression(node)?, ASSIGNMENT);
+                return Ok((format!("BigInt({code})"), ATOM));
+            }
+        }
+        self.expression(node)
+    }
+
+    // A length or count, which is a `usize`
+    fn length(&self, code: String) -> (String, u8) {
+        if self.bigint_usize {
+            (format!("BigInt({code})"), ATOM)
+        } else {
+            (code, ATOM)
+        }
+    }
+
+    // The first `usize` index of a count
+    fn first_index(&self) -> &'static str {
+        if self.bigint_usize {
+            "0n"
+        } else {
+            "0"
+        }
+    }
+
+    // A `usize` position handed to a JS method, which only takes numbers
+    fn position(&mut self, node: &Node) -> Result<(String, u8), Box<dyn Error>> {
+        let code = self.expression(node)?;
+        if self
+            .integer_type(node)
+            .is_some_and(|ty| self.is_big(&Type::named(&ty)))
+        {
+            return Ok((format!("Number({})", wrap(code, ASSIGNMENT)), ATOM));
+        }
+        Ok(code)
+    }
+
+    fn arguments(&mut self, input: &[Node]) -> Result<String, Box<dyn Error>> {
+        let mut arguments = Vec::new();
+        for n in input {
+            arguments.push(wrap(self.copied(n, true)?, ASSIGNMENT));
+        }
+        Ok(arguments.join(", "))
+    }
+
+    // The arguments of a call to a user function, copied only for the parameters it
+    // changes or keeps
+    fn call_arguments(&mut self, path: &str, input: &[Node]) -> Result<String, Box<dyn Error>> {
+        let parameters = self
+            .parameter_changes
+            .get(path)
+            .cloned()
+            .unwrap_or_default();
+        let mut changes = parameters.iter().filter(|(name, _)| name != "self");
+        let mut types = self.argument_types(path).into_iter();
+        let mut cells = self.cell_parameters.get(path).cloned().unwrap_or_default();
+        cells.resize(input.len(), false);
+        let mut arguments = Vec::new();
+        for (node, cell) in input.iter().zip(cells) {
+            let changes = changes.next().is_none_or(|(_, changes)| *changes);
+            let ty = types.next().unwrap_or(Type::Unknown);
+            if cell {
+                arguments.push(self.cell(node)?);
+                continue;
+            }
+            arguments.push(wrap(self.stored(node, &ty, changes)?, ASSIGNMENT));
+        }
+        Ok(arguments.join(", "))
+    }
+
+    // The parameter types of a user function, or the field types of a tuple struct or variant
+    fn argument_types(&self, path: &str) -> Vec<Type> {
+        if let Some(types) = self.parameter_types.get(path) {
+            return types.clone();
+        }
+        let fields = match self.variant(path) {
+            Some((_, variant)) => Some(variant.fields),
+            None => self.structs.get(path).cloned(),
+        };
+        match fields {
+            Some(Fields::Tuple(types)) => types,
+            _ => Vec::new(),
+        }
+    }
+
+    // The receiver of a call to a user method, copied when the method changes its own `self`
+    fn call_receiver(&mut self, path: &str, node: &Node) -> Result<String, Box<dyn Error>> {
+        let changes = self
+            .parameter_changes
+            .get(path)
+            .is_some_and(|parameters| parameters.iter().any(|p| p == &("self".to_string(), true)));
+        if !changes {
+            return self.receiver(node);
+        }
+        Ok(wrap(self.copied(node, true)?, ATOM))
+    }
+
+    // A local a `*name = value` writes through has to be a cell or stand for the place,
+    // a scalar copied into a plain variable would keep the write to itself
+    fn written_reference(&self, node: &Node) -> Result<(), Box<dyn Error>> {
+        let Node::VeriableCall(name) = node else {
+            return Ok(());
+        };
+        match self.local(name) {
+            Some(local)
+                if local.cell.is_none() && is_identifier(&local.js) && is_scalar(&local.ty) =>
+            {
+                Err(format!(
+                    "Code generator Assignment: Can not write through {name}, it is a copy of the place it points to"
+                )
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // A temporary cell standing for a method call that is assigned to, only the
+    // `unwrap` of `get_mut` and the like is a place
+    fn method_place(&mut self, node: &Node) -> Result<Node, Box<dyn Error>> {
+        let Some(cell) = self.unwrapped_cell(node)? else {
+            let method = match node {
+                Node::MethodCall { method, .. } => method.as_str(),
+                _ => "",
+            };
+            return Err(format!(
+                "Code generator Assignment: Can not assign to the value of {method}()"
+            )
+            .into());
+        };
+        let name = self.temporary("c");
+        self.hoisted.push(format!("const {name}={cell};\n"));
+        self.insert_cell(&name, self.type_of(node), &name);
+        Ok(Node::VeriableCall(name))
+    }
+
+    // A `&mut` to a scalar place, JS can only share it as an object reading and writing
+    // the place. Passing on a `&mut` that already is a cell passes the cell
+    fn cell(&mut self, node: &Node) -> Result<String, Box<dyn Error>> {
+        let place = match node {
+            Node::MutableReference(place) => place.as_ref(),
+            node => node,
+        };
+        let name = match place {
+            Node::VeriableCall(name) => Some(name),
+            Node::Deref(expression) => match expression.as_ref() {
+                Node::VeriableCall(name) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(cell) = name.and_then(|name| self.local(name)?.cell.clone()) {
+            return Ok(cell);
+        }
+        let place = self.stable_place(place)?;
+        let code = wrap(self.expression(&place)?, ATOM);
+        let write = match &place {
+            Node::Index { expression, index } if is_map(&self.type_of(expression)) => format!(
+                "{}.set({}, $v)",
+                self.receiver(expression)?,
+                wrap(self.expression(index)?, ASSIGNMENT)
+            ),
+            _ => match self.checked_index(&place)? {
+                Some((values, index)) => {
+                    let helper = self.runtime("$index");
+                    format!("{helper}.set({values}, {index}, $v)")
+                }
+                None => format!("{code}=$v"),
+            },
+        };
+        // Arrows keep the `this` of a `&mut self.field`
+        let helper = self.runtime("$cell");
+        Ok(format!("{helper}(()=>{code}, ($v)=>{write})"))
+    }
+
+    // The array and index code of a place in a `Vec` or array, whose writes are bounds
+    // checked. Maps, `VecDeque`s and `IndexMut` types check their own
+    fn checked_index(&mut self, place: &Node) -> Result<Option<(String, String)>, Box<dyn Error>> {
+        let place = match place {
+            Node::MutableReference(place) => place.as_ref(),
+            place => place,
+        };
+        let Node::Index { expression, index } = place else {
+            return Ok(None);
+        };
+        let ty = self.type_of(expression);
+        if !is_vec(&ty)
+            || matches!(index.as_ref(), Node::Range { .. })
+            || self.overloaded(&ty, "IndexMut", "index_mut").is_some()
+        {
+            return Ok(None);
+        }
+        let values = wrap(self.expression(expression)?, ASSIGNMENT);
+        let index = wrap(self.expression(index)?, ASSIGNMENT);
+        Ok(Some((values, index)))
+    }
+
+    // `values.get_mut(key)`, `first_mut()` and `last_mut()` of scalar elements, as the
+    // test for the element and its place. The element is a copy, writes through the
+    // `&mut` have to go to the place
+    fn element_place(&mut self, node: &Node) -> Result<Option<(Node, Node)>, Box<dyn Error>> {
+        let Node::MethodCall {
+            expression,
+            method,
+            input,
+            ..
+        } = node
+        else {
+            return Ok(None);
+        };
+        let ty = pointee(self.type_of(expression));
+        let map = is_map(&ty);
+        let item = if map {
+            generic_argument(&ty, 1)
+        } else {
+            element_type(&ty)
+        };
+        if !(map || is_vec(&ty)) || !is_scalar(&item) {
+            return Ok(None);
+        }
+        let call = |values: &Node, method: &str, input: Vec<Node>| Node::MethodCall {
+            expression: Box::new(values.clone()),
+            method: method.to_string(),
+            generics: Vec::new(),
+            input,
+        };
+        let (test, index) = match (method.as_str(), &input[..]) {
+            ("get_mut", [key]) => {
+                let values = self.stable_place(expression)?;
+                let key = self.stable_value(key)?;
+                let test = if map {
+                    call(&values, "contains_key", vec![key.clone()])
+                } else {
+                    Node::Binary {
+                        left: Box::new(key.clone()),
+                        operator: Operator::Lesser,
+                        right: Box::new(call(&values, "len", Vec::new())),
+                    }
+                };
+                (test, (values, key))
+            }
+            ("first_mut" | "last_mut", []) if !map => {
+                let values = self.stable_place(expression)?;
+                let test = Node::Unary {
+                    operator: Symbol::Exclamation,
+                    expression: Box::new(call(&values, "is_empty", Vec::new())),
+                };
+                let index = if method == "first_mut" {
+                    Node::NumberLiteral("0".to_string())
+                } else {
+                    Node::Binary {
+                        left: Box::new(call(&values, "len", Vec::new())),
+                        operator: Operator::Subtract,
+                        right: Box::new(Node::NumberLiteral("1".to_string())),
+                    }
+                };
+                (test, (values, index))
+            }
+            _ => return Ok(None),
+        };
+        let (values, index) = index;
+        let place = Node::Index {
+            expression: Box::new(values),
+            index: Box::new(index),
+        };
+        Ok(Some((test, place)))
+    }
+
+    // The cell of `values.get_mut(key).unwrap()` and the like, panicking first like the
+    // `unwrap` would
+    fn unwrapped_cell(&mut self, node: &Node) -> Result<Option<String>, Box<dyn Error>> {
+        let Node::MethodCall {
+            expression,
+            method,
+            input,
+            ..
+        } = node
+        else {
+            return Ok(None);
+        };
+        // `map.entry(key).or_insert(value)` on scalars, the entry reads and writes the map
+        let ty = self.type_of(expression);
+        if ty.name() == Some("Entry") && is_scalar(&generic_argument(&ty, 1)) {
+            let entry = self.receiver(expression)?;
+            return Ok(Some(match (method.as_str(), &input[..]) {
+                ("or_insert", [value]) => {
+                    format!(
+                        "{entry}.place({})",
+                        wrap(self.expression(value)?, ASSIGNMENT)
+                    )
+                }
+                ("or_insert_with", [value]) => {
+                    format!(
+                        "{entry}.place_with({})",
+                        wrap(self.expression(value)?, ASSIGNMENT)
+                    )
+                }
+                ("or_default", []) => format!(
+                    "{entry}.place({})",
+                    self.default_value(&generic_argument(&ty, 1))?
+                ),
+                _ => return Ok(None),
+            }));
+        }
+        if !matches!(method.as_str(), "unwrap" | "expect") {
+            return Ok(None);
+        }
+        let Some((test, place)) = self.element_place(expression)? else {
+            return Ok(None);
+        };
+        let message = match input.first() {
+            Some(message) => wrap(self.expression(message)?, ASSIGNMENT),
+            None => "\"called `Option::unwrap()` on a `None` value\"".to_string(),
+        };
+        let test = wrap(self.expression(&test)?, UNARY);
+        let panic = self.runtime("$panic");
+        self.hoisted
+            .push(format!("if (!{test}){{\n{panic}({message});\n}}\n"));
+        Ok(Some(self.cell(&place)?))
+    }
+
+    // `if let Some(value) = values.get_mut(key)` on scalars, as the test and a `let`
+    // binding a `&mut` to the place
+    fn place_binding(
+        &mut self,
+        condition: &Node,
+        pattern: Option<&Pattern>,
+    ) -> Result<Option<(Node, Node)>, Box<dyn Error>> {
+        let Some(Pattern::TupleStruct { name, fields }) = pattern else {
+            return Ok(None);
+        };
+        let [Pattern::Binding {
+            name: binding,
+            pattern: None,
+        }] = &fields[..]
+        else {
+            return Ok(None);
+        };
+        if self.resolve(name) != "Option::Some" {
+            return Ok(None);
+        }
+        let Some((test, place)) = self.element_place(condition)? else {
+            return Ok(None);
+        };
+        let binding = Node::Veriable {
+            name: binding.to_string(),
+            ty: None,
+            value: Some(Box::new(Node::MutableReference(Box::new(place)))),
+        };
+        Ok(Some((test, binding)))
+    }
+
+    // Whether the place a value is read from changes in place somewhere in the function
+    fn changed(&self, node: &Node) -> bool {
+        place_root(node).is_some_and(|root| self.changes.mutated.iter().any(|name| name == root))
+    }
+
+    // A value read out of a place into another one. JS would share one object between
+    // the two, so `Copy` aggregates get copied when either of them can change while the
+    // other is still around, which `changes` tells
+    fn copied(&mut self, node: &Node, changes: bool) -> Result<(String, u8), Box<dyn Error>> {
+        let needed = changes && place_root(node).is_some_and(|root| self.variant(root).is_none());
+        let ty = self.type_of(node);
+        if !needed || !self.is_copy(&ty) {
+            return self.expression(node);
+        }
+        Ok(match &ty {
+            Type::Named { name, .. } if self.implements(name, "Copy") => {
+                let method = self.method_name(Some(name), "clone");
+                (format!("{}.{method}()", self.receiver(node)?), ATOM)
+            }
+            Type::Array(element, _) if is_primitive(element) => (
+                format!("[...{}]", wrap(self.expression(node)?, ASSIGNMENT)),
+                ATOM,
+            ),
+            Type::Tuple(types) if types.iter().all(is_primitive) => (
+                format!("[...{}]", wrap(self.expression(node)?, ASSIGNMENT)),
+                ATOM,
+            ),
+            Type::Array(..) | Type::Tuple(_) => {
+                let helper = self.runtime("$clone");
+                let value = wrap(self.expression(node)?, ASSIGNMENT);
+                (format!("{helper}({value})"), ATOM)
+            }
+            _ => self.expression(node)?,
+        })
+    }
+
+    // A returned place outlives the function, it only stays unshared if the function
+    // owned it
+    fn returned(&mut self, node: &Node) -> Result<(String, u8), Box<dyn Error>> {
+        if let Some(output) = self.output.clone() {
+            if let Some(code) = self.coerced(node, &output)? {
+                return Ok(code);
+            }
+        }
+        let owned =
+            place_root(node).is_some_and(|root| self.changes.owned.iter().any(|name| name == root));
+        if owned {
+            self.expression(node)
+        } else {
+            self.copied(node, true)
+        }
+    }
+
+    // Values that are copied rather than moved, the mutable ones among them are JS objects
+    fn is_copy(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Named { name, .. } => is_primitive(ty) || self.implements(name, "Copy"),
+            Type::Tuple(types) => !types.is_empty() && types.iter().all(|ty| self.is_copy(ty)),
+            Type::Array(element, _) => self.is_copy(element),
+            Type::Unknown => false,
+        }
+    }
+
+    // The left hand side of a `.` or `[`
+    fn receiver(&mut self, node: &Node) -> Result<String, Box<dyn Error>> {
+        let code = wrap(self.expression(node)?, ATOM);
+        // `5.abs()` would read as a malformed number in JS
+        if let Node::NumberLiteral(n) = node {
+            if !is_float_literal(n) {
+                return Ok(format!("({code})"));
+            }
+        }
+        Ok(code)
+    }
+
+    fn match_statement(
+        &mut self,
+        expression: &Node,
+        arms: &[Arm],
+        target: &Target,
+    ) -> Result<String, Box<dyn Error>> {
+        let ty = self.type_of(expression);
+        let arms: Vec<Arm> = arms
+            .iter()
+            .map(|arm| Arm {
+                pattern: self.normalize(&arm.pattern),
+                ..arm.clone()
+            })
+            .collect();
+        let arms = &arms[..];
+        self.check_match(&ty, arms)?;
+        let reference = self.is_reference(expression);
+        // The scrutinee is read by every arm
+        let access = self.matched(expression, arms.iter().map(|arm| &arm.pattern))?;
+        if let Some(switch) = self.match_switch(&access, &ty, arms, reference, target)? {
+            return Ok(switch);
+        }
+        let mut program = String::new();
+
+        let mut first = true;
+        for (n, arm) in arms.iter().enumerate() {
+            let mut tests = self.pattern_test(&arm.pattern, &access, &ty)?;
+            // The match is exhaustive, so whatever reaches the last arm matches it
+            if n + 1 == arms.len() && arm.guard.is_none() {
+                tests.clear();
+            }
+            let bindings = self.pattern_bindings(&arm.pattern, &access, &ty, reference)?;
+            if let Some(guard) = &arm.guard {
+                // The guard reads the bindings straight from the scrutinee
+                self.scopes.push(HashMap::new());
+                for (name, code, ty, _) in &bindings {
+                    self.insert(name, ty.clone(), code);
+                }
+                let hoisted = self.hoisted.len();
+                let guard = self.expression(guard);
+                self.scopes.pop();
+                if self.hoisted.len() != hoisted {
+                    return Err(
+                        "Code generator Match: Guards can not contain branching expressions".into(),
+                    );
+                }
+                tests.push(guard?.0);
+            }
+
+            let body = self.arm_body(&bindings, &arm.body, target)?;
+            if tests.is_empty() {
+                // Every following arm is unreachable
+                if first {
+                    program.push_str(format!("{{\n{body}}}\n").as_str());
+                } else if !body.is_empty() {
+                    program.push_str(format!("else{{\n{body}}}\n").as_str());
+                }
+                break;
+            }
+            let keyword = if first { "if" } else { "else if" };
+            program.push_str(format!("{keyword} ({}){{\n{body}}}\n", conjunction(&tests)).as_str());
+            first = false;
+        }
+        Ok(program)
+    }
+
+    fn arm_body(
+        &mut self,
+        bindings: &[Binding],
+        body: &Node,
+        target: &Target,
+    ) -> Result<String, Box<dyn Error>> {
+        match body {
+            Node::Block(body) => self.bound_block(bindings, body, target),
+            _ => self.bound_block(bindings, std::slice::from_ref(body), target),
+        }
+    }
+
+    // Declares a name a pattern binds, a `&mut` to a scalar is a cell over its place
+    fn bind(&mut self, (name, code, ty, place): &Binding) -> String {
+        let js = self.fresh_name(name);
+        if !place {
+            self.insert(name, ty.clone(), &js);
+            return format!("let {js}={code}");
+        }
+        let helper = self.runtime("$cell");
+        self.insert_cell(name, ty.clone(), &js);
+        format!("let {js}={helper}(()=>{code}, ($v)=>{code}=$v)")
+    }
+
+    // A block that starts by declaring the bindings of a pattern
+    fn bound_block(
+        &mut self,
+        bindings: &[Binding],
+        body: &[Node],
+        target: &Target,
+    ) -> Result<String, Box<dyn Error>> {
+        self.scopes.push(HashMap::new());
+        let mut program = String::new();
+        for binding in bindings {
+            program.push_str(format!("{};\n", self.bind(binding)).as_str());
+        }
+        let body = self.statements(body, target);
+        self.scopes.pop();
+        Ok(program + &body?)
+    }
+
+    // Lowers a match to a JS switch when every arm is a literal or a variant without guards
+    fn match_switch(
+        &mut self,
+        access: &str,
+        ty: &Type,
+        arms: &[Arm],
+        reference: bool,
+        target: &Target,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        if arms.len() < 3 || arms.iter().any(|arm| arm.guard.is_some()) {
+            return Ok(None);
+        }
+        let mut on_tag = None;
+        let mut cases = Vec::new();
+        for arm in arms {
+            let Some((labels, tags)) = self.switch_labels(&arm.pattern, ty)? else {
+                return Ok(None);
+            };
+            if !labels.is_empty() {
+                if on_tag.is_some_and(|on_tag| on_tag != tags) {
+                    return Ok(None);
+                }
+                on_tag = Some(tags);
+            }
+            let default = labels.is_empty();
+            cases.push((labels, arm));
+            if default {
+                break;
+            }
+        }
+
+        let scrutinee = if on_tag == Some(true) {
+            format!("{access}.$tag")
+        } else {
+            access.to_string()
+        };
+        let mut program = format!("switch ({scrutinee}){{\n");
+        for (labels, arm) in cases {
+            if labels.is_empty() {
+                program.push_str("default:");
+            }
+            for (n, label) in labels.iter().enumerate() {
+                if n > 0 {
+                    program.push('\n');
+                }
+                program.push_str(format!("case {label}:").as_str());
+            }
+            let bindings = self.pattern_bindings(&arm.pattern, access, ty, reference)?;
+            if let Some(current) = self.loops.last_mut() {
+                current.switches += 1;
+            }
+            let body = self.arm_body(&bindings, &arm.body, target);
+            if let Some(current) = self.loops.last_mut() {
+                current.switches -= 1;
+            }
+            let body = body?;
+            // Nothing runs after a jump
+            let jumps = ["return", "break", "continue"].iter().any(|jump| {
+                body.lines()
+                    .last()
+                    .is_some_and(|line| line.starts_with(jump))
+            });
+            if jumps {
+                program.push_str(format!("{{\n{body}}}\n").as_str());
+            } else {
+                program.push_str(format!("{{\n{body}break;\n}}\n").as_str());
+            }
+        }
+        program.push_str("}\n");
+        Ok(Some(program))
+    }
+
+    // The case labels of a pattern and whether they are enum tags,
+    // no labels means the default case and `None` that a switch can not express it
+    // A plain name in a pattern binds a variable, unless it names a unit variant or struct
+    fn normalize(&self, pattern: &Pattern) -> Pattern {
+        let normalize = |patterns: &[Pattern]| patterns.iter().map(|p| self.normalize(p)).collect();
+        match pattern {
+            Pattern::Binding {
+                name,
+                pattern: None,
+            } => {
+                let unit = match self.variant(name) {
+                    Some((_, variant)) => matches!(variant.fields, Fields::Unit),
+                    None => matches!(self.structs.get(&self.resolve(name)), Some(Fields::Unit)),
+                };
+                if unit {
+                    Pattern::Path(self.resolve(name))
+                } else {
+                    pattern.clone()
+                }
+            }
+            Pattern::Binding {
+                name,
+                pattern: Some(pattern),
+            } => Pattern::Binding {
+                name: name.to_string(),
+                pattern: Some(Box::new(self.normalize(pattern))),
+            },
+            Pattern::Or(patterns) => Pattern::Or(normalize(patterns)),
+            Pattern::Tuple(patterns) => Pattern::Tuple(normalize(patterns)),
+            Pattern::Slice(patterns) => Pattern::Slice(normalize(patterns)),
+            Pattern::TupleStruct { name, fields } => Pattern::TupleStruct {
+                name: name.to_string(),
+                fields: normalize(fields),
+            },
+            Pattern::Struct { name, fields } => Pattern::Struct {
+                name: name.to_string(),
+                fields: fields
+                    .iter()
+                    .map(|(field, p)| (field.to_string(), self.normalize(p)))
+                    .collect(),
+            },
+            _ => pattern.clone(),
+        }
+    }
+
+    fn tag_label(&self, name: &str) -> Option<Labels> {
+        if self.is_nullable(name) {
+            return None;
+        }
+        self.variant(name)
+            .map(|(_, variant)| (vec![format!("\"{}\"", variant.name)], true))
+    }
+
+    fn switch_labels(
+        &mut self,
+        pattern: &Pattern,
+        ty: &Type,
+    ) -> Result<Option<Labels>, Box<dyn Error>> {
+        Ok(match pattern {
+            Pattern::Wildcard | Pattern::Binding { pattern: None, .. } | Pattern::RefMut(_) => {
+                Some((Vec::new(), false))
+            }
+            Pattern::Literal(literal) => Some((vec![self.stored(literal, ty, false)?.0], false)),
+            Pattern::Path(name) => self.tag_label(name),
+            Pattern::TupleStruct { name, fields } if self.all_irrefutable(fields.iter()) => {
+                self.tag_label(name)
+            }
+            Pattern::Struct { name, fields }
+                if self.all_irrefutable(fields.iter().map(|(_, p)| p)) =>
+            {
+                self.tag_label(name)
+            }
+            Pattern::Or(alternatives) => {
+                let mut labels = Vec::new();
+                let mut on_tag = None;
+                for alternative in alternatives {
+                    if !self.pattern_names(alternative).is_empty() {
+                        return Ok(None);
+                    }
+                    match self.switch_labels(alternative, ty)? {
+                        Some((alternative, tags))
+                            if !alternative.is_empty()
+                                && on_tag.is_none_or(|on_tag| on_tag == tags) =>
+                        {
+                            on_tag = Some(tags);
+                            labels.extend(alternative);
+                        }
+                        _ => return Ok(None),
+                    }
+                }
+                Some((labels, on_tag.unwrap_or(false)))
+            }
+            _ => None,
+        })
+    }
+
+    fn all_irrefutable<'a>(&self, mut patterns: impl Iterator<Item = &'a Pattern>) -> bool {
+        patterns.all(|pattern| self.is_irrefutable(pattern))
+    }
+
+    fn is_irrefutable(&self, pattern: &Pattern) -> bool {
+        match pattern {
+            Pattern::Wildcard | Pattern::Rest | Pattern::RefMut(_) => true,
+            Pattern::Binding { pattern, .. } => {
+                pattern.as_ref().is_none_or(|p| self.is_irrefutable(p))
+            }
+            Pattern::Tuple(elements) => self.all_irrefutable(elements.iter()),
+            Pattern::Struct { name, fields } => {
+                self.variant(name).is_none() && self.all_irrefutable(fields.iter().map(|(_, p)| p))
+            }
+            Pattern::TupleStruct { name, fields } => {
+                self.variant(name).is_none() && self.all_irrefutable(fields.iter())
+            }
+            _ => false,
+        }
+    }
+
+    fn pattern_names(&self, pattern: &Pattern) -> Vec<String> {
+        match pattern {
+            Pattern::RefMut(name) => vec![name.to_string()],
+            Pattern::Binding { name, pattern } => {
+                let mut names = vec![name.to_string()];
+                if let Some(pattern) = pattern {
+                    names.extend(self.pattern_names(pattern));
+                }
+                names
+            }
+            Pattern::Or(alternatives) => alternatives
+                .first()
+                .map(|first| self.pattern_names(first))
+                .unwrap_or_default(),
+            Pattern::Tuple(patterns)
+            | Pattern::Slice(patterns)
+            | Pattern::TupleStruct {
+                fields: patterns, ..
+            } => patterns
+                .iter()
+                .flat_map(|p| self.pattern_names(p))
+                .collect(),
+            Pattern::Struct { fields, .. } => fields
+                .iter()
+                .flat_map(|(_, p)| self.pattern_names(p))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // The enum and variant a path like `Shape::Circle` names
+    fn variant(&self, name: &str) -> Option<(String, Variant)> {
+        let name = self.resolve(name);
+        let (owner, variant) = name.rsplit_once("::")?;
+        let variant = self.enums.get(owner)?.iter().find(|v| v.name == variant)?;
+        Some((owner.to_string(), variant.clone()))
+    }
+
+    // The fields of the variant or struct a pattern names
+    fn pattern_fields(&self, name: &str) -> Result<Fields, Box<dyn Error>> {
+        if let Some((_, variant)) = self.variant(name) {
+            return Ok(variant.fields);
+        }
+        match self.structs.get(&self.resolve(name)) {
+            Some(fields) => Ok(fields.clone()),
+            None => Err(format!("Code generator Pattern: Unknown struct or variant {name}").into()),
+        }
+    }
+
+    // Whether a path names a variant of a nullable option
+    fn is_nullable(&self, name: &str) -> bool {
+        self.nullable
+            && self
+                .variant(name)
+                .is_some_and(|(owner, _)| owner == "Option")
+    }
+
+    // Reads a positional field, the value of a nullable `Some` is the option itself
+    fn positional(&self, name: &str, access: &str, n: usize) -> String {
+        if self.is_nullable(name) {
+            access.to_string()
+        } else {
+            format!("{access}[{n}]")
+        }
+    }
+
+    // The types of positional fields, generic prelude variants take them from the matched type
+    fn positional_types(&self, name: &str, ty: &Type) -> Result<Vec<Type>, Box<dyn Error>> {
+        let generics = match ty {
+            Type::Named { generics, .. } => generics.as_slice(),
+            _ => &[],
+        };
+        if let Some((owner, variant)) = self.variant(name) {
+            if owner == "Option" || owner == "Result" {
+                let n = usize::from(variant.name == "Err");
+                return Ok(vec![generics.get(n).cloned().unwrap_or(Type::Unknown)]);
+            }
+        }
+        Ok(match self.pattern_fields(name)? {
+            Fields::Tuple(types) => types.iter().map(|t| self.instantiate(ty, t)).collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    // Checks the tag when the pattern names an enum variant
+    fn variant_test(&self, name: &str, access: &str) -> Vec<String> {
+        if self.is_nullable(name) {
+            let operator = if name.ends_with("None") { "===" } else { "!==" };
+            return vec![format!("{access}{operator}null")];
+        }
+        match self.variant(name) {
+            Some((_, variant)) => vec![format!("{access}.$tag===\"{}\"", variant.name)],
+            None => Vec::new(),
+        }
+    }
+
+    // The conditions under which `pattern` matches the value `access` reads
+    fn pattern_test(
+        &mut self,
+        pattern: &Pattern,
+        access: &str,
+        ty: &Type,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(match pattern {
+            Pattern::Wildcard | Pattern::Rest | Pattern::RefMut(_) => Vec::new(),
+            Pattern::Binding { pattern, .. } => match pattern {
+                Some(pattern) => self.pattern_test(pattern, access, ty)?,
+                None => Vec::new(),
+            },
+            Pattern::Literal(literal) => {
+                vec![format!("{access}==={}", self.stored(literal, ty, false)?.0)]
+            }
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let mut tests = Vec::new();
+                if let Some(start) = start {
+                    tests.push(format!("{access}>={}", self.stored(start, ty, false)?.0));
+                }
+                if let Some(end) = end {
+                    let operator = if *inclusive { "<=" } else { "<" };
+                    tests.push(format!(
+                        "{access}{operator}{}",
+                        self.stored(end, ty, false)?.0
+                    ));
+                }
+                tests
+            }
+            Pattern::Or(alternatives) => {
+                let mut any = Vec::new();
+                for alternative in alternatives {
+                    let tests = self.pattern_test(alternative, access, ty)?;
+                    if tests.is_empty() {
+                        return Ok(Vec::new());
+                    }
+                    any.push(conjunction(&tests));
+                }
+                vec![any.join("||")]
+            }
+            Pattern::Tuple(elements) => {
+                let mut tests = Vec::new();
+                for (element, access, ty) in self.sequence(elements, access, ty) {
+                    tests.extend(self.pattern_test(element, &access, &ty)?);
+                }
+                tests
+            }
+            Pattern::Slice(elements) => {
+                let length = elements.iter().filter(|p| !is_rest(p)).count();
+                let mut tests = if elements.iter().any(is_rest) {
+                    vec![format!("{access}.length>={length}")]
+                } else {
+                    vec![format!("{access}.length==={length}")]
+                };
+                for (element, access, ty) in self.sequence(elements, access, ty) {
+                    tests.extend(self.pattern_test(element, &access, &ty)?);
+                }
+                tests
+            }
+            Pattern::TupleStruct { name, fields } => {
+                let mut tests = self.variant_test(name, access);
+                let types = self.positional_types(name, ty)?;
+                for (n, field) in fields.iter().enumerate() {
+                    let ty = types.get(n).cloned().unwrap_or(Type::Unknown);
+                    let access = self.positional(name, access, n);
+                    tests.extend(self.pattern_test(field, &access, &ty)?);
+                }
+                tests
+            }
+            Pattern::Struct { name, fields } => {
+                let mut tests = self.variant_test(name, access);
+                let types = self.pattern_fields(name)?;
+                for (field, pattern) in fields {
+                    let ty = self.instantiate(ty, &field_type(&types, field));
+                    tests.extend(self.pattern_test(pattern, &format!("{access}.{field}"), &ty)?);
+                }
+                tests
+            }
+            Pattern::Path(name) => match self.variant(name) {
+                Some(_) => self.variant_test(name, access),
+                None => vec![format!("{access}==={}", self.path(name))],
+            },
+        })
+    }
+
+    // The names `pattern` binds, with the code reading them and their type. Matching
+    // through a `&mut` binds the scalars as `&mut`s to their places
+    fn pattern_bindings(
+        &mut self,
+        pattern: &Pattern,
+        access: &str,
+        ty: &Type,
+        reference: bool,
+    ) -> Result<Vec<Binding>, Box<dyn Error>> {
+        Ok(match pattern {
+            Pattern::Binding { name, pattern } => {
+                let mut bindings = vec![(
+                    name.to_string(),
+                    access.to_string(),
+                    ty.clone(),
+                    reference && is_scalar(ty),
+                )];
+                if let Some(pattern) = pattern {
+                    bindings.extend(self.pattern_bindings(pattern, access, ty, reference)?);
+                }
+                bindings
+            }
+            // Other values are shared by JS already
+            Pattern::RefMut(name) => {
+                vec![(
+                    name.to_string(),
+                    access.to_string(),
+                    ty.clone(),
+                    is_scalar(ty),
+                )]
+            }
+            Pattern::Or(alternatives) => {
+                let mut per_alternative = Vec::new();
+                for alternative in alternatives {
+                    let tests = self.pattern_test(alternative, access, ty)?;
+                    let bindings = self.pattern_bindings(alternative, access, ty, reference)?;
+                    per_alternative.push((tests, bindings));
+                }
+                let Some((_, first)) = per_alternative.first() else {
+                    return Ok(Vec::new());
+                };
+                let mut bindings = Vec::new();
+                for (name, code, ty, place) in first {
+                    let codes: Vec<(&Vec<String>, &String)> = per_alternative
+                        .iter()
+                        .filter_map(|(tests, bindings)| {
+                            bindings
+                                .iter()
+                                .find(|(other, ..)| other == name)
+                                .map(|(_, code, ..)| (tests, code))
+                        })
+                        .collect();
+                    if codes.iter().all(|(_, other)| *other == code) {
+                        bindings.push((name.to_string(), code.to_string(), ty.clone(), *place));
+                        continue;
+                    }
+                    if *place {
+                        return Err(format!(
+                            "Code generator Pattern: Can not bind {name} to a different place in each alternative"
+                        )
+                        .into());
+                    }
+                    // Each alternative finds the value somewhere else
+                    let mut select = codes.last().map(|(_, code)| code.to_string());
+                    for (tests, code) in codes.iter().rev().skip(1) {
+                        select = Some(format!(
+                            "{}?{code}:{}",
+                            conjunction(tests),
+                            select.unwrap_or_default()
+                        ));
+                    }
+                    bindings.push((
+                        name.to_string(),
+                        format!("({})", select.unwrap_or_default()),
+                        ty.clone(),
+                        false,
+                    ));
+                }
+                bindings
+            }
+            Pattern::Tuple(elements) | Pattern::Slice(elements) => {
+                let mut bindings = Vec::new();
+                for (element, access, ty) in self.sequence(elements, access, ty) {
+                    bindings.extend(self.pattern_bindings(element, &access, &ty, reference)?);
+                }
+                bindings
+            }
+            Pattern::TupleStruct { name, fields } => {
+                let types = self.positional_types(name, ty)?;
+                let mut bindings = Vec::new();
+                for (n, field) in fields.iter().enumerate() {
+                    let ty = types.get(n).cloned().unwrap_or(Type::Unknown);
+                    let access = self.positional(name, access, n);
+                    bindings.extend(self.pattern_bindings(field, &access, &ty, reference)?);
+                }
+                bindings
+            }
+            Pattern::Struct { name, fields } => {
+                let types = self.pattern_fields(name)?;
+                let mut bindings = Vec::new();
+                for (field, pattern) in fields {
+                    let ty = self.instantiate(ty, &field_type(&types, field));
+                    bindings.extend(self.pattern_bindings(
+                        pattern,
+                        &format!("{access}.{field}"),
+                        &ty,
+                        reference,
+                    )?);
+                }
+                bindings
+            }
+            _ => Vec::new(),
+        })
+    }
+
+    // Pairs the elements of a tuple or slice pattern with the code reading them,
+    // elements after a `..` are read from the end
+    fn sequence<'a>(
+        &self,
+        elements: &'a [Pattern],
+        access: &str,
+        ty: &Type,
+    ) -> Vec<(&'a Pattern, String, Type)> {
+        let rest = elements.iter().position(is_rest);
+        let after = rest.map(|rest| elements.len() - rest - 1).unwrap_or(0);
+        elements
+            .iter()
+            .enumerate()
+            .map(|(n, element)| {
+                let element_type = match ty {
+                    Type::Tuple(types) => types.get(n).cloned().unwrap_or(Type::Unknown),
+                    Type::Array(element, _) => *element.clone(),
+                    _ => Type::Unknown,
+                };
+                match rest {
+                    Some(rest) if n == rest => {
+                        let end = if after == 0 {
+                            String::new()
+                        } else {
+                            format!(", {access}.length-{after}")
+                        };
+                        (element, format!("{access}.slice({n}{end})"), ty.clone())
+                    }
+                    Some(rest) if n > rest => (
+                        element,
+                        format!("{access}[{access}.length-{}]", elements.len() - n),
+                        element_type,
+                    ),
+                    _ => (element, format!("{access}[{n}]"), element_type),
+                }
+            })
+            .collect()
+    }
+
+    // Rejects matches that miss values and warns about arms that can never run
+    fn check_match(&mut self, ty: &Type, arms: &[Arm]) -> Result<(), Box<dyn Error>> {
+        let types = [ty.clone()];
+        let mut rows = Vec::new();
+        for (n, arm) in arms.iter().enumerate() {
+            let pattern = self.lower(&arm.pattern, ty);
+            if self
+                .usefulness(&rows, std::slice::from_ref(&pattern), &types)
+                .is_empty()
+            {
+                self.warnings.push(format!(
+                    "Code generator Match: Unreachable pattern in arm {}",
+                    n + 1
+                ));
+            }
+            // A guard can fail, so the arm covers nothing for sure
+            if arm.guard.is_none() {
+                expand_or(&[pattern], &mut rows);
+            }
+        }
+        // Inside, only the constructors no arm names are listed as missing,
+        // but every constructor of the scrutinee itself is tried
+        let column: Vec<&Pat> = rows.iter().map(|row| &row[0]).collect();
+        let column_type = self.column_type(ty, &column);
+        let constructors = self.constructors(&column_type, &column);
+        let mut witnesses = Vec::new();
+        if constructors.iter().all(|c| self.arity(c, &column) == 0) {
+            // Without fields, the constructors no arm names are all that is missing
+            witnesses = self.usefulness(&rows, &[Pat::Wild], &types);
+        } else {
+            for constructor in constructors {
+                let fields = vec![Pat::Wild; self.arity(&constructor, &column)];
+                let q = Pat::Constructor(constructor, fields);
+                witnesses.extend(self.usefulness(&rows, &[q], &types));
+            }
+        }
+        let missing: Vec<String> = witnesses
+            .iter()
+            .map(|witness| self.witness(&witness[0], ty))
+            .collect();
+        // Like rustc, only the first three missing patterns are listed
+        let mut shown: Vec<String> = missing.iter().take(3).map(|m| format!("`{m}`")).collect();
+        let list = match shown.pop() {
+            None => return Ok(()),
+            Some(last) if shown.is_empty() => last,
+            Some(last) if missing.len() > 3 => format!(
+                "{}, {last} and {} more",
+                shown.join(", "),
+                missing.len() - 3
+            ),
+            Some(last) => format!("{} and {last}", shown.join(", ")),
+        };
+        Err(format!("Code generator Match: Non-exhaustive patterns: {list} not covered").into())
+    }
+
+    fn lower(&self, pattern: &Pattern, ty: &Type) -> Pat {
+        match pattern {
+            Pattern::Wildcard | Pattern::Rest | Pattern::RefMut(_) => Pat::Wild,
+            Pattern::Binding { pattern, .. } => match pattern {
+                Some(pattern) => self.lower(pattern, ty),
+                None => Pat::Wild,
+            },
+            Pattern::Literal(literal) => Pat::Constructor(literal_constructor(literal), Vec::new()),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let bound = |bound: &Option<Node>, open: i128| match bound {
+                    Some(Node::NumberLiteral(n)) => n.parse::<i128>().ok(),
+                    Some(_) => None,
+                    None => Some(open),
+                };
+                match (bound(start, i128::MIN), bound(end, i128::MAX)) {
+                    (Some(start), Some(end)) if *inclusive || end.checked_sub(1).is_some() => {
+                        let end = if *inclusive || end == i128::MAX {
+                            end
+                        } else {
+                            end - 1
+                        };
+                        Pat::Constructor(Constructor::Int(start, end), Vec::new())
+                    }
+                    _ => Pat::Constructor(Constructor::Opaque(format!("{pattern:?}")), Vec::new()),
+                }
+            }
+            Pattern::Or(alternatives) => Pat::Or(
+                alternatives
+                    .iter()
+                    .map(|alternative| self.lower(alternative, ty))
+                    .collect(),
+            ),
+            Pattern::Tuple(elements) => {
+                let types = match ty {
+                    Type::Tuple(types) => types.to_vec(),
+                    _ => Vec::new(),
+                };
+                match self.lower_fields(elements, &types) {
+                    Some(fields) => Pat::Constructor(Constructor::Single(None), fields),
+                    None => Pat::Wild,
+                }
+            }
+            Pattern::Slice(elements) => {
+                let element = match ty {
+                    Type::Array(element, _) => *element.clone(),
+                    _ => Type::Unknown,
+                };
+                let mut lowered = elements.split(is_rest).map(|part| {
+                    part.iter()
+                        .map(|pattern| self.lower(pattern, &element))
+                        .collect::<Vec<Pat>>()
+                });
+                let prefix = lowered.next().unwrap_or_default();
+                Pat::Slice(prefix, lowered.next())
+            }
+            Pattern::TupleStruct { name, fields } => {
+                let Some((constructor, types)) = self.named_constructor(name) else {
+                    return Pat::Constructor(Constructor::Opaque(name.to_string()), Vec::new());
+                };
+                match self.lower_fields(fields, &types) {
+                    Some(fields) => Pat::Constructor(constructor, fields),
+                    None => Pat::Constructor(constructor, vec![Pat::Wild; types.len()]),
+                }
+            }
+            Pattern::Struct { name, fields } => {
+                let Some((constructor, _)) = self.named_constructor(name) else {
+                    return Pat::Constructor(Constructor::Opaque(name.to_string()), Vec::new());
+                };
+                let declared = match self.pattern_fields(name) {
+                    Ok(Fields::Named(declared)) => declared,
+                    Ok(Fields::Tuple(types)) => types
+                        .into_iter()
+                        .enumerate()
+                        .map(|(n, ty)| (n.to_string(), ty))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                // Fields left out of the pattern match anything
+                let lowered = declared
+                    .iter()
+                    .map(
+                        |(field, ty)| match fields.iter().find(|(name, _)| name == field) {
+                            Some((_, pattern)) => self.lower(pattern, ty),
+                            None => Pat::Wild,
+                        },
+                    )
+                    .collect();
+                Pat::Constructor(constructor, lowered)
+            }
+            Pattern::Path(name) => match self.named_constructor(name) {
+                Some((constructor, types)) => {
+                    Pat::Constructor(constructor, vec![Pat::Wild; types.len()])
+                }
+                None => Pat::Constructor(Constructor::Opaque(self.path(name)), Vec::new()),
+            },
+        }
+    }
+
+    // Lowers positional fields, a `..` stands for as many wildcards as needed.
+    // Returns `None` when the number of fields is unknown
+    fn lower_fields(&self, fields: &[Pattern], types: &[Type]) -> Option<Vec<Pat>> {
+        let Some(rest) = fields.iter().position(is_rest) else {
+            return Some(
+                fields
+                    .iter()
+                    .enumerate()
+                    .map(|(n, field)| self.lower(field, types.get(n).unwrap_or(&Type::Unknown)))
+                    .collect(),
+            );
+        };
+        let missing = (types.len() + 1).checked_sub(fields.len())?;
+        let mut lowered = Vec::new();
+        for (n, field) in fields.iter().enumerate() {
+            if n == rest {
+                lowered.extend(vec![Pat::Wild; missing]);
+            } else {
+                let position = if n < rest { n } else { n + missing - 1 };
+                lowered.push(self.lower(field, types.get(position).unwrap_or(&Type::Unknown)));
+            }
+        }
+        Some(lowered)
+    }
+
+    // The constructor and field types of a variant or struct name
+    fn named_constructor(&self, name: &str) -> Option<(Constructor, Vec<Type>)> {
+        let (constructor, fields) = match self.variant(name) {
+            Some((owner, variant)) => {
+                let index = self
+                    .enums
+                    .get(&owner)?
+                    .iter()
+                    .position(|v| v.name == variant.name)?;
+                (Constructor::Variant(owner, index), variant.fields)
+            }
+            None => {
+                let name = self.resolve(name);
+                let fields = self.structs.get(&name)?.clone();
+                (Constructor::Single(Some(name)), fields)
+            }
+        };
+        Some((constructor, field_types(&fields)))
+    }
+
+    // The type of a column, taken from its patterns when the scrutinee's type is not known
+    fn column_type(&self, ty: &Type, column: &[&Pat]) -> Type {
+        let known = match ty.name() {
+            Some(name) => {
+                self.enums.contains_key(name)
+                    || self.structs.contains_key(name)
+                    || name == "bool"
+                    || integer_range(name).is_some()
+            }
+            None => matches!(ty, Type::Tuple(_) | Type::Array(..)),
+        };
+        if known {
+            return ty.clone();
+        }
+        for pattern in column {
+            match pattern {
+                Pat::Constructor(Constructor::Variant(owner, _), _) => return Type::named(owner),
+                Pat::Constructor(Constructor::Single(Some(name)), _) => return Type::named(name),
+                Pat::Constructor(Constructor::Single(None), fields) => {
+                    return Type::Tuple(vec![Type::Unknown; fields.len()])
+                }
+                Pat::Constructor(Constructor::Bool(_), _) => return Type::named("bool"),
+                // Like rustc, integers default to `i32`
+                Pat::Constructor(Constructor::Int(..), _) => return Type::named("i32"),
+                Pat::Slice(..) => return Type::Array(Box::new(Type::Unknown), None),
+                _ => {}
+            }
+        }
+        ty.clone()
+    }
+
+    // Every constructor of a type, split so that each one is either
+    // fully covered or not covered at all by each pattern of the column
+    fn constructors(&self, ty: &Type, column: &[&Pat]) -> Vec<Constructor> {
+        if let Some(variants) = ty.name().and_then(|name| self.enums.get(name)) {
+            let owner = ty.name().unwrap_or_default();
+            return (0..variants.len())
+                .map(|n| Constructor::Variant(owner.to_string(), n))
+                .collect();
+        }
+        if let Some((min, max)) = ty.name().and_then(integer_range) {
+            return split_range(min, max, column);
+        }
+        match ty {
+            Type::Named { name, .. } if name == "bool" => {
+                vec![Constructor::Bool(false), Constructor::Bool(true)]
+            }
+            Type::Named { name, .. } if self.structs.contains_key(name) => {
+                vec![Constructor::Single(Some(name.to_string()))]
+            }
+            Type::Tuple(_) => vec![Constructor::Single(None)],
+            // An array has exactly one length
+            Type::Array(_, Some(length)) => vec![Constructor::Slice(*length, false)],
+            Type::Array(..) => {
+                // Lengths up to the longest pattern are checked one by one, the rest together
+                let longest = column
+                    .iter()
+                    .filter_map(|pattern| match pattern {
+                        Pat::Slice(prefix, suffix) => {
+                            Some(prefix.len() + suffix.as_ref().map_or(0, |s| s.len()))
+                        }
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or(0);
+                let mut constructors: Vec<Constructor> = (0..=longest)
+                    .map(|n| Constructor::Slice(n, false))
+                    .collect();
+                constructors.push(Constructor::Slice(longest + 1, true));
+                constructors
+            }
+            _ => {
+                let mut constructors = Vec::new();
+                for pattern in column {
+                    if let Pat::Constructor(constructor, _) = pattern {
+                        if !constructors.contains(constructor) {
+                            constructors.push(constructor.clone());
+                        }
+                    }
+                }
+                constructors.push(Constructor::Other);
+                constructors
+            }
+        }
+    }
+
+    fn field_types(&self, constructor: &Constructor, ty: &Type, arity: usize) -> Vec<Type> {
+        let types = match constructor {
+            // The prelude leaves its payloads unknown, they are the type's generic arguments
+            Constructor::Variant(owner, index)
+                if matches!(owner.as_str(), "Option" | "Result")
+                    && !self.type_parameters.contains_key(owner) =>
+            {
+                match ty {
+                    Type::Named { generics, .. } => {
+                        generics.get(*index).cloned().into_iter().collect()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            Constructor::Variant(owner, index) => self
+                .enums
+                .get(owner)
+                .and_then(|variants| variants.get(*index))
+                .map(|variant| {
+                    let types = field_types(&variant.fields);
+                    types
+                        .iter()
+                        .map(|field| self.instantiate(ty, field))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Constructor::Single(Some(name)) => {
+                self.structs.get(name).map(field_types).unwrap_or_default()
+            }
+            Constructor::Single(None) => match ty {
+                Type::Tuple(types) => types.to_vec(),
+                _ => Vec::new(),
+            },
+            Constructor::Slice(length, _) => match ty {
+                Type::Array(element, _) => vec![*element.clone(); *length],
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+        let mut types = types;
+        types.resize(arity, Type::Unknown);
+        types
+    }
+
+    // The values `q` matches that no row matches, as witness patterns.
+    // `q` is useful when there is at least one
+    fn usefulness(&self, rows: &[Vec<Pat>], q: &[Pat], types: &[Type]) -> Vec<Vec<Pat>> {
+        let Some(head) = q.first() else {
+            return if rows.is_empty() {
+                vec![Vec::new()]
+            } else {
+                Vec::new()
+            };
+        };
+        if let Pat::Or(alternatives) = head {
+            let mut witnesses = Vec::new();
+            for alternative in alternatives {
+                let mut q = q.to_vec();
+                q[0] = alternative.clone();
+                witnesses.extend(self.usefulness(rows, &q, types));
+            }
+            return witnesses;
+        }
+        let mut expanded = Vec::new();
+        let rows = if rows.iter().any(|row| matches!(row[0], Pat::Or(_))) {
+            for row in rows {
+                expand_or(row, &mut expanded);
+            }
+            &expanded
+        } else {
+            rows
+        };
+        if matches!(head, Pat::Wild) && rows.iter().all(|row| matches!(row[0], Pat::Wild)) {
+            // Nothing in the column narrows the values down
+            let rows: Vec<Vec<Pat>> = rows.iter().map(|row| row[1..].to_vec()).collect();
+            return self
+                .usefulness(&rows, &q[1..], &types[1..])
+                .into_iter()
+                .map(|witness| [vec![Pat::Wild], witness].concat())
+                .collect();
+        }
+
+        let mut column: Vec<&Pat> = rows.iter().map(|row| &row[0]).collect();
+        column.push(head);
+        let ty = self.column_type(&types[0], &column);
+        // Only the values `q` matches can be missing, so a constructor in `q`
+        // is split by itself instead of every constructor of the type
+        let constructors = match head {
+            Pat::Constructor(Constructor::Int(start, end), _) => split_range(*start, *end, &column),
+            Pat::Constructor(constructor, _) => vec![constructor.clone()],
+            _ => self.constructors(&ty, &column),
+        };
+        let missing = match head {
+            Pat::Wild => missing(&constructors, &column),
+            _ => Vec::new(),
+        };
+        if !missing.is_empty() {
+            // Like the rows with a wildcard, the values no row names are only matched by
+            // the rest of the row, so the other constructors need not be tried
+            let rows: Vec<Vec<Pat>> = rows
+                .iter()
+                .filter(|row| matches!(row[0], Pat::Wild))
+                .map(|row| row[1..].to_vec())
+                .collect();
+            let mut witnesses = Vec::new();
+            for witness in self.usefulness(&rows, &q[1..], &types[1..]) {
+                for constructor in &missing {
+                    let fields = vec![Pat::Wild; self.arity(constructor, &column)];
+                    let mut witness = witness.clone();
+                    witness.insert(0, Pat::Constructor(constructor.clone(), fields));
+                    witnesses.push(witness);
+                }
+            }
+            return witnesses;
+        }
+        let mut witnesses = Vec::new();
+        for constructor in constructors {
+            let arity = self.arity(&constructor, &column);
+            let Some(q) = specialize(q, &constructor, arity) else {
+                continue;
+            };
+            let rows: Vec<Vec<Pat>> = rows
+                .iter()
+                .filter_map(|row| specialize(row, &constructor, arity))
+                .collect();
+            let types = [
+                self.field_types(&constructor, &ty, arity),
+                types[1..].to_vec(),
+            ]
+            .concat();
+            for witness in self.usefulness(&rows, &q, &types) {
+                let (fields, rest) = witness.split_at(arity);
+                let mut witness = vec![Pat::Constructor(constructor.clone(), fields.to_vec())];
+                witness.extend_from_slice(rest);
+                witnesses.push(witness);
+            }
+        }
+        witnesses
+    }
+
+    fn arity(&self, constructor: &Constructor, column: &[&Pat]) -> usize {
+        match constructor {
+            Constructor::Slice(length, _) => *length,
+            Constructor::Variant(..) | Constructor::Single(_) => column
+                .iter()
+                .find_map(|pattern| match pattern {
+                    Pat::Constructor(other, fields) if other == constructor => Some(fields.len()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| self.declared_arity(constructor)),
+            _ => 0,
+        }
+    }
+
+    // The number of fields a variant or struct declares
+    fn declared_arity(&self, constructor: &Constructor) -> usize {
+        match constructor {
+            // `Some`, `Ok` and `Err` have one field, `None` has none
+            Constructor::Variant(owner, index)
+                if matches!(owner.as_str(), "Option" | "Result")
+                    && !self.type_parameters.contains_key(owner) =>
+            {
+                usize::from(owner == "Result" || *index == 0)
+            }
+            Constructor::Variant(owner, index) => self
+                .enums
+                .get(owner)
+                .and_then(|variants| variants.get(*index))
+                .map_or(0, |variant| field_types(&variant.fields).len()),
+            Constructor::Single(Some(name)) => self
+                .structs
+                .get(name)
+                .map_or(0, |fields| field_types(fields).len()),
+            _ => 0,
+        }
+    }
+
+    // Writes a witness the way it would be written in Rust
+    fn witness(&self, pattern: &Pat, ty: &Type) -> String {
+        let Pat::Constructor(constructor, fields) = pattern else {
+            return "_".to_string();
+        };
+        let ty = self.column_type(ty, &[pattern]);
+        let types = self.field_types(constructor, &ty, fields.len());
+        let fields: Vec<String> = fields
+            .iter()
+            .zip(&types)
+            .map(|(field, ty)| self.witness(field, ty))
+            .collect();
+        let (name, declared) = match constructor {
+            Constructor::Variant(owner, index) => {
+                match self.enums.get(owner).and_then(|v| v.get(*index)) {
+                    // The prelude variants are written without their enum like rustc does
+                    Some(variant)
+                        if matches!(owner.as_str(), "Option" | "Result")
+                            && !self.type_parameters.contains_key(owner) =>
+                    {
+                        (variant.name.clone(), &variant.fields)
+                    }
+                    Some(variant) => (format!("{owner}::{}", variant.name), &variant.fields),
+                    None => return "_".to_string(),
+                }
+            }
+            Constructor::Single(Some(name)) => match self.structs.get(name) {
+                Some(declared) => (name.to_string(), declared),
+                None => return "_".to_string(),
+            },
+            Constructor::Single(None) if fields.len() == 1 => return format!("({},)", fields[0]),
+            Constructor::Single(None) => return format!("({})", fields.join(", ")),
+            Constructor::Bool(value) => return value.to_string(),
+            Constructor::Int(start, end) => {
+                let ty = ty.name().unwrap_or("i32");
+                let (min, max) = integer_range(ty).unwrap_or((i128::MIN, i128::MAX));
+                let bound = |value: i128| match value {
+                    _ if value == min => format!("{ty}::MIN"),
+                    _ if value == max => format!("{ty}::MAX"),
+                    _ => format!("{value}_{ty}"),
+                };
+                if start == end {
+                    return bound(*start);
+                }
+                return format!("{}..={}", bound(*start), bound(*end));
+            }
+            Constructor::Slice(_, true) if fields.is_empty() => return "[..]".to_string(),
+            Constructor::Slice(_, true) => return format!("[{}, ..]", fields.join(", ")),
+            Constructor::Slice(_, false) => return format!("[{}]", fields.join(", ")),
+            Constructor::Opaque(value) => return value.to_string(),
+            Constructor::Other => return "_".to_string(),
+        };
+        match declared {
+            Fields::Unit => name,
+            Fields::Tuple(_) => format!("{name}({})", fields.join(", ")),
+            Fields::Named(declared) => {
+                let mut written: Vec<String> = declared
+                    .iter()
+                    .zip(&fields)
+                    .filter(|(_, field)| *field != "_")
+                    .map(|((field, _), value)| format!("{field}: {value}"))
+                    .collect();
+                if written.len() < declared.len() {
+                    written.push("..".to_string());
+                }
+                format!("{name} {{ {} }}", written.join(", "))
+            }
+        }
+    }
+
+    fn is_fieldless_enum(&self, ty: &Type) -> bool {
+        ty.name()
+            .and_then(|name| self.enums.get(name))
+            .is_some_and(|variants| variants.iter().all(|v| matches!(v.fields, Fields::Unit)))
+    }
+
+    fn type_of(&self, node: &Node) -> Type {
+        match node {
+            Node::NumberLiteral(n) if is_float_literal(n) => Type::named("f64"),
+            Node::NumberLiteral(_) => Type::named("i32"),
+            Node::StringLiteral(_) => Type::named("str"),
+            Node::CharLiteral(_) => Type::named("char"),
+            Node::VeriableCall(name) => match (self.local(name), self.variant(name)) {
+                (Some(local), _) => local.ty.clone(),
+                _ if name == "true" || name == "false" => Type::named("bool"),
+                (None, Some((owner, _))) => Type::named(&owner),
+                (None, None) if self.structs.contains_key(&self.resolve(name)) => {
+                    Type::named(&self.resolve(name))
+                }
+                _ => self
+                    .numeric_constant(&self.resolve(name))
+                    .map_or(Type::Unknown, |(_, ty)| ty),
+            },
+            Node::FunctionCall { name, input, .. } if is_prelude_variant(&self.resolve(name)) => {
+                // The payload fills in the generic argument
+                let payload = input.first().map_or(Type::Unknown, |v| self.type_of(v));
+                match self.resolve(name).as_str() {
+                    "Result::Err" => Type::Named {
+                        name: "Result".to_string(),
+                        generics: vec![Type::Unknown, payload],
+                    },
+                    resolved => Type::Named {
+                        name: resolved.split("::").next().unwrap_or_default().to_string(),
+                        generics: vec![payload],
+                    },
+                }
+            }
+            Node::FunctionCall { name, .. }
+                if matches!(
+                    self.resolve(name).as_str(),
+                    "Vec::new" | "Vec::with_capacity"
+                ) =>
+            {
+                Type::Named {
+                    name: "Vec".to_string(),
+                    generics: vec![Type::Unknown],
+                }
+            }
+            Node::FunctionCall { name, .. } if self.resolve(name).starts_with("String::") => {
+                Type::named("String")
+            }
+            Node::FunctionCall { name, .. } if name.ends_with("::from_str_radix") => Type::Named {
+                name: "Result".to_string(),
+                generics: vec![
+                    Type::named(name.trim_end_matches("::from_str_radix")),
+                    Type::named("ParseIntError"),
+                ],
+            },
+            Node::FunctionCall { name, input, .. }
+                if matches!(name.as_str(), "Box::new" | "Rc::new") =>
+            {
+                Type::Named {
+                    name: name.split("::").next().unwrap_or_default().to_string(),
+                    generics: vec![input.first().map_or(Type::Unknown, |v| self.type_of(v))],
+                }
+            }
+            Node::FunctionCall { name, .. } if name == "Default::default" => {
+                self.expected.clone().unwrap_or(Type::Unknown)
+            }
+            Node::FunctionCall { name, input, .. } if is_collection(&self.resolve(name)) => {
+                let resolved = self.resolve(name);
+                let name = resolved.split("::").next().unwrap_or_default().to_string();
+                // `from` fills in the generic arguments
+                let element = input
+                    .first()
+                    .map_or(Type::Unknown, |values| element_type(&self.type_of(values)));
+                let generics = match element {
+                    Type::Tuple(types) if types.len() == 2 && name.ends_with("Map") => types,
+                    _ if name.ends_with("Map") => vec![Type::Unknown, Type::Unknown],
+                    element => vec![element],
+                };
+                Type::Named { name, generics }
+            }
+            Node::FunctionCall {
+                name,
+                generics,
+                input,
+            } => {
+                let resolved = self.resolve(name);
+                let values: Vec<(Option<&str>, &Node)> =
+                    input.iter().map(|value| (None, value)).collect();
+                if let Some((owner, variant)) = self.variant(name) {
+                    return self.constructed(&owner, &variant.fields, &values);
+                }
+                if let Some(fields) = self.structs.get(&resolved) {
+                    return self.constructed(&resolved, fields, &values);
+                }
+                if let Some((owner, function)) = resolved.rsplit_once("::") {
+                    // `Stack::<f64>::new()` names the type arguments of the owner
+                    let owner_type = Type::Named {
+                        name: owner.to_string(),
+                        generics: generics.clone(),
+                    };
+                    return self.instantiate(&owner_type, &self.method_output(owner, function));
+                }
+                match self.functions.get(&resolved) {
+                    Some(Some(output)) => output.clone(),
+                    _ => Type::Unknown,
+                }
+            }
+            Node::Call { callee, .. } => match callee.as_ref() {
+                Node::Closure { body, .. } => self.type_of(body),
+                // `Fn(i32) -> i32` and `fn(i32) -> i32` keep their output after the inputs
+                callee => match pointee(self.type_of(callee)) {
+                    Type::Named { name, generics } if name.starts_with("Fn") || name == "fn" => {
+                        generics.get(1).cloned().unwrap_or(Type::Tuple(Vec::new()))
+                    }
+                    _ => Type::Unknown,
+                },
+            },
+            Node::StructLiteral { name, fields } => {
+                let resolved = self.resolve(name);
+                let values: Vec<(Option<&str>, &Node)> = fields
+                    .iter()
+                    .map(|(field, value)| (Some(field.as_str()), value))
+                    .collect();
+                if let Some((owner, variant)) = self.variant(name) {
+                    return self.constructed(&owner, &variant.fields, &values);
+                }
+                match self.structs.get(&resolved) {
+                    Some(fields) => self.constructed(&resolved, fields, &values),
+                    None => Type::Unknown,
+                }
+            }
+            Node::MethodCall {
+                expression, method, ..
+            } if matches!(self.type_of(expression).name(), Some("Option" | "Result")) => {
+                prelude_output(&self.type_of(expression), method)
+            }
+            Node::Try(expression) => match self.type_of(expression) {
+                Type::Named { generics, .. } => generics.first().cloned().unwrap_or(Type::Unknown),
+                _ => Type::Unknown,
+            },
+            Node::MethodCall {
+                expression,
+                method,
+                generics,
+                input,
+            } if self.is_iterator_method(&self.type_of(expression), method) => {
+                let item = self.item_of(&self.type_of(expression));
+                let option = |ty: Type| Type::Named {
+                    name: "Option".to_string(),
+                    generics: vec![ty],
+                };
+                let vec = |ty: Type| Type::Named {
+                    name: "Vec".to_string(),
+                    generics: vec![ty],
+                };
+                match method.as_str() {
+                    "map" | "filter_map" | "flat_map" | "map_while" => iterator(Type::Unknown),
+                    "flatten" => iterator(item_type(&item)),
+                    "enumerate" => iterator(Type::Tuple(vec![Type::named("usize"), item])),
+                    "zip" => {
+                        let other = input
+                            .first()
+                            .map_or(Type::Unknown, |other| self.item_of(&self.type_of(other)));
+                        iterator(Type::Tuple(vec![item, other]))
+                    }
+                    "collect" => self.collect_target(generics, &item),
+                    "sum" | "product" => generics.first().cloned().unwrap_or(item),
+                    "fold" => input
+                        .first()
+                        .map_or(Type::Unknown, |init| self.type_of(init)),
+                    "any" | "all" => Type::named("bool"),
+                    "count" => Type::named("usize"),
+                    "position" => option(Type::named("usize")),
+                    "find_map" => option(Type::Unknown),
+                    "find" | "next" | "peek" | "nth" | "last" | "reduce" | "max" | "min"
+                    | "max_by_key" | "min_by_key" | "max_by" | "min_by" => option(item),
+                    "partition" => Type::Tuple(vec![vec(item.clone()), vec(item)]),
+                    "unzip" => Type::Tuple(vec![
+                        vec(generic_of_tuple(&item, 0)),
+                        vec(generic_of_tuple(&item, 1)),
+                    ]),
+                    "for_each" => Type::Tuple(Vec::new()),
+                    _ => iterator(item),
+                }
+            }
+            Node::Range { start, end, .. } => {
+                let known = start
+                    .iter()
+                    .chain(end)
+                    .find(|bound| self.integer_type(bound).is_some());
+                let bound = known.or(start.as_ref()).or(end.as_ref());
+                iterator(bound.map_or(Type::named("i32"), |bound| self.type_of(bound)))
+            }
+            Node::MethodCall {
+                expression,
+                method,
+                generics,
+                ..
+            } if is_string(&self.type_of(expression)) => {
+                let string = Type::named("String");
+                let str = Type::named("str");
+                let result = |ty: Type, error: &str| Type::Named {
+                    name: "Result".to_string(),
+                    generics: vec![ty, Type::named(error)],
+                };
+                match method.as_str() {
+                    "len" => Type::named("usize"),
+                    "is_empty" | "contains" | "starts_with" | "ends_with" => Type::named("bool"),
+                    "trim_matches" | "trim_start_matches" | "trim_end_matches" => str.clone(),
+                    "chars" => Type::Array(Box::new(Type::named("char")), None),
+                    "bytes" | "as_bytes" => Type::Array(Box::new(Type::named("u8")), None),
+                    "char_indices" => Type::Array(
+                        Box::new(Type::Tuple(vec![Type::named("usize"), Type::named("char")])),
+                        None,
+                    ),
+                    "split" | "splitn" | "rsplit" | "split_whitespace" | "lines" => {
+                        Type::Array(Box::new(str), None)
+                    }
+                    "split_once" | "rsplit_once" => Type::Named {
+                        name: "Option".to_string(),
+                        generics: vec![Type::Tuple(vec![str.clone(), str])],
+                    },
+                    "find" | "rfind" => Type::Named {
+                        name: "Option".to_string(),
+                        generics: vec![Type::named("usize")],
+                    },
+                    "trim" | "trim_start" | "trim_end" | "as_str" => str,
+                    "parse" => match self.parse_target(generics).as_deref() {
+                        Some("f32" | "f64") => result(
+                            generics.first().cloned().unwrap_or(Type::named("f64")),
+                            "ParseFloatError",
+                        ),
+                        Some("bool") => result(Type::named("bool"), "ParseBoolError"),
+                        Some("char") => result(Type::named("char"), "ParseCharError"),
+                        Some(name) => result(Type::named(name), "ParseIntError"),
+                        None => result(Type::Unknown, "ParseIntError"),
+                    },
+                    "push" | "push_str" | "insert" | "insert_str" => Type::Tuple(Vec::new()),
+                    _ => string,
+                }
+            }
+            Node::MethodCall { method, .. } if method == "to_string" => Type::named("String"),
+            Node::MethodCall {
+                expression, method, ..
+            } if pointee(self.type_of(expression)).name() == Some("char")
+                && char_type(method).is_some() =>
+            {
+                char_type(method).unwrap_or(Type::Unknown)
+            }
+            Node::MethodCall {
+                expression,
+                method,
+                input,
+                ..
+            } if is_vec(&self.type_of(expression)) => {
+                let ty = self.type_of(expression);
+                let element = element_type(&ty);
+                match method.as_str() {
+                    "len" | "count" => Type::named("usize"),
+                    "is_empty" | "contains" => Type::named("bool"),
+                    "pop" | "get" | "first" | "last" | "get_mut" | "first_mut" | "last_mut" => {
+                        Type::Named {
+                            name: "Option".to_string(),
+                            generics: vec![element],
+                        }
+                    }
+                    "remove" => element,
+                    "iter" | "iter_mut" | "into_iter" | "clone" => ty,
+                    "as_slice" | "as_mut_slice" | "to_vec" | "drain" | "split_off" => {
+                        Type::Array(Box::new(element), None)
+                    }
+                    "concat" | "join" if self.joins_strings(&element, input) => {
+                        Type::named("String")
+                    }
+                    "concat" => Type::Array(Box::new(element_type(&element)), None),
+                    "split_at" => Type::Tuple(vec![
+                        Type::Array(Box::new(element.clone()), None),
+                        Type::Array(Box::new(element), None),
+                    ]),
+                    "capacity" => Type::named("usize"),
+                    "binary_search" => Type::Named {
+                        name: "Result".to_string(),
+                        generics: vec![Type::named("usize"), Type::named("usize")],
+                    },
+                    "windows" | "chunks" => Type::Array(Box::new(ty), None),
+                    "push" | "insert" | "clear" | "truncate" | "extend" | "retain" | "dedup"
+                    | "reverse" | "swap" | "sort" | "sort_unstable" | "sort_by" | "sort_by_key"
+                    | "resize" => Type::Tuple(Vec::new()),
+                    _ => Type::Unknown,
+                }
+            }
+            Node::MethodCall {
+                expression, method, ..
+            } if is_map(&self.type_of(expression)) => {
+                let ty = self.type_of(expression);
+                let (key, value) = (generic_argument(&ty, 0), generic_argument(&ty, 1));
+                match method.as_str() {
+                    "len" => Type::named("usize"),
+                    "is_empty" | "contains_key" => Type::named("bool"),
+                    "insert" | "get" | "get_mut" | "remove" => Type::Named {
+                        name: "Option".to_string(),
+                        generics: vec![value],
+                    },
+                    "entry" => Type::Named {
+                        name: "Entry".to_string(),
+                        generics: vec![key, value],
+                    },
+                    "keys" | "into_keys" => Type::Array(Box::new(key), None),
+                    "values" | "into_values" => Type::Array(Box::new(value), None),
+                    "iter" | "iter_mut" | "into_iter" => {
+                        Type::Array(Box::new(Type::Tuple(vec![key, value])), None)
+                    }
+                    "clone" => ty,
+                    "clear" | "retain" | "extend" => Type::Tuple(Vec::new()),
+                    _ => Type::Unknown,
+                }
+            }
+            Node::MethodCall {
+                expression, method, ..
+            } if is_set(&self.type_of(expression)) => {
+                let element = element_type(&self.type_of(expression));
+                match method.as_str() {
+                    "len" => Type::named("usize"),
+                    "is_empty" | "contains" | "insert" | "remove" | "is_subset" | "is_superset"
+                    | "is_disjoint" => Type::named("bool"),
+                    "iter"
+                    | "into_iter"
+                    | "union"
+                    | "intersection"
+                    | "difference"
+                    | "symmetric_difference" => Type::Array(Box::new(element), None),
+                    "clone" => self.type_of(expression),
+                    "clear" | "retain" | "extend" => Type::Tuple(Vec::new()),
+                    _ => Type::Unknown,
+                }
+            }
+            Node::MethodCall {
+                expression, method, ..
+            } if self.type_of(expression).name() == Some("VecDeque") => {
+                let element = element_type(&self.type_of(expression));
+                match method.as_str() {
+                    "len" => Type::named("usize"),
+                    "is_empty" | "contains" => Type::named("bool"),
+                    "pop_back" | "pop_front" | "front" | "back" | "get" => Type::Named {
+                        name: "Option".to_string(),
+                        generics: vec![element],
+                    },
+                    "iter" => Type::Array(Box::new(element), None),
+                    "clone" => self.type_of(expression),
+                    "push_back" | "push_front" | "clear" | "extend" => Type::Tuple(Vec::new()),
+                    _ => Type::Unknown,
+                }
+            }
+            // `f.debug_struct("Point").field("x", &self.x).finish()`
+            Node::MethodCall {
+                expression, method, ..
+            } if matches!(
+                self.type_of(expression).name(),
+                Some("Formatter" | "DebugBuilder")
+            ) =>
+            {
+                match method.as_str() {
+                    "write_str" | "write_fmt" | "pad" | "finish" => Type::Named {
+                        name: "Result".to_string(),
+                        generics: vec![Type::Tuple(Vec::new()), Type::named("Error")],
+                    },
+                    "alternate" | "sign_plus" => Type::named("bool"),
+                    _ => Type::named("DebugBuilder"),
+                }
+            }
+            Node::MethodCall {
+                expression, method, ..
+            } if self.type_of(expression).name() == Some("Entry") => match method.as_str() {
+                "and_modify" => self.type_of(expression),
+                _ => generic_argument(&self.type_of(expression), 1),
+            },
+            Node::MethodCall {
+                expression, method, ..
+            } if matches!(method.as_str(), "cmp" | "partial_cmp") => {
+                let ordering = Type::named("Ordering");
+                match (
+                    self.method_output(self.type_of(expression).name().unwrap_or_default(), method),
+                    method.as_str(),
+                ) {
+                    (Type::Unknown, "cmp") => ordering,
+                    (Type::Unknown, _) => Type::Named {
+                        name: "Option".to_string(),
+                        generics: vec![ordering],
+                    },
+                    (output, _) => output,
+                }
+            }
+            Node::MethodCall {
+                expression, method, ..
+            } if numeric_output(&pointee(self.type_of(expression)), method).is_some() => {
+                numeric_output(&pointee(self.type_of(expression)), method).unwrap_or(Type::Unknown)
+            }
+            Node::MethodCall {
+                expression, method, ..
+            } if method == "clone"
+                || matches!(method.as_str(), "max" | "min" | "clamp")
+                    && self
+                        .type_of(expression)
+                        .name()
+                        .is_some_and(|name| self.implements(name, "Ord")) =>
+            {
+                pointee(self.type_of(expression))
+            }
+            Node::MethodCall {
+                expression, method, ..
+            } => self.receiver_output(&self.type_of(expression), method),
+            Node::FieldAccess { expression, field } => {
+                let ty = pointee(self.type_of(expression));
+                if let (Type::Tuple(types), Ok(n)) = (&ty, field.parse::<usize>()) {
+                    return types.get(n).cloned().unwrap_or(Type::Unknown);
+                }
+                match ty.name().and_then(|n| self.structs.get(n)) {
+                    Some(fields) => self.instantiate(&ty, &field_type(fields, field)),
+                    None => Type::Unknown,
+                }
+            }
+            Node::Cast { ty, .. } => ty.clone(),
+            Node::MutableReference(expression) => self.type_of(expression),
+            Node::Deref(expression) => pointee(self.type_of(expression)),
+            Node::Macro { name, .. } if name == "format" => Type::named("String"),
+            Node::Macro { name, input } if name == "vec" => {
+                let element = match input.first() {
+                    Some(Node::ArrayRepeat { value, .. }) => self.type_of(value),
+                    Some(_) => self.element_literal_type(input),
+                    None => Type::Unknown,
+                };
+                Type::Named {
+                    name: "Vec".to_string(),
+                    generics: vec![element],
+                }
+            }
+            Node::Macro { .. } => Type::Tuple(Vec::new()),
+            Node::ArrayLiteral(elements) => match elements.first() {
+                Some(_) => Type::Array(
+                    Box::new(self.element_literal_type(elements)),
+                    Some(elements.len()),
+                ),
+                None => Type::Array(Box::new(Type::Unknown), Some(0)),
+            },
+            Node::ArrayRepeat { value, count } => {
+                Type::Array(Box::new(self.type_of(value)), literal_length(count))
+            }
+            Node::TupleLiteral(elements) => {
+                Type::Tuple(elements.iter().map(|n| self.type_of(n)).collect())
+            }
+            Node::Index { expression, index } => match (index.as_ref(), self.type_of(expression)) {
+                // A sub-slice has no fixed length
+                (Node::Range { .. }, ty) if is_vec(&ty) => {
+                    Type::Array(Box::new(element_type(&ty)), None)
+                }
+                (Node::Range { .. }, ty) => ty,
+                (_, ty) if self.overloaded(&ty, "Index", "index").is_some() => {
+                    self.receiver_output(&ty, "index")
+                }
+                (_, ty) if is_map(&ty) => generic_argument(&ty, 1),
+                (_, ty) => element_type(&ty),
+            },
+            Node::Binary { left, operator, .. }
+                if operator_trait(*operator).is_some_and(|(trait_name, method)| {
+                    trait_name != "PartialOrd"
+                        && self
+                            .overloaded(&self.type_of(left), trait_name, method)
+                            .is_some()
+                }) =>
+            {
+                let (_, method) = operator_trait(*operator).unwrap_or_default();
+                self.receiver_output(&self.type_of(left), method)
+            }
+            Node::Binary {
+                left,
+                operator,
+                right,
+            } => match operator {
+                // `2 * x` has the type of `x`
+                Operator::Add
+                | Operator::Subtract
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo
+                | Operator::BitAnd
+                | Operator::BitOr
+                | Operator::BitXor
+                    if matches!(left.as_ref(), Node::NumberLiteral(_)) =>
+                {
+                    self.type_of(right)
+                }
+                Operator::Add
+                | Operator::Subtract
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo
+                | Operator::BitAnd
+                | Operator::BitOr
+                | Operator::BitXor
+                | Operator::ShiftLeft
+                | Operator::ShiftRight => self.type_of(left),
+                _ => Type::named("bool"),
+            },
+            Node::Unary {
+                operator,
+                expression,
+            } if self
+                .overloaded(&self.type_of(expression), unary_trait(*operator).0, "")
+                .is_some() =>
+            {
+                self.receiver_output(&self.type_of(expression), unary_trait(*operator).1)
+            }
+            Node::Unary {
+                operator: Symbol::Exclamation,
+                expression,
+            } if !is_integer(&self.type_of(expression)) => Type::named("bool"),
+            Node::Unary { expression, .. } => self.type_of(expression),
+            Node::Block(body) => match body.last() {
+                Some(last) if is_expression(last) => self.type_of(last),
+                None => Type::Tuple(Vec::new()),
+                _ => Type::Unknown,
+            },
+            Node::IfStatement { body, .. } => self.type_of(&Node::Block(body.to_vec())),
+            Node::Match { arms, .. } => arms
+                .iter()
+                .map(|arm| self.type_of(&arm.body))
+                .find(|ty| *ty != Type::Unknown)
+                .unwrap_or(Type::Unknown),
+            _ => Type::Unknown,
+        }
+    }
+
+    // A field or method type of a generic type, with the type arguments of `owner` filled in
+    fn instantiate(&self, owner: &Type, ty: &Type) -> Type {
+        let (Some(parameters), Type::Named { generics, .. }) = (
+            owner.name().and_then(|name| self.type_parameters.get(name)),
+            owner,
+        ) else {
+            return ty.clone();
+        };
+        substitute(ty, parameters, generics)
+    }
+
+    // The type a struct literal or variant constructor builds, its type arguments are
+    // taken from the values of the fields
+    fn constructed(&self, owner: &str, fields: &Fields, values: &[(Option<&str>, &Node)]) -> Type {
+        let parameters = self.type_parameters.get(owner).cloned().unwrap_or_default();
+        let generics = parameters
+            .iter()
+            .map(|parameter| {
+                values
+                    .iter()
+                    .enumerate()
+                    .find_map(|(n, (field, value))| {
+                        let field = field.map_or_else(|| n.to_string(), str::to_string);
+                        let ty = self.type_of(value);
+                        bind_generic(&field_type(fields, &field), &ty, parameter)
+                    })
+                    .unwrap_or(Type::Unknown)
+            })
+            .collect();
+        Type::Named {
+            name: owner.to_string(),
+            generics,
+        }
+    }
+
+    // What a method returns on a value, `Self` keeps the type arguments of the value
+    fn receiver_output(&self, operand: &Type, method: &str) -> Type {
+        let operand = pointee(operand.clone());
+        let owner = operand.name().unwrap_or_default();
+        match self.method_output(owner, method) {
+            Type::Named { name, generics } if name == owner && generics.is_empty() => {
+                operand.clone()
+            }
+            output => self.instantiate(&operand, &output),
+        }
+    }
+
+    fn method_output(&self, owner: &str, method: &str) -> Type {
+        let output = match self.find_method(owner, method) {
+            Some(Node::Function { output, .. }) => output,
+            _ => None,
+        };
+        match output {
+            Some(Type::Named { name, .. }) if name == "Self" => Type::named(owner),
+            Some(output) => output,
+            // Methods `#[derive(...)]` generates
+            None => match method {
+                "clone" if self.implements(owner, "Clone") => Type::named(owner),
+                "default" if self.implements(owner, "Default") => Type::named(owner),
+                "eq" | "ne" if self.implements(owner, "PartialEq") => Type::named("bool"),
+                "cmp" if self.implements(owner, "Ord") => Type::named("Ordering"),
+                "partial_cmp" if self.implements(owner, "PartialOrd") => Type::Named {
+                    name: "Option".to_string(),
+                    generics: vec![Type::named("Ordering")],
+                },
+                _ => Type::Unknown,
+            },
+        }
+    }
+}
+
+// The variable a place like `a.b[0]` belongs to
+fn place_root(node: &Node) -> Option<&str> {
+    match node {
+        Node::VeriableCall(name) => Some(name),
+        Node::FieldAccess { expression, .. }
+        | Node::Index { expression, .. }
+        | Node::Deref(expression) => place_root(expression),
+        _ => None,
+    }
+}
+
+fn changes(input: &[Parameter], body: &[Node], mutating_methods: &[String]) -> Changes {
+    let mut changes = Changes::default();
+    // What a `&mut` points to is changed by whoever it is handed to
+    for parameter in input {
+        if parameter.mutable {
+            changes.mutated.push(parameter.name.to_string());
+        } else if parameter.name != "self" {
+            changes.owned.push(parameter.name.to_string());
+        }
+    }
+    if let Some(tail) = body.last().filter(|node| is_expression(node)) {
+        returned_roots(tail, &mut changes.returned);
+    }
+    for node in body {
+        collect_changes(node, mutating_methods, &mut changes);
+    }
+    changes
+}
+
+fn collect_changes(node: &Node, mutating_methods: &[String], changes: &mut Changes) {
+    let mutated = match node {
+        // Replacing a whole variable does not change the value it held
+        Node::Assignment { target, .. } if !matches!(target.as_ref(), Node::VeriableCall(_)) => {
+            place_root(target)
+        }
+        // `a += b` can change `a` in place through `AddAssign`
+        Node::CompoundAssignment { target, .. } => place_root(target),
+        Node::MutableReference(expression) => place_root(expression),
+        Node::MethodCall {
+            expression, method, ..
+        } if MUTATING_METHODS.contains(&method.as_str()) || mutating_methods.contains(method) => {
+            place_root(expression)
+        }
+        _ => None,
+    };
+    if let Some(root) = mutated {
+        changes.mutated.push(root.to_string());
+    }
+    match node {
+        Node::Veriable {
+            name,
+            value: Some(value),
+            ..
+        } if matches!(value.as_ref(), Node::MutableReference(_)) => {
+            changes.mutated.push(name.to_string())
+        }
+        Node::Veriable { name, .. } => changes.owned.push(name.to_string()),
+        Node::Return(Some(value)) => returned_roots(value, &mut changes.returned),
+        Node::Closure { body, .. } => returned_roots(body, &mut changes.returned),
+        _ => {}
+    }
+    for child in children(node) {
+        collect_changes(child, mutating_methods, changes);
+    }
+}
+
+// The variables whose values an expression evaluates to, through its branches
+fn returned_roots(node: &Node, roots: &mut Vec<String>) {
+    match node {
+        Node::IfStatement {
+            body,
+            else_body: Some(else_body),
+            ..
+        } => {
+            for body in [body, else_body] {
+                if let Some(tail) = body.last().filter(|node| is_expression(node)) {
+                    returned_roots(tail, roots);
+                }
+            }
+        }
+        Node::Block(body) => {
+            if let Some(tail) = body.last().filter(|node| is_expression(node)) {
+                returned_roots(tail, roots);
+            }
+        }
+        Node::Match { arms, .. } => {
+            for arm in arms {
+                returned_roots(&arm.body, roots);
+            }
+        }
+        node => roots.extend(place_root(node).map(|root| root.to_string())),
+    }
+}
+
+// The expressions directly inside a node
+fn children(node: &Node) -> Vec<&Node> {
+    match node {
+        Node::Veriable { value, .. } => value.iter().map(|v| v.as_ref()).collect(),
+        Node::IfStatement {
+            condition,
+            body,
+            else_body,
+            ..
+        } => {
+            let mut nodes = vec![condition.as_ref()];
+            nodes.extend(body);
+            nodes.extend(else_body.iter().flatten());
+            nodes
+        }
+        Node::WhileLoop {
+            condition, body, ..
+        } => [vec![condition.as_ref()], body.iter().collect()].concat(),
+        Node::LetPattern {
+            value, else_body, ..
+        } => [vec![value.as_ref()], else_body.iter().flatten().collect()].concat(),
+        Node::ForLoop { iterable, body, .. } => {
+            [vec![iterable.as_ref()], body.iter().collect()].concat()
+        }
+        Node::Function { body, .. } | Node::Impl { body, .. } | Node::Block(body) => {
+            body.iter().collect()
+        }
+        Node::Call { callee, input } => [vec![callee.as_ref()], input.iter().collect()].concat(),
+        Node::FunctionCall { input, .. }
+        | Node::Macro { input, .. }
+        | Node::ArrayLiteral(input)
+        | Node::TupleLiteral(input) => input.iter().collect(),
+        Node::Match { expression, arms } => {
+            let mut nodes = vec![expression.as_ref()];
+            for arm in arms {
+                nodes.extend(arm.guard.iter());
+                nodes.push(&arm.body);
+            }
+            nodes
+        }
+        Node::Return(value) => value.iter().map(|v| v.as_ref()).collect(),
+        Node::Assignment { target, value } | Node::CompoundAssignment { target, value, .. } => {
+            vec![target, value]
+        }
+        Node::Binary { left, right, .. } => vec![left, right],
+        Node::Unary { expression, .. }
+        | Node::Cast { expression, .. }
+        | Node::FieldAccess { expression, .. }
+        | Node::MutableReference(expression)
+        | Node::Deref(expression) => vec![expression],
+        Node::MethodCall {
+            expression, input, ..
+        } => [vec![expression.as_ref()], input.iter().collect()].concat(),
+        Node::Index { expression, index } => vec![expression, index],
+        Node::Range { start, end, .. } => start.iter().chain(end).map(|n| n.as_ref()).collect(),
+        Node::ArrayRepeat { value, count } => vec![value, count],
+        Node::StructLiteral { fields, .. } => fields.iter().map(|(_, value)| value).collect(),
+        Node::Closure { body, .. } | Node::Try(body) => vec![body],
+        _ => Vec::new(),
+    }
+}
+
+// Every name used as a variable or called as a function inside a node
+fn identifiers(node: &Node, names: &mut Vec<String>) {
+    match node {
+        Node::VeriableCall(name) | Node::FunctionCall { name, .. } => names.push(name.to_string()),
+        Node::Macro { input, .. } => names.extend(format_captures(input)),
+        _ => {}
+    }
+    for child in children(node) {
+        identifiers(child, names);
+    }
+}
+
+// The items of an impl with associated types like `Self::Output` replaced by what the
+// impl sets them to, impls for different traits of a type can set them differently
+fn associated_types(body: &[Node]) -> Vec<Node> {
+    let (names, types): (Vec<String>, Vec<Type>) = body
+        .iter()
+        .filter_map(|node| match node {
+            Node::TypeAlias { name, ty } => Some((name.to_string(), ty.clone())),
+            _ => None,
+        })
+        .unzip();
+    body.iter()
+        .map(|node| match node {
+            Node::Function {
+                name,
+                generics,
+                input,
+                output,
+                body,
+            } => Node::Function {
+                name: name.to_string(),
+                generics: generics.clone(),
+                input: input
+                    .iter()
+                    .map(|parameter| Parameter {
+                        name: parameter.name.to_string(),
+                        ty: substitute(&parameter.ty, &names, &types),
+                        mutable: parameter.mutable,
+                    })
+                    .collect(),
+                output: output.as_ref().map(|ty| substitute(ty, &names, &types)),
+                body: body.clone(),
+            },
+            node => node.clone(),
+        })
+        .collect()
+}
+
+// A body that assigns `value` to the place it would have returned
+fn assign_tail(body: &[Node], value: &Node) -> Vec<Node> {
+    let mut body = body.to_vec();
+    let Some(last) = body.pop() else {
+        return body;
+    };
+    body.push(match last {
+        Node::IfStatement {
+            condition,
+            pattern,
+            body: then_body,
+            else_body,
+        } => Node::IfStatement {
+            condition,
+            pattern,
+            body: assign_tail(&then_body, value),
+            else_body: else_body.map(|else_body| assign_tail(&else_body, value)),
+        },
+        Node::Match { expression, arms } => Node::Match {
+            expression,
+            arms: arms
+                .into_iter()
+                .map(|arm| Arm {
+                    body: Node::Block(assign_tail(&[arm.body], value)),
+                    ..arm
+                })
+                .collect(),
+        },
+        Node::Block(block) => Node::Block(assign_tail(&block, value)),
+        Node::Return(Some(place)) => Node::Assignment {
+            target: place,
+            value: Box::new(value.clone()),
+        },
+        place => Node::Assignment {
+            target: Box::new(place),
+            value: Box::new(value.clone()),
+        },
+    });
+    body
+}
+
+// Display and Debug both name their method `fmt`, so the class gets them as `$display`
+// and `$debug` which the formatting runtime calls
+fn formatting_impl(trait_name: Option<&str>, body: Vec<Node>) -> Vec<Node> {
+    let method = match trait_name {
+        Some("Display") => "$display",
+        Some("Debug") => "$debug",
+        _ => return body,
+    };
+    body.into_iter()
+        .map(|node| match node {
+            Node::Function {
+                name,
+                generics,
+                input,
+                output,
+                body,
+            } if name == "fmt" => Node::Function {
+                name: method.to_string(),
+                generics,
+                input,
+                output,
+                body,
+            },
+            node => node,
+        })
+        .collect()
+}
+
+// The std::ops trait and method behind `-x` and `!x`
+fn unary_trait(operator: Symbol) -> (&'static str, &'static str) {
+    if operator == Symbol::Minus {
+        ("Neg", "neg")
+    } else {
+        ("Not", "not")
+    }
+}
+
+// The std::ops trait and method behind an operator
+fn operator_trait(operator: Operator) -> Option<(&'static str, &'static str)> {
+    Some(match operator {
+        Operator::Add => ("Add", "add"),
+        Operator::Subtract => ("Sub", "sub"),
+        Operator::Multiply => ("Mul", "mul"),
+        Operator::Divide => ("Div", "div"),
+        Operator::Modulo => ("Rem", "rem"),
+        Operator::BitAnd => ("BitAnd", "bitand"),
+        Operator::BitOr => ("BitOr", "bitor"),
+        Operator::BitXor => ("BitXor", "bitxor"),
+        Operator::ShiftLeft => ("Shl", "shl"),
+        Operator::ShiftRight => ("Shr", "shr"),
+        Operator::Lesser | Operator::LesserEqual | Operator::Greater | Operator::GreaterEqual => {
+            ("PartialOrd", "partial_cmp")
+        }
+        _ => return None,
+    })
+}
+
+// Replaces the generic `parameters` in `ty` with their `arguments`
+fn substitute(ty: &Type, parameters: &[String], arguments: &[Type]) -> Type {
+    match ty {
+        Type::Named { name, generics } if generics.is_empty() => {
+            match parameters.iter().position(|parameter| parameter == name) {
+                Some(n) => arguments.get(n).cloned().unwrap_or(Type::Unknown),
+                None => ty.clone(),
+            }
+        }
+        Type::Named { name, generics } => Type::Named {
+            name: name.to_string(),
+            generics: generics
+                .iter()
+                .map(|ty| substitute(ty, parameters, arguments))
+                .collect(),
+        },
+        Type::Tuple(types) => Type::Tuple(
+            types
+                .iter()
+                .map(|ty| substitute(ty, parameters, arguments))
+                .collect(),
+        ),
+        Type::Array(element, length) => Type::Array(
+            Box::new(substitute(element, parameters, arguments)),
+            *length,
+        ),
+        Type::Unknown => Type::Unknown,
+    }
+}
+
+// What a `Box` points to, methods on it are called on the value
+fn pointee(ty: Type) -> Type {
+    match ty.name() {
+        Some("Box") => pointee(generic_argument(&ty, 0)),
+        _ => ty,
+    }
+}
+
+// The paths a function body calls with their turbofishes, `text.parse::<T>()` calls
+// `T::from_str`
+fn generic_uses(node: &Node, uses: &mut Vec<(String, Vec<Type>)>) {
+    match node {
+        Node::VeriableCall(name) => uses.push((name.to_string(), Vec::new())),
+        Node::FunctionCall { name, generics, .. } => {
+            uses.push((name.to_string(), generics.clone()))
+        }
+        Node::MethodCall {
+            method, generics, ..
+        } if method == "parse" => {
+            if let Some(name) = generics.first().and_then(|ty| ty.name()) {
+                uses.push((format!("{name}::from_str"), Vec::new()));
+            }
+        }
+        _ => {}
+    }
+    for child in children(node) {
+        generic_uses(child, uses);
+    }
+}
+
+// Records that a function calls `method` on the witness of `generic`, false if it already did
+fn add_witness(witnesses: &mut Vec<(String, Vec<String>)>, generic: &str, method: &str) -> bool {
+    match witnesses.iter_mut().find(|(name, _)| name == generic) {
+        Some((_, methods)) if methods.iter().any(|m| m == method) => false,
+        Some((_, methods)) => {
+            methods.push(method.to_string());
+            true
+        }
+        None => {
+            witnesses.push((generic.to_string(), vec![method.to_string()]));
+            true
+        }
+    }
+}
+
+// The type `generic` stands for when a value of `pattern` is `actual`
+fn bind_generic(pattern: &Type, actual: &Type, generic: &str) -> Option<Type> {
+    match (pattern, actual) {
+        (_, Type::Unknown) => None,
+        (Type::Named { name, generics }, _) if name == generic && generics.is_empty() => {
+            Some(actual.clone())
+        }
+        (Type::Array(pattern, _), Type::Array(..))
+        | (Type::Array(pattern, _), Type::Named { .. }) => {
+            bind_generic(pattern, &element_type(actual), generic)
+        }
+        (
+            Type::Named { generics, .. },
+            Type::Named {
+                generics: actual, ..
+            },
+        ) => generics
+            .iter()
+            .zip(actual)
+            .find_map(|(pattern, actual)| bind_generic(pattern, actual, generic)),
+        (Type::Tuple(patterns), Type::Tuple(actual)) => patterns
+            .iter()
+            .zip(actual)
+            .find_map(|(pattern, actual)| bind_generic(pattern, actual, generic)),
+        _ => None,
+    }
+}
+
+// Splits a format string into text and placeholders
+fn format_pieces(template: &str) -> Result<Vec<Piece>, Box<dyn Error>> {
+    let invalid = || format!("Code generator Format: Invalid format string {template:?}");
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => inner.push(c),
+                        None => return Err(invalid().into()),
+                    }
+                }
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                let placeholder = placeholder(&inner).ok_or_else(invalid)?;
+                pieces.push(Piece::Placeholder(placeholder));
+            }
+            '}' => return Err(invalid().into()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+// Parses `argument:[[fill]align][sign]['#']['0'][width]['.' precision][type]`
+fn placeholder(inner: &str) -> Option<Placeholder> {
+    let (argument, spec) = inner.split_once(':').unwrap_or((inner, ""));
+    let mut placeholder = Placeholder {
+        argument: format_argument(argument.trim())?,
+        ..Default::default()
+    };
+    let spec: Vec<char> = spec.chars().collect();
+    let mut i = 0;
+    let is_align = |c: Option<&char>| matches!(c, Some('<' | '^' | '>'));
+    if is_align(spec.get(1)) {
+        placeholder.fill = Some(spec[0]);
+        placeholder.align = Some(spec[1]);
+        i = 2;
+    } else if is_align(spec.first()) {
+        placeholder.align = Some(spec[0]);
+        i = 1;
+    }
+    if matches!(spec.get(i), Some('+' | '-')) {
+        placeholder.sign = spec[i] == '+';
+        i += 1;
+    }
+    if spec.get(i) == Some(&'#') {
+        placeholder.alternate = true;
+        i += 1;
+    }
+    // `0$` is a width taken from the first argument, not the zero flag
+    if spec.get(i) == Some(&'0') && spec.get(i + 1) != Some(&'$') {
+        placeholder.zero = true;
+        i += 1;
+    }
+    let (width, end) = count(&spec, i)?;
+    placeholder.width = width;
+    i = end;
+    if spec.get(i) == Some(&'.') {
+        if spec.get(i + 1) == Some(&'*') {
+            placeholder.precision = Some(Count::Next);
+            i += 2;
+        } else {
+            let (precision, end) = count(&spec, i + 1)?;
+            placeholder.precision = Some(precision?);
+            i = end;
+        }
+    }
+    placeholder.kind = spec[i..].iter().collect();
+    let kinds = ["", "?", "x", "X", "o", "b", "e", "E", "x?", "X?"];
+    kinds
+        .contains(&placeholder.kind.as_str())
+        .then_some(placeholder)
+}
+
+// `None` for `{}`, an index for `{0}` and a name for `{name}`
+fn format_argument(argument: &str) -> Option<Option<Argument>> {
+    if argument.is_empty() {
+        Some(None)
+    } else if let Ok(n) = argument.parse() {
+        Some(Some(Argument::Index(n)))
+    } else if is_identifier(argument) {
+        Some(Some(Argument::Name(argument.to_string())))
+    } else {
+        None
+    }
+}
+
+// A width or precision: `5`, `1$` or `name$`
+fn count(spec: &[char], start: usize) -> Option<(Option<Count>, usize)> {
+    let mut end = start;
+    while spec
+        .get(end)
+        .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+    {
+        end += 1;
+    }
+    let word: String = spec[start..end].iter().collect();
+    if spec.get(end) == Some(&'$') {
+        let argument = format_argument(&word)??;
+        return Some((Some(Count::Argument(argument)), end + 1));
+    }
+    // Anything else after the number is the type
+    let digits = word.chars().take_while(|c| c.is_ascii_digit()).count();
+    match word[..digits].parse() {
+        Ok(n) => Some((Some(Count::Literal(n)), start + digits)),
+        Err(_) => Some((None, start)),
+    }
+}
+
+// The names a format string reads straight from the scope, like `{x}`
+fn format_captures(input: &[Node]) -> Vec<String> {
+    let (Some(Node::StringLiteral(template)), arguments) = (input.first(), input.get(1..)) else {
+        return Vec::new();
+    };
+    let named: Vec<&str> = arguments
+        .into_iter()
+        .flatten()
+        .filter_map(|argument| match argument {
+            Node::Assignment { target, .. } => match target.as_ref() {
+                Node::VeriableCall(name) => Some(name.as_str()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    let mut captures = Vec::new();
+    for piece in format_pieces(template).unwrap_or_default() {
+        if let Piece::Placeholder(placeholder) = piece {
+            let counts = [placeholder.width, placeholder.precision];
+            let counted = counts
+                .into_iter()
+                .flatten()
+                .filter_map(|count| match count {
+                    Count::Argument(argument) => Some(argument),
+                    _ => None,
+                });
+            for argument in placeholder.argument.into_iter().chain(counted) {
+                if let Argument::Name(name) = argument {
+                    if !named.contains(&name.as_str()) && !captures.contains(&name) {
+                        captures.push(name);
+                    }
+                }
+            }
+        }
+    }
+    captures
+}
+
+// A JS string literal with the same contents
+fn js_string(text: &str) -> String {
+    let mut js = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                js.push('\\');
+                js.push(c);
+            }
+            _ => js.push_str(&js_escape(c)),
+        }
+    }
+    js.push('"');
+    js
+}
+
+// Escapes the text between the `${...}` of a JS template literal
+fn js_template(text: &str) -> String {
+    let mut js = String::new();
+    for c in text.chars() {
+        match c {
+            '`' | '\\' | '$' => {
+                js.push('\\');
+                js.push(c);
+            }
+            _ => js.push_str(&js_escape(c)),
+        }
+    }
+    js
+}
+
+fn js_escape(c: char) -> String {
+    match c {
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        c if c.is_control() => format!("\\u{:04x}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+fn is_prelude_variant(resolved: &str) -> bool {
+    matches!(resolved, "Option::Some" | "Result::Ok" | "Result::Err")
+}
+
+// The types the methods of `Option` and `Result` return
+fn prelude_output(ty: &Type, method: &str) -> Type {
+    let (name, generics) = match ty {
+        Type::Named { name, generics } => (name.as_str(), generics.as_slice()),
+        _ => return Type::Unknown,
+    };
+    let generic = |n: usize| generics.get(n).cloned().unwrap_or(Type::Unknown);
+    match method {
+        "is_some" | "is_none" | "is_ok" | "is_err" => Type::named("bool"),
+        "unwrap" | "expect" | "unwrap_or" | "unwrap_or_else" | "unwrap_or_default" => generic(0),
+        "unwrap_err" => generic(1),
+        "ok" | "err" => Type::Named {
+            name: "Option".to_string(),
+            generics: vec![generic(usize::from(method == "err"))],
+        },
+        "ok_or" | "ok_or_else" => Type::Named {
+            name: "Result".to_string(),
+            generics: vec![generic(0)],
+        },
+        "filter" | "or" | "or_else" | "clone" | "as_ref" | "as_mut" | "take" => ty.clone(),
+        "map" | "and_then" | "map_err" => Type::named(name),
+        _ => Type::Unknown,
+    }
+}
+
+// Finds `Option<Option<T>>` and `Option<()>` inside a type
+fn ambiguous_option_type(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Named { name, generics } => {
+            match (name.as_str(), generics.first()) {
+                ("Option", Some(Type::Named { name, .. })) if name == "Option" => {
+                    return Some("Option<Option<T>>".to_string())
+                }
+                ("Option", Some(Type::Tuple(inner))) if inner.is_empty() => {
+                    return Some("Option<()>".to_string())
+                }
+                _ => {}
+            }
+            generics.iter().find_map(ambiguous_option_type)
+        }
+        Type::Tuple(types) => types.iter().find_map(ambiguous_option_type),
+        Type::Array(element, _) => ambiguous_option_type(element),
+        Type::Unknown => None,
+    }
+}
+
+fn field_types(fields: &Fields) -> Vec<Type> {
+    match fields {
+        Fields::Unit => Vec::new(),
+        Fields::Tuple(types) => types.to_vec(),
+        Fields::Named(fields) => fields.iter().map(|(_, ty)| ty.clone()).collect(),
+    }
+}
+
+// What a method of a number type returns, `None` for the methods it does not have here
+fn numeric_output(ty: &Type, method: &str) -> Option<Type> {
+    if !is_integer(ty) && !is_float(ty) {
+        return None;
+    }
+    Some(match method {
+        "leading_zeros" | "trailing_zeros" | "count_ones" => Type::named("u32"),
+        "is_nan" | "is_finite" => Type::named("bool"),
+        _ if method.starts_with("checked_") => Type::Named {
+            name: "Option".to_string(),
+            generics: vec![ty.clone()],
+        },
+        "abs" | "signum" | "pow" | "powi" | "powf" | "min" | "max" | "clamp" | "rem_euclid"
+        | "sqrt" | "cbrt" | "floor" | "ceil" | "round" | "trunc" | "exp" | "ln" | "log10"
+        | "log2" | "sin" | "cos" | "tan" | "asin" | "acos" | "atan" | "atan2" | "sinh" | "cosh"
+        | "tanh" | "hypot" | "to_degrees" | "to_radians" => ty.clone(),
+        _ if method.starts_with("wrapping_") || method.starts_with("saturating_") => ty.clone(),
+        _ => return None,
+    })
+}
+
+fn integer_range(name: &str) -> Option<(i128, i128)> {
+    Some(match name {
+        "i8" => (i8::MIN as i128, i8::MAX as i128),
+        "i16" => (i16::MIN as i128, i16::MAX as i128),
+        "i32" => (i32::MIN as i128, i32::MAX as i128),
+        "i64" | "isize" => (i64::MIN as i128, i64::MAX as i128),
+        "i128" => (i128::MIN, i128::MAX),
+        "u8" => (0, u8::MAX as i128),
+        "u16" => (0, u16::MAX as i128),
+        "u32" => (0, u32::MAX as i128),
+        "u64" | "usize" => (0, u64::MAX as i128),
+        // Values above `i128::MAX` can not be written in a pattern here
+        "u128" => (0, i128::MAX),
+        _ => return None,
+    })
+}
+
+// The value of `i32::MIN` or `u8::MAX` as the digits of a literal
+fn integer_constant(path: &str) -> Option<String> {
+    let path = path
+        .strip_prefix("std::")
+        .or_else(|| path.strip_prefix("core::"))
+        .unwrap_or(path);
+    let (owner, constant) = path.split_once("::")?;
+    let (min, max) = integer_range(owner)?;
+    match (owner, constant) {
+        ("u128", "MAX") => Some(u128::MAX.to_string()),
+        (_, "MIN") => Some(min.to_string()),
+        (_, "MAX") => Some(max.to_string()),
+        _ => None,
+    }
+}
+
+fn literal_constructor(literal: &Node) -> Constructor {
+    match literal {
+        Node::NumberLiteral(n) => match n.parse::<i128>() {
+            Ok(value) => Constructor::Int(value, value),
+            Err(_) => Constructor::Opaque(n.to_string()),
+        },
+        Node::StringLiteral(s) => Constructor::Opaque(format!("{s:?}")),
+        Node::CharLiteral(c) => {
+            Constructor::Opaque(format!("{:?}", c.chars().next().unwrap_or_default()))
+        }
+        Node::VeriableCall(value) if value == "true" || value == "false" => {
+            Constructor::Bool(value == "true")
+        }
+        _ => Constructor::Opaque(format!("{literal:?}")),
+    }
+}
+
+// Splits `min..=max` at the bounds of the ranges in the column
+fn split_range(min: i128, max: i128, column: &[&Pat]) -> Vec<Constructor> {
+    let mut bounds = vec![min];
+    for pattern in column {
+        if let Pat::Constructor(Constructor::Int(start, end), _) = pattern {
+            if *start > min && *start <= max {
+                bounds.push(*start);
+            }
+            if *end >= min && *end < max {
+                bounds.push(end + 1);
+            }
+        }
+    }
+    bounds.sort();
+    bounds.dedup();
+    bounds
+        .iter()
+        .enumerate()
+        .map(|(n, start)| {
+            let end = bounds.get(n + 1).map_or(max, |next| next - 1);
+            Constructor::Int(*start, end)
+        })
+        .collect()
+}
+
+// Splits rows with an or-pattern in front into one row per alternative
+fn expand_or(row: &[Pat], rows: &mut Vec<Vec<Pat>>) {
+    match row.first() {
+        Some(Pat::Or(alternatives)) => {
+            for alternative in alternatives {
+                let mut row = row.to_vec();
+                row[0] = alternative.clone();
+                expand_or(&row, rows);
+            }
+        }
+        _ => rows.push(row.to_vec()),
+    }
+}
+
+// The constructors no pattern in the column names
+fn missing(constructors: &[Constructor], column: &[&Pat]) -> Vec<Constructor> {
+    // Split ranges are sorted, so the ranges in the column are merged and looked up
+    let mut ranges: Vec<(i128, i128)> = column
+        .iter()
+        .filter_map(|pattern| match pattern {
+            Pat::Constructor(Constructor::Int(start, end), _) => Some((*start, *end)),
+            _ => None,
+        })
+        .collect();
+    ranges.sort();
+    let mut merged: Vec<(i128, i128)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    constructors
+        .iter()
+        .filter(|constructor| match constructor {
+            Constructor::Int(start, _) => {
+                let n = merged.partition_point(|range| range.1 < *start);
+                merged.get(n).is_none_or(|range| range.0 > *start)
+            }
+            _ => column.iter().all(|pattern| {
+                let arity = match constructor {
+                    Constructor::Slice(length, _) => *length,
+                    _ => 0,
+                };
+                matches!(pattern, Pat::Wild)
+                    || specialize(std::slice::from_ref(*pattern), constructor, arity).is_none()
+            }),
+        })
+        .cloned()
+        .collect()
+}
+
+fn covers(pattern: &Constructor, constructor: &Constructor) -> bool {
+    match (pattern, constructor) {
+        (Constructor::Int(start, end), Constructor::Int(other_start, other_end)) => {
+            start <= other_start && other_end <= end
+        }
+        _ => pattern == constructor,
+    }
+}
+
+// The rest of a row once its first value is known to be built by `constructor`,
+// `None` when the row can not match such a value
+fn specialize(row: &[Pat], constructor: &Constructor, arity: usize) -> Option<Vec<Pat>> {
+    let fields = match &row[0] {
+        Pat::Wild => vec![Pat::Wild; arity],
+        Pat::Constructor(pattern, fields) if covers(pattern, constructor) => fields.to_vec(),
+        Pat::Slice(prefix, suffix) => match (suffix, constructor) {
+            (None, Constructor::Slice(length, false)) if prefix.len() == *length => prefix.to_vec(),
+            (Some(suffix), Constructor::Slice(length, _))
+                if prefix.len() + suffix.len() <= *length =>
+            {
+                let mut fields = prefix.to_vec();
+                fields.extend(vec![Pat::Wild; length - prefix.len() - suffix.len()]);
+                fields.extend_from_slice(suffix);
+                fields
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some([fields, row[1..].to_vec()].concat())
+}
+
+fn field_type(fields: &Fields, field: &str) -> Type {
+    match fields {
+        Fields::Named(fields) => fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, ty)| ty.clone())
+            .unwrap_or(Type::Unknown),
+        Fields::Tuple(types) => field
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| types.get(n).cloned())
+            .unwrap_or(Type::Unknown),
+        Fields::Unit => Type::Unknown,
+    }
+}
+
+// Enums become a class with a `$tag` naming the variant. Unit variants are frozen
+// singletons, data variants are built by static constructors under the enum's name
+fn enumeration(name: &str, variants: &[Variant], methods: &str) -> String {
+    let fieldless = variants.iter().all(|v| matches!(v.fields, Fields::Unit));
+    let mut program = format!(
+        "class {name}{{\nconstructor($tag, $fields){{\nthis.$tag=$tag;\nObject.assign(this, $fields);\n}}\n"
+    );
+    let mut discriminant = 0;
+    for variant in variants {
+        if let Some(value) = variant.discriminant {
+            discriminant = value;
+        }
+        let fields = &variant.fields;
+        let variant = &variant.name;
+        match fields {
+            Fields::Unit => {
+                let fields = if fieldless {
+                    format!("{{$disc:{discriminant}}}")
+                } else {
+                    "{}".to_string()
+                };
+                program.push_str(
+                    format!(
+                        "static {variant}=Object.freeze(new {name}(\"{variant}\", {fields}));\n"
+                    )
+                    .as_str(),
+                );
+            }
+            Fields::Tuple(types) => {
+                let input: Vec<String> = (0..types.len()).map(|n| format!("_{n}")).collect();
+                let fields: Vec<String> = (0..types.len()).map(|n| format!("{n}:_{n}")).collect();
+                program.push_str(
+                    format!(
+                        "static {variant}({}){{\nreturn new {name}(\"{variant}\", {{{}}});\n}}\n",
+                        input.join(", "),
+                        fields.join(", ")
+                    )
+                    .as_str(),
+                );
+            }
+            // Rebuilt so the fields keep their declaration order
+            Fields::Named(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, _)| format!("{field}:$fields.{field}"))
+                    .collect();
+                program.push_str(
+                    format!(
+                        "static {variant}($fields){{\nreturn new {name}(\"{variant}\", {{{}}});\n}}\n",
+                        fields.join(", ")
+                    )
+                    .as_str(),
+                )
+            }
+        }
+        discriminant += 1;
+    }
+    program.push_str(methods);
+    program.push_str("}\n");
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str) -> Result<Program, Box<dyn Error>> {
+        let tokens = tokenise(source.to_string())?;
+        let ast = parser(0, &tokens)?;
+        code_generator(ast.node, &Options::default())
+    }
+
+    fn not_covered(source: &str) -> String {
+        match compile(source) {
+            Ok(_) => String::new(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    // Compiles `source` and runs its `main` with node, returning what it prints
+    fn run_with(source: &str, options: &Options) -> String {
+        let tokens = tokenise(source.to_string()).unwrap();
+        let ast = parser(0, &tokens).unwrap();
+        let program = code_generator(ast.node, options).unwrap_or_else(|e| panic!("{e}"));
+        let mut node = std::process::Command::new("node")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .expect("node has to be installed to run the generated code");
+        let code = format!("{}\nmain();\n", program.code);
+        std::io::Write::write_all(&mut node.stdin.take().unwrap(), code.as_bytes()).unwrap();
+        let output = node.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn run(source: &str) -> String {
+        run_with(source, &Options::default())
+    }
+
+    #[test]
+    fn split_range_cuts_at_the_bounds_in_the_column() {
+        let low = Pat::Constructor(Constructor::Int(0, 9), Vec::new());
+        let high = Pat::Constructor(Constructor::Int(5, 300), Vec::new());
+        let wild = Pat::Wild;
+        assert_eq!(
+            split_range(0, 255, &[&low, &high, &wild]),
+            vec![
+                Constructor::Int(0, 4),
+                Constructor::Int(5, 9),
+                Constructor::Int(10, 255),
+            ]
+        );
+        assert_eq!(
+            split_range(0, 255, &[&wild]),
+            vec![Constructor::Int(0, 255)]
+        );
+    }
+
+    #[test]
+    fn usefulness_names_the_uncovered_values() {
+        let error = not_covered(
+            "fn main() { let x: Option<u8> = Some(3); match x { Some(0..=100) => {} None => {} } }",
+        );
+        assert!(error.contains("`Some(101_u8..=u8::MAX)` not covered"));
+        let error = not_covered(
+            "fn main() { let x = 5; match x { i32::MIN..=0 => {} 2..=i32::MAX => {} } }",
+        );
+        assert!(error.contains("`1_i32` not covered"), "{error}");
+        let error = not_covered("fn main() { let a = [1, 2, 3]; match a { [1, ..] => {} } }");
+        assert!(
+            error.contains("`[i32::MIN..=0_i32, _, _]` and `[2_i32..=i32::MAX, _, _]` not covered"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn usefulness_accepts_exhaustive_matches() {
+        let sources = [
+            "fn main() { let a: [i32; 2] = [1, 2]; match a { [a, b] => {} } }",
+            "fn main() { let a: [i32; 3] = [1, 2, 3]; match a { [first, .., last] => {} } }",
+            "fn main() { let x: Option<u8> = Some(3); match x { Some(0..=255) | None => {} } }",
+            "fn main() { let x = 5; match x { i32::MIN..=0 => {} 1..=i32::MAX => {} } }",
+            "fn main() { let x: u8 = 5; match x { 0..=9 => {} 5..=u8::MAX => {} } }",
+        ];
+        for source in sources {
+            let program = compile(source).unwrap_or_else(|e| panic!("{source}: {e}"));
+            assert!(
+                program.warnings.is_empty(),
+                "{source}: {:?}",
+                program.warnings
+            );
+        }
+    }
+
+    #[test]
+    fn exhaustive_match_runs_and_warns_on_unreachable_arms() {
+        let source = "fn main() {
+            for x in [0u8, 7, 200] {
+                let s = match x { 0 => \"zero\", 1..=9 => \"digit\", _ => \"big\" };
+                println!(\"{}\", s);
+            }
+        }";
+        assert_eq!(run(source), "zero\ndigit\nbig\n");
+        let program = compile("fn main() { let x = 1; match x { _ => {} 0 => {} } }").unwrap();
+        assert_eq!(program.warnings.len(), 1, "{:?}", program.warnings);
+        assert!(program.warnings[0].contains("Unreachable pattern"));
+    }
+
+    #[test]
+    fn enum_variants_with_data_run() {
+        let source = r#"
+        enum Shape {
+            Circle(f64),
+            Rect { w: f64, h: f64 },
+            Empty,
+        }
+
+        fn area(shape: &Shape) -> f64 {
+            match shape {
+                Shape::Circle(r) => 3.0 * r * r,
+                Shape::Rect { w, h } => w * h,
+                Shape::Empty => 0.0,
+            }
+        }
+
+        fn main() {
+            let shapes = vec![Shape::Circle(1.5), Shape::Rect { w: 2.0, h: 3.5 }, Shape::Empty];
+            for shape in &shapes {
+                println!("{}", area(shape));
+            }
+        }
+        "#;
+        assert_eq!(run(source), "6.75\n7\n0\n");
+    }
+
+    #[test]
+    fn match_patterns_bind_guard_and_destructure() {
+        let source = r#"
+        fn describe(pair: (i32, i32)) -> String {
+            match pair {
+                (0, 0) => "origin".to_string(),
+                (x, 0) | (0, x) if x < 0 => format!("negative axis {}", x),
+                (x @ 1..=9, y) => format!("small {} {}", x, y),
+                (x, y) if x == y => "diagonal".to_string(),
+                _ => "other".to_string(),
+            }
+        }
+
+        fn main() {
+            for pair in [(0, 0), (-3, 0), (0, -4), (5, 7), (12, 12), (20, 1)] {
+                println!("{}", describe(pair));
+            }
+            let values = [1, 2, 3, 4];
+            match values {
+                [first, .., last] => println!("{} {}", first, last),
+            }
+        }
+        "#;
+        assert_eq!(
+            run(source),
+            "origin\nnegative axis -3\nnegative axis -4\nsmall 5 7\ndiagonal\nother\n1 4\n"
+        );
+    }
+
+    #[test]
+    fn if_let_while_let_and_let_else_run() {
+        let source = r#"
+        fn parse(text: &str) -> i32 {
+            let Ok(value) = text.parse::<i32>() else {
+                return -1;
+            };
+            value
+        }
+
+        fn main() {
+            let mut stack = vec![1, 2, 3];
+            while let Some(top) = stack.pop() {
+                print!("{} ", top);
+            }
+            println!();
+            let maybe: Option<&str> = Some("7");
+            if let Some(text) = maybe {
+                println!("{} {}", parse(text), parse("x"));
+            } else {
+                println!("none");
+            }
+        }
+        "#;
+        assert_eq!(run(source), "3 2 1 \n7 -1\n");
+    }
+
+    #[test]
+    fn tuples_and_nested_arrays_are_values() {
+        let source = r#"
+        fn main() {
+            let mut grid = [[0; 3]; 2];
+            grid[1][2] = 7;
+            let pair = (1, ("two", 3.5));
+            let (a, (b, c)) = pair;
+            println!("{:?} {} {} {} {}", grid, a, b, c, (pair.1).0);
+            let mut copy = grid;
+            copy[0][0] = 1;
+            println!("{:?} {:?}", grid[0], copy[0]);
+        }
+        "#;
+        assert_eq!(
+            run(source),
+            "[[0, 0, 0], [0, 0, 7]] 1 two 3.5 two\n[0, 0, 0] [1, 0, 0]\n"
+        );
+    }
+
+    #[test]
+    fn closures_capture_by_reference_and_by_move() {
+        let source = r#"
+        fn apply<F: Fn(i32) -> i32>(f: F, x: i32) -> i32 {
+            f(x)
+        }
+
+        fn make_adder(n: i32) -> impl Fn(i32) -> i32 {
+            move |x| x + n
+        }
+
+        fn main() {
+            let mut count = 0;
+            let mut bump = || count += 1;
+            bump();
+            bump();
+            println!("{}", count);
+            let add = make_adder(5);
+            println!("{} {}", add(1), apply(|x| x * 3, 4));
+            let mut point = (1, 2);
+            let snapshot = move || point.0;
+            point.0 = 10;
+            println!("{} {}", snapshot(), point.0);
+        }
+        "#;
+        assert_eq!(run(source), "2\n6 12\n1 10\n");
+    }
+
+    #[test]
+    fn question_mark_returns_the_error_or_none() {
+        let source = r#"
+        fn half(x: i32) -> Result<i32, String> {
+            if x % 2 == 0 {
+                Ok(x / 2)
+            } else {
+                Err(format!("{} is odd", x))
+            }
+        }
+
+        fn quarter(x: i32) -> Result<i32, String> {
+            let h = half(x)?;
+            half(h)
+        }
+
+        fn first_even(values: &[i32]) -> Option<i32> {
+            let first = values.iter().find(|v| *v % 2 == 0)?;
+            Some(*first * 10)
+        }
+
+        fn main() {
+            println!("{:?} {:?}", quarter(12), quarter(6));
+            println!("{:?} {:?}", first_even(&[1, 4]), first_even(&[3]));
+            let name: Option<String> = None;
+            println!("{}", name.map(|n| n.len()).unwrap_or(0));
+        }
+        "#;
+        let expected = "Ok(3) Err(\"3 is odd\")\nSome(40) None\n0\n";
+        assert_eq!(run(source), expected);
+        let options = Options {
+            nullable_option: true,
+            ..Options::default()
+        };
+        assert_eq!(run_with(source, &options), expected);
+    }
+
+    #[test]
+    fn format_pieces_split_text_and_placeholders() {
+        let pieces = format_pieces("{{a}} {} and {name:>8}!").unwrap();
+        assert_eq!(pieces.len(), 5);
+        assert!(matches!(&pieces[0], Piece::Text(text) if text == "{a} "));
+        assert!(matches!(&pieces[1], Piece::Placeholder(p) if p.argument.is_none()));
+        assert!(matches!(&pieces[2], Piece::Text(text) if text == " and "));
+        assert!(matches!(&pieces[3], Piece::Placeholder(p)
+            if p.argument == Some(Argument::Name("name".to_string())) && p.align == Some('>')));
+        assert!(matches!(&pieces[4], Piece::Text(text) if text == "!"));
+        assert!(format_pieces("{").is_err());
+        assert!(format_pieces("}").is_err());
+        assert!(format_pieces("{:y}").is_err());
+    }
+
+    #[test]
+    fn placeholder_reads_every_part_of_the_spec() {
+        let p = placeholder("1:*^+#010.3x?").unwrap();
+        assert_eq!(p.argument, Some(Argument::Index(1)));
+        assert_eq!((p.fill, p.align), (Some('*'), Some('^')));
+        assert!(p.sign && p.alternate && p.zero);
+        assert!(matches!(p.width, Some(Count::Literal(10))));
+        assert!(matches!(p.precision, Some(Count::Literal(3))));
+        assert_eq!(p.kind, "x?");
+        let p = placeholder(":0$.*").unwrap();
+        assert!(!p.zero);
+        assert!(matches!(p.width, Some(Count::Argument(Argument::Index(0)))));
+        assert!(matches!(p.precision, Some(Count::Next)));
+    }
+
+    #[test]
+    fn format_specs_print_like_rust() {
+        let source = r#"
+        fn main() {
+            let pi = 3.14159;
+            let name = "ada";
+            println!("[{:>8.2}] [{:<6}] [{:^7}] [{:+}]", pi, name, "mid", 5);
+            println!("{:08.3} {:#x} {:#b} {:o} {:e}", -pi, 255, 5, 8, 1234.5);
+            println!("{0} {1} {0} {name}", 1, 2);
+            println!("{:?} {:?} {:5}|", "q\"s", Some(1.0), true);
+            let width = 6;
+            println!("[{:>width$}] [{:>1$}] [{:.*}]", 42, 7, 2, 1.23456);
+            eprintln!("to stderr");
+            print!("{}", format!("{}-{}\n", 'c', 2));
+        }
+        "#;
+        assert_eq!(run(source), "[    3.14] [ada   ] [  mid  ] [+5]\n-003.142 0xff 0b101 10 1.2345e3\n1 2 1 ada\n\"q\\\"s\" Some(1.0) true |\n[    42] [      7] [1.23]\nc-2\n");
+    }
+
+    #[test]
+    fn vec_methods_run_on_js_arrays() {
+        let source = r#"
+        fn main() {
+            let mut v = vec![5, 3, 8, 1];
+            v.push(9);
+            v.sort();
+            v.retain(|x| *x != 8);
+            v.insert(1, 2);
+            println!("{:?} {} {:?} {:?}", v, v.len(), v.first(), v.iter().position(|x| *x == 5));
+            let removed = v.remove(0);
+            v.extend(vec![7, 7]);
+            v.dedup();
+            println!("{} {:?} {} {:?}", removed, v, v.contains(&9), v.binary_search(&3));
+            let words = vec!["a", "b", "c"];
+            println!("{} {:?}", words.join("-"), words.iter().rev().collect::<Vec<_>>());
+        }
+        "#;
+        assert_eq!(run(source), "[1, 2, 3, 5, 9] 5 Some(1) Some(3)\n1 [2, 3, 5, 9, 7] true Ok(1)\na-b-c [\"c\", \"b\", \"a\"]\n");
+    }
+
+    #[test]
+    fn strings_count_utf8_bytes_and_chars() {
+        let source = r#"
+        fn main() {
+            let text = "héllo wörld";
+            println!("{} {}", text.len(), text.chars().count());
+            println!("{:?} {:?}", text.find('w'), text.char_indices().nth(2));
+            let upper: String = text.split_whitespace().map(|w| w.to_uppercase()).collect::<Vec<_>>().join("_");
+            println!("{} {}", upper, &text[0..3]);
+            let mut s = String::from("ab");
+            s.push('ç');
+            s.push_str("d");
+            println!("{} {} {}", s, s.replace("b", "B"), s.chars().rev().collect::<String>());
+            println!("{:?}", "a,b,,c".split(',').collect::<Vec<_>>());
+        }
+        "#;
+        assert_eq!(run(source), "13 11\nSome(7) Some((3, 'l'))\nHÉLLO_WÖRLD hé\nabçd aBçd dçba\n[\"a\", \"b\", \"\", \"c\"]\n");
+    }
+
+    #[test]
+    fn std_collections_run() {
+        let source = r#"
+        use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+        fn main() {
+            let mut counts: HashMap<char, usize> = HashMap::new();
+            for c in "banana".chars() {
+                *counts.entry(c).or_insert(0) += 1;
+            }
+            let mut sorted: BTreeMap<char, usize> = BTreeMap::new();
+            for (c, n) in &counts {
+                sorted.insert(*c, *n);
+            }
+            println!("{:?}", sorted);
+            let seen: HashSet<i32> = vec![1, 2, 2, 3].into_iter().collect();
+            println!("{} {}", seen.len(), seen.contains(&2));
+            let mut queue = VecDeque::new();
+            queue.push_back(1);
+            queue.push_back(2);
+            queue.push_front(0);
+            println!("{:?} {:?}", queue.pop_front(), queue);
+        }
+        "#;
+        assert_eq!(
+            run(source),
+            "{'a': 3, 'b': 1, 'n': 2}\n3 true\nSome(0) [1, 2]\n"
+        );
+    }
+
+    #[test]
+    fn struct_and_tuple_keys_compare_by_value() {
+        let source = r#"
+        use std::collections::{HashMap, HashSet};
+
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        fn main() {
+            let mut names = HashMap::new();
+            names.insert(Point { x: 1, y: 2 }, "a");
+            names.insert(Point { x: 1, y: 2 }, "b");
+            println!("{} {:?}", names.len(), names.get(&Point { x: 1, y: 2 }));
+            let mut visited = HashSet::new();
+            visited.insert((0, 1));
+            println!("{} {}", visited.insert((0, 1)), visited.contains(&(0, 1)));
+            println!("{}", Point { x: 3, y: 4 } == Point { x: 3, y: 4 });
+        }
+        "#;
+        assert_eq!(run(source), "1 Some(\"b\")\nfalse true\ntrue\n");
+    }
+
+    #[test]
+    fn iterator_adapters_run_lazy_and_fused() {
+        let source = r#"
+        fn main() {
+            let values = vec![1, 2, 3, 4, 5, 6];
+            let total: i32 = values.iter().filter(|x| *x % 2 == 0).map(|x| x * x).sum();
+            let pairs: Vec<(usize, char)> = "abc".chars().enumerate().skip(1).collect();
+            let zipped: Vec<i32> = values.iter().zip(values.iter().rev()).map(|(a, b)| a * b).take(3).collect();
+            println!("{} {:?} {:?}", total, pairs, zipped);
+            let small: Vec<&i32> = values.iter().take_while(|x| **x < 4).collect();
+            println!("{:?} {:?} {}", small, values.iter().max(), (1..=4).fold(0, |a, b| a * 10 + b));
+        }
+        "#;
+        let expected = "56 [(1, 'b'), (2, 'c')] [6, 10, 12]\n[1, 2, 3] Some(6) 1234\n";
+        assert_eq!(run(source), expected);
+        let options = Options {
+            fuse_iterators: true,
+            ..Options::default()
+        };
+        assert_eq!(run_with(source, &options), expected);
+    }
+
+    #[test]
+    fn user_iterators_take_the_adapters() {
+        let source = r#"
+        struct Fib {
+            a: u32,
+            b: u32,
+        }
+
+        impl Iterator for Fib {
+            type Item = u32;
+
+            fn next(&mut self) -> Option<u32> {
+                let value = self.a;
+                self.a = self.b;
+                self.b += value;
+                Some(value)
+            }
+        }
+
+        fn main() {
+            let first: Vec<u32> = Fib { a: 0, b: 1 }.take(8).collect();
+            println!("{:?}", first);
+            for n in (Fib { a: 0, b: 1 }).skip(10).step_by(2).take(2) {
+                println!("{}", n);
+            }
+        }
+        "#;
+        assert_eq!(run(source), "[0, 1, 1, 2, 3, 5, 8, 13]\n55\n144\n");
+    }
+
+    #[test]
+    fn trait_objects_and_primitive_impls_dispatch() {
+        let source = r#"
+        trait Animal {
+            fn name(&self) -> String;
+            fn speak(&self) -> String {
+                format!("{} makes a sound", self.name())
+            }
+        }
+
+        struct Dog;
+        struct Cat {
+            lives: u8,
+        }
+
+        impl Animal for Dog {
+            fn name(&self) -> String {
+                "Dog".to_string()
+            }
+            fn speak(&self) -> String {
+                "Woof".to_string()
+            }
+        }
+
+        impl Animal for Cat {
+            fn name(&self) -> String {
+                format!("Cat with {} lives", self.lives)
+            }
+        }
+
+        trait Zero {
+            fn zero() -> Self;
+        }
+
+        impl Zero for i32 {
+            fn zero() -> Self {
+                0
+            }
+        }
+
+        fn zero<T: Zero>() -> T {
+            T::zero()
+        }
+
+        fn main() {
+            let animals: Vec<Box<dyn Animal>> = vec![Box::new(Dog), Box::new(Cat { lives: 9 })];
+            for animal in animals.iter() {
+                println!("{}", animal.speak());
+            }
+            let z: i32 = zero();
+            println!("{}", z);
+        }
+        "#;
+        assert_eq!(run(source), "Woof\nCat with 9 lives makes a sound\n0\n");
+    }
+
+    #[test]
+    fn generic_impls_bounds_and_defaults_run() {
+        let source = r#"
+        use std::fmt::Display;
+
+        struct Pair<T> {
+            a: T,
+            b: T,
+        }
+
+        impl<T: PartialOrd + Copy> Pair<T> {
+            fn largest(&self) -> T {
+                if self.a > self.b {
+                    self.a
+                } else {
+                    self.b
+                }
+            }
+        }
+
+        fn show<T>(items: &[T]) -> String
+        where
+            T: Display,
+        {
+            items.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(",")
+        }
+
+        fn make<T: Default>() -> T {
+            T::default()
+        }
+
+        fn main() {
+            let p = Pair { a: 3, b: 9 };
+            let q = Pair { a: 2.5, b: -1.0 };
+            println!("{} {}", p.largest(), q.largest());
+            println!("{}", show(&["x", "y"]));
+            let n: i32 = make();
+            let s: String = make();
+            println!("{} {:?} {}", n, s, "42".parse::<u8>().unwrap() + 1);
+        }
+        "#;
+        assert_eq!(run(source), "9 2.5\nx,y\n0 \"\" 43\n");
+    }
+
+    #[test]
+    fn operator_traits_run() {
+        let source = r#"
+        use std::ops::{Add, AddAssign, Mul, Neg};
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct V2 {
+            x: i32,
+            y: i32,
+        }
+
+        impl Add for V2 {
+            type Output = V2;
+            fn add(self, other: V2) -> V2 {
+                V2 { x: self.x + other.x, y: self.y + other.y }
+            }
+        }
+
+        impl Mul<i32> for V2 {
+            type Output = V2;
+            fn mul(self, k: i32) -> V2 {
+                V2 { x: self.x * k, y: self.y * k }
+            }
+        }
+
+        impl Neg for V2 {
+            type Output = V2;
+            fn neg(self) -> V2 {
+                V2 { x: -self.x, y: -self.y }
+            }
+        }
+
+        impl AddAssign for V2 {
+            fn add_assign(&mut self, other: V2) {
+                self.x += other.x;
+                self.y += other.y;
+            }
+        }
+
+        fn main() {
+            let a = V2 { x: 1, y: 2 };
+            let b = V2 { x: 3, y: 4 };
+            let mut c = a + b * 2;
+            c += -a;
+            println!("{:?} {}", c, c == V2 { x: 6, y: 8 });
+        }
+        "#;
+        assert_eq!(run(source), "V2 { x: 6, y: 8 } true\n");
+    }
+
+    #[test]
+    fn display_and_debug_impls_format() {
+        let source = r#"
+        use std::fmt;
+
+        struct Money {
+            cents: i64,
+        }
+
+        impl fmt::Display for Money {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "${}.{:02}", self.cents / 100, self.cents % 100)
+            }
+        }
+
+        struct Tag(u8);
+
+        impl fmt::Debug for Tag {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_tuple("Tag").field(&self.0).finish()
+            }
+        }
+
+        fn main() {
+            let m = Money { cents: 1234 };
+            println!("{} [{}] {:?}", m, m.to_string(), Tag(7));
+            println!("{:?}", vec![Tag(1), Tag(2)]);
+        }
+        "#;
+        assert_eq!(run(source), "$12.34 [$12.34] Tag(7)\n[Tag(1), Tag(2)]\n");
+    }
+
+    #[test]
+    fn derived_traits_compare_sort_and_clone() {
+        let source = r#"
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+        struct Version {
+            major: u32,
+            minor: u32,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+        enum Level {
+            Low,
+            High(u8),
+        }
+
+        fn main() {
+            let mut versions = vec![Version { major: 1, minor: 5 }, Version { major: 0, minor: 9 }, Version::default()];
+            versions.sort();
+            println!("{:?}", versions);
+            let copy = versions.clone();
+            versions[0].minor = 3;
+            println!("{} {}", copy[0] == versions[0], Version { major: 1, minor: 0 } < Version { major: 1, minor: 2 });
+            println!("{:?} {}", Level::High(2).max(Level::Low), Level::High(1) < Level::High(3));
+        }
+        "#;
+        assert_eq!(run(source), "[Version { major: 0, minor: 0 }, Version { major: 0, minor: 9 }, Version { major: 1, minor: 5 }]\nfalse true\nHigh(2) true\n");
+    }
+
+    #[test]
+    fn copy_types_are_copied_on_assignment() {
+        let source = r#"
+        #[derive(Debug, Clone, Copy)]
+        struct P {
+            x: i32,
+        }
+
+        fn bump(mut p: P) -> P {
+            p.x += 1;
+            p
+        }
+
+        fn main() {
+            let a = P { x: 1 };
+            let mut b = a;
+            b.x = 5;
+            let c = bump(a);
+            println!("{:?} {:?} {:?}", a, b, c);
+            let arr = [1, 2, 3];
+            let mut other = arr;
+            other[0] = 9;
+            let v = vec![P { x: 0 }; 2];
+            let mut w = v.clone();
+            w[0].x = 4;
+            println!("{:?} {:?} {:?} {:?}", arr, other, v, w);
+        }
+        "#;
+        assert_eq!(run(source), "P { x: 1 } P { x: 5 } P { x: 2 }\n[1, 2, 3] [9, 2, 3] [P { x: 0 }, P { x: 0 }] [P { x: 4 }, P { x: 0 }]\n");
+    }
+
+    #[test]
+    fn integer_division_wraps_and_overflows() {
+        let source = r#"
+        fn main() {
+            let a: i32 = -7;
+            println!("{} {} {} {}", a / 2, a % 3, 7 / -2, a.rem_euclid(3));
+            let x: u8 = 250;
+            println!("{} {:?} {}", x.wrapping_add(10), x.checked_add(10), x.saturating_add(10));
+            let big: i32 = i32::MAX;
+            println!("{} {}", big.wrapping_mul(2), (big as i64) * 2);
+        }
+        "#;
+        assert_eq!(run(source), "-3 -1 -3 2\n4 None 255\n-2 4294967294\n");
+        let source = r#"
+        fn next(x: i32, y: u8) -> (i32, u8) {
+            (x + 1, y * 2)
+        }
+
+        fn main() {
+            println!("{:?}", next(i32::MAX, 200));
+        }
+        "#;
+        let options = Options {
+            release: true,
+            ..Options::default()
+        };
+        assert_eq!(run_with(source, &options), "(-2147483648, 144)\n");
+    }
+
+    #[test]
+    fn sixty_four_and_128_bit_integers_run_as_bigint() {
+        let source = r#"
+        fn main() {
+            let a: u64 = 1 << 40;
+            let b: i128 = -170141183460469231731687303715884105728;
+            println!("{} {} {}", a * 3, b + 1, u64::MAX);
+            let v = vec![1, 2];
+            let total: u64 = a + v[0];
+            println!("{} {}", total, 10_000_000_000i64 / 3);
+            let mut sum = 0u64;
+            for x in [5, 6].iter() {
+                sum += *x;
+            }
+            println!("{}", sum);
+        }
+        "#;
+        assert_eq!(run(source), "3298534883328 -170141183460469231731687303715884105727 18446744073709551615\n1099511627777 3333333333\n11\n");
+    }
+
+    #[test]
+    fn numeric_casts_truncate_and_saturate() {
+        let source = r#"
+        fn main() {
+            let n: i32 = -1;
+            println!("{} {} {} {}", n as u32, 300i32 as u8, -129i32 as i8, 3.99f64 as i32);
+            println!("{} {} {}", -3.9f64 as u8, 1e10 as i32, 'A' as u8 + 1);
+            println!("{} {} {:.3} {}", 2i32.pow(10), (10f64).sqrt() as u8, 2.0f64.powf(0.5), 7.5f64.floor());
+            println!("{} {} {}", (65u8 as char), i16::MAX, f64::MAX > 1e300);
+        }
+        "#;
+        assert_eq!(
+            run(source),
+            "4294967295 44 127 3\n0 2147483647 66\n1024 3 1.414 7\nA 32767 true\n"
+        );
+    }
+
+    #[test]
+    fn bitwise_operators_keep_the_integer_width() {
+        let source = r#"
+        fn main() {
+            let a: u8 = 0b1010_1100;
+            println!("{} {} {} {}", !a, a >> 2, a << 1, a & 0x0f);
+            println!("{} {} {}", -8i32 >> 1, 1u32 << 31, 0xffi32 ^ 0x0f);
+            let mut flags = 0u16;
+            flags |= 1 << 3;
+            flags ^= 0xff;
+            println!("{} {} {}", flags, flags.count_ones(), (-1i64 as u64) >> 60);
+        }
+        "#;
+        assert_eq!(run(source), "83 43 88 12\n-4 2147483648 240\n247 7 15\n");
+    }
+
+    #[test]
+    fn compound_assignment_and_mutable_references_run() {
+        let source = r#"
+        fn double(x: &mut i32) {
+            *x *= 2;
+        }
+
+        fn main() {
+            let mut x = 10;
+            x -= 3;
+            x *= -2;
+            x %= 5;
+            double(&mut x);
+            let flag = !(x < 0);
+            println!("{} {} {}", x, -x, flag);
+            let mut pairs = vec![(1, 2), (3, 4)];
+            for (a, b) in pairs.iter_mut() {
+                *a += *b;
+            }
+            let mut o = Some(1);
+            if let Some(v) = &mut o {
+                *v += 41;
+            }
+            let mut s = String::from("a");
+            s += "b";
+            println!("{:?} {:?} {}", pairs, o, s);
+        }
+        "#;
+        assert_eq!(run(source), "-8 8 false\n[(3, 2), (7, 4)] Some(42) ab\n");
+    }
 }
@@ -2,10 +2,25 @@ use std::fs;
 use std::io::prelude::*;
 use std::process;
 
-use basic_compiler::{code_generator, parser, tokenise};
+use basic_compiler::{code_generator, parser, tokenise, Options};
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let mut options = Options::default();
+    let mut args: Vec<String> = Vec::new();
+    for arg in std::env::args() {
+        match arg.as_str() {
+            "--nullable-option" => options.nullable_option = true,
+            "--fast-strings" => options.fast_strings = true,
+            "--fuse-iterators" => options.fuse_iterators = true,
+            "--release" => options.release = true,
+            "--bigint-usize" => options.bigint_usize = true,
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option {arg}");
+                process::exit(1);
+            }
+            _ => args.push(arg),
+        }
+    }
     if args.len() != 3 {
         eprintln!("Expected 2 argument recieved {}", args.len() - 1);
         process::exit(1);
@@ -20,21 +35,25 @@ fn main() {
         process::exit(1)
     });
 
-    let ast = parser(0, tokens).unwrap_or_else(|e| {
+    let ast = parser(0, &tokens).unwrap_or_else(|e| {
         eprintln!("Error {e}");
         process::exit(1)
     });
-    let program = code_generator(ast.node).unwrap_or_else(|e| {
+    let program = code_generator(ast.node, &options).unwrap_or_else(|e| {
         eprintln!("Error {e}");
         process::exit(1)
     });
 
+    for warning in &program.warnings {
+        eprintln!("Warning {warning}");
+    }
+
     let mut file = fs::File::create(&args[2]).unwrap_or_else(|e| {
         eprintln!("Error {e}");
         process::exit(1)
     });
 
-    file.write_all(program.as_bytes()).unwrap_or_else(|e| {
+    file.write_all(program.code.as_bytes()).unwrap_or_else(|e| {
         eprintln!("Error {e}");
         process::exit(1)
     });